@@ -0,0 +1,228 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk::{
+    prelude::*,
+    Application,
+    ApplicationWindow,
+    Box,
+    CenterBox,
+    Orientation,
+    Widget,
+    GestureClick,
+    EventControllerScroll,
+    EventControllerScrollFlags,
+    gdk,
+    glib::{
+        self,
+        ControlFlow,
+    },
+};
+
+use gtk4_layer_shell::LayerShell;
+
+use crate::{
+    style,
+    hyprland::{
+        self,
+        HyprEvent,
+        SubscriptionId,
+    },
+    config::{
+        BarConfig,
+        Edge,
+        Layer,
+        ExclusiveZone,
+    },
+    modules::{
+        BoxedModule,
+        ModuleContext,
+        Registry,
+    },
+};
+
+// everything a bar keeps running, stopped once the bar is destroyed
+#[derive(Default)]
+struct BarCleanup {
+    subscriptions: Vec<SubscriptionId>,
+    timers: Vec<glib::SourceId>,
+    modules: Vec<Rc<RefCell<BoxedModule>>>,
+}
+
+impl BarCleanup {
+    fn every<F: FnMut() + 'static>(&mut self, interval: Duration, mut f: F) {
+        self.timers.push(glib::timeout_add_local(interval,move || {
+            f();
+            ControlFlow::Continue
+        }));
+    }
+
+    fn subscribe<F: Fn(&HyprEvent) + 'static>(&mut self, f: F) {
+        self.subscriptions.push(hyprland::subscribe(f));
+    }
+
+    fn run(&mut self) {
+        for subscription in self.subscriptions.drain(..) {
+            hyprland::unsubscribe(subscription);
+        }
+        for timer in self.timers.drain(..) {
+            timer.remove();
+        }
+        /* modules subscribed elsewhere are kept alive by their own widgets' closures,
+         * Drop would never run for them
+         */
+        for module in self.modules.drain(..) {
+            module.borrow_mut().cleanup();
+        }
+    }
+}
+
+pub fn build_bar(
+    app: &Application,
+    monitor: &gdk::Monitor,
+    bar: &BarConfig,
+    registry: &Registry) -> ApplicationWindow {
+
+    // only workspaces on this output are shown
+    let connector = monitor.connector().map(|name| name.to_string());
+    let mut cleanup = BarCleanup::default();
+
+    let [left,center,right] = [&bar.left,&bar.center,&bar.right].map(|modules| {
+        let section = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .build();
+
+        for config in modules {
+            let context = ModuleContext {
+                config,
+                connector: connector.as_deref(),
+            };
+            // names are checked when the config is loaded
+            if let Some(module) = registry.build(&context) {
+                section.append(&attach(module,&mut cleanup));
+            }
+        }
+        section
+    });
+    right.append(&style::error_indicator());
+
+    let main_container = CenterBox::builder()
+        .hexpand(true)
+        .css_name("main-box")
+        .start_widget(&left)
+        .center_widget(&center)
+        .end_widget(&right)
+        .build();
+
+    if bar.height > 0 {
+        main_container.set_height_request(bar.height);
+    }
+
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .css_name(match bar.edge {
+            Edge::Top => "top-bar",
+            Edge::Bottom => "bottom-bar",
+        })
+        .child(&main_container)
+        .build();
+
+    LayerShell::init_layer_shell(&window);
+    LayerShell::set_layer(&window,match bar.layer {
+        Layer::Background => gtk4_layer_shell::Layer::Background,
+        Layer::Bottom => gtk4_layer_shell::Layer::Bottom,
+        Layer::Top => gtk4_layer_shell::Layer::Top,
+        Layer::Overlay => gtk4_layer_shell::Layer::Overlay,
+    });
+    LayerShell::set_monitor(&window,Some(monitor));
+
+    match bar.exclusive_zone {
+        ExclusiveZone::Auto => LayerShell::auto_exclusive_zone_enable(&window),
+        ExclusiveZone::None => LayerShell::set_exclusive_zone(&window,0),
+        ExclusiveZone::Fixed(size) => LayerShell::set_exclusive_zone(&window,size),
+    }
+
+    LayerShell::set_anchor(&window, match bar.edge {
+        Edge::Top => gtk4_layer_shell::Edge::Top,
+        Edge::Bottom => gtk4_layer_shell::Edge::Bottom,
+    }, true);
+    LayerShell::set_anchor(&window, gtk4_layer_shell::Edge::Left, true);
+    LayerShell::set_anchor(&window, gtk4_layer_shell::Edge::Right, true);
+
+    window.set_decorated(true);
+    window.present();
+
+    // stop updating once the output is gone
+    let cleanup = RefCell::new(cleanup);
+    window.connect_destroy(move |_| cleanup.borrow_mut().run());
+
+    window
+}
+
+/* an event for a module already busy, handling a signal its own update set off say,
+ * is handed over again once the main loop is idle
+ */
+fn deliver(module: &Rc<RefCell<BoxedModule>>, event: &HyprEvent) {
+    match module.try_borrow_mut() {
+        Ok(mut module) => module.event(event),
+        Err(_) => {
+            let (module,event) = (module.clone(),event.clone());
+            glib::idle_add_local_once(move || deliver(&module,&event));
+        },
+    }
+}
+
+/* hook a module up to its timer, hyprland events and pointer input
+ * a tick, click or scroll reaching a module that is busy is dropped
+ */
+fn attach(module: BoxedModule, cleanup: &mut BarCleanup) -> Widget {
+    let widget = module.widget();
+    let interval = module.interval();
+    let listens = module.listens();
+    let module = Rc::new(RefCell::new(module));
+    cleanup.modules.push(module.clone());
+
+    if let Some(interval) = interval {
+        module.borrow_mut().update();
+
+        let module = module.clone();
+        cleanup.every(interval,move || {
+            if let Ok(mut module) = module.try_borrow_mut() {
+                module.update();
+            }
+        });
+    }
+
+    if listens {
+        let module = module.clone();
+        cleanup.subscribe(move |event| deliver(&module,event));
+    }
+
+    // any button, buttons inside the module still get their own clicks
+    let click = GestureClick::builder()
+        .button(0)
+        .build();
+    let click_module = module.clone();
+    click.connect_released(move |gesture,_,_,_| {
+        if let Ok(mut module) = click_module.try_borrow_mut() {
+            module.click(gesture.current_button());
+        }
+    });
+    widget.add_controller(click);
+
+    let scroll = EventControllerScroll::new(
+        EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE);
+    scroll.connect_scroll(move |_,_,dy| {
+        if module.try_borrow_mut().is_ok_and(|mut module| module.scroll(dy)) {
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    });
+    widget.add_controller(scroll);
+
+    widget
+}
@@ -0,0 +1,531 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt,
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use serde::Deserialize;
+
+use crate::theme::{
+    self,
+    Theme,
+};
+
+/* ~/.config/epic-bar/config.toml
+ *
+ * each [[bar]] is shown on every output unless `monitors` lists
+ * connector names (same as `hyprctl monitors`), modules are either
+ * a name or a table with `module` set to the name plus its options:
+ *
+ *  [[bar]]
+ *  edge = "top"
+ *  monitors = ["DP-1"]
+ *  left = [{ module = "workspaces", count = 5 }]
+ *  right = ["cpu", "clock"]
+ *
+ * `theme` names one of theme::THEMES and [palette] overrides single values of it
+ */
+pub const DEFAULT_CONFIG: &str = r#"
+[[bar]]
+edge = "top"
+layer = "top"
+exclusive_zone = "auto"
+left = ["workspaces"]
+right = ["status", "cpu", "health", "memory", "battery", "clock"]
+
+[[bar]]
+edge = "bottom"
+layer = "top"
+exclusive_zone = "auto"
+left = ["windows"]
+"#;
+
+const BAR_KEYS: [&str;8] = [
+    "edge",
+    "layer",
+    "height",
+    "exclusive_zone",
+    "monitors",
+    "left",
+    "center",
+    "right",
+];
+
+const SECTIONS: [&str;3] = ["left", "center", "right"];
+
+// module names with the options each accepts, see modules::Registry::known
+pub type KnownModules<'a> = &'a [(&'a str, &'a [(&'a str, OptionKind)])];
+
+// the value a module option takes, read with the ModuleConfig method of the same name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptionKind {
+    Int,
+    String,
+    Bool,
+    Strings,
+}
+
+impl OptionKind {
+    fn accepts(&self, value: &toml::Value) -> bool {
+        match (self,value) {
+            (OptionKind::Int,toml::Value::Integer(_))
+            | (OptionKind::String,toml::Value::String(_))
+            | (OptionKind::Bool,toml::Value::Boolean(_))
+            | (OptionKind::Strings,toml::Value::String(_)) => true,
+            (OptionKind::Strings,toml::Value::Array(values)) => values.iter().all(toml::Value::is_str),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for OptionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionKind::Int => write!(f,"a whole number"),
+            OptionKind::String => write!(f,"a string"),
+            OptionKind::Bool => write!(f,"true or false"),
+            OptionKind::Strings => write!(f,"a string or a list of strings"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Config {
+    #[serde(rename = "bar", default)]
+    pub bars: Vec<BarConfig>,
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub palette: BTreeMap<String,String>,
+}
+
+/* BarConfig: one [[bar]] table
+ *
+ * height: 0 meaning as tall as the modules need
+ * monitors: connector names, empty meaning every output
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct BarConfig {
+    pub edge: Edge,
+    #[serde(default)]
+    pub layer: Layer,
+    #[serde(default)]
+    pub height: i32,
+    #[serde(default)]
+    pub exclusive_zone: ExclusiveZone,
+    #[serde(default)]
+    pub monitors: Vec<String>,
+    #[serde(default)]
+    pub left: Vec<ModuleConfig>,
+    #[serde(default)]
+    pub center: Vec<ModuleConfig>,
+    #[serde(default)]
+    pub right: Vec<ModuleConfig>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Top,
+    Bottom,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Background,
+    Bottom,
+    #[default]
+    Top,
+    Overlay,
+}
+
+// "auto" to reserve the height of the bar, "none" to draw over windows or a size in pixels
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(try_from = "toml::Value")]
+pub enum ExclusiveZone {
+    #[default]
+    Auto,
+    None,
+    Fixed(i32),
+}
+
+impl TryFrom<toml::Value> for ExclusiveZone {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::String(s) if s == "auto" => Ok(ExclusiveZone::Auto),
+            toml::Value::String(s) if s == "none" => Ok(ExclusiveZone::None),
+            toml::Value::Integer(n) => i32::try_from(n)
+                .map(ExclusiveZone::Fixed)
+                .map_err(|_| format!("exclusive_zone {n} is out of range")),
+            other => Err(format!("expected \"auto\", \"none\" or a number of pixels, found {other}")),
+        }
+    }
+}
+
+/* ModuleConfig: a module name with the rest of its table as options */
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "toml::Value")]
+pub struct ModuleConfig {
+    pub module: String,
+    pub options: toml::Table,
+}
+
+impl TryFrom<toml::Value> for ModuleConfig {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::String(module) => Ok(ModuleConfig {
+                module,
+                options: toml::Table::new()
+            }),
+            toml::Value::Table(mut options) => match options.remove("module") {
+                Some(toml::Value::String(module)) => Ok(ModuleConfig { module, options }),
+                _ => Err("module table needs a `module` name".to_string()),
+            },
+            other => Err(format!("expected a module name or table, found {other}")),
+        }
+    }
+}
+
+impl ModuleConfig {
+    pub fn int(&self, key: &str) -> Option<i64> {
+        self.options.get(key).and_then(toml::Value::as_integer)
+    }
+
+    // a threshold in percent, held to 0 to 100
+    pub fn percent(&self, key: &str, default: u32) -> u32 {
+        self.int(key).map_or(default,|percent| percent.clamp(0,100) as u32)
+    }
+
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.options.get(key).and_then(toml::Value::as_str).map(str::to_string)
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        self.options.get(key).and_then(toml::Value::as_bool)
+    }
+
+    // a list of strings, a single string is a list of one
+    pub fn strings(&self, key: &str) -> Option<Vec<String>> {
+        match self.options.get(key)? {
+            toml::Value::String(value) => Some(vec![value.clone()]),
+            toml::Value::Array(values) => values.iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/* ConfigError: everything wrong with a config file, reported at once */
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f,"invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f,"\n  {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("epic-bar"))
+}
+
+/* list keys and module names that are not known, and options of the wrong type,
+ * so typos are not silently ignored
+ */
+fn unknown_keys(table: &toml::Table, modules: KnownModules) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for key in table.keys().filter(|key| !["bar","theme","palette"].contains(&key.as_str())) {
+        problems.push(format!("{key}: unknown key"));
+    }
+
+    if let Some(toml::Value::String(name)) = table.get("theme") {
+        if Theme::named(name).is_none() {
+            problems.push(format!("theme: unknown theme \"{name}\", one of {}",theme::names().join(", ")));
+        }
+    }
+
+    if let Some(toml::Value::Table(palette)) = table.get("palette") {
+        for key in palette.keys().filter(|key| !theme::is_variable(key)) {
+            problems.push(format!("palette.{key}: unknown variable"));
+        }
+    }
+
+    let Some(toml::Value::Array(bars)) = table.get("bar") else {
+        return problems
+    };
+
+    for (i,bar) in bars.iter().enumerate() {
+        let Some(bar) = bar.as_table() else {
+            continue
+        };
+
+        for key in bar.keys().filter(|key| !BAR_KEYS.contains(&key.as_str())) {
+            problems.push(format!("bar[{i}].{key}: unknown key"));
+        }
+
+        for section in SECTIONS {
+            let Some(toml::Value::Array(entries)) = bar.get(section) else {
+                continue
+            };
+
+            for (j,module) in entries.iter().enumerate() {
+                let path = format!("bar[{i}].{section}[{j}]");
+                let (name,options) = match module {
+                    toml::Value::String(name) => (name.as_str(),None),
+                    toml::Value::Table(options) => match options.get("module") {
+                        Some(toml::Value::String(name)) => (name.as_str(),Some(options)),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+
+                let Some((_,known)) = modules.iter().find(|(module,_)| *module == name) else {
+                    problems.push(format!("{path}: unknown module \"{name}\""));
+                    continue
+                };
+
+                for (key,value) in options.into_iter().flatten().filter(|(key,_)| *key != "module") {
+                    match known.iter().find(|(option,_)| option == key) {
+                        None => problems.push(format!("{path}.{key}: unknown option for module \"{name}\"")),
+                        Some((_,kind)) if !kind.accepts(value) => {
+                            problems.push(format!("{path}.{key}: expected {kind}, found {}",value.type_str()));
+                        },
+                        Some(_) => {},
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+impl Config {
+    pub fn parse(text: &str, modules: KnownModules) -> Result<Self, ConfigError> {
+        let table: toml::Table = text.parse()
+            .map_err(|err: toml::de::Error| ConfigError { problems: vec![err.message().to_string()] })?;
+
+        let mut problems = unknown_keys(&table,modules);
+        let declares_bars = table.contains_key("bar");
+
+        match table.try_into::<Config>() {
+            // a file only picking a theme or colours keeps the default bars
+            Ok(mut config) if problems.is_empty() => {
+                if !declares_bars {
+                    config.bars = Config::default_config(modules).bars;
+                }
+                Ok(config)
+            },
+            Ok(_) => Err(ConfigError { problems }),
+            Err(err) => {
+                problems.push(err.message().to_string());
+                Err(ConfigError { problems })
+            }
+        }
+    }
+
+    pub fn default_config(modules: KnownModules) -> Self {
+        Config::parse(DEFAULT_CONFIG,modules).expect("built-in config is valid")
+    }
+
+    // a missing file is not an error, a broken one is reported and the default used
+    pub fn load(modules: KnownModules) -> Self {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Config::default_config(modules)
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text,modules).unwrap_or_else(|err| {
+                eprintln!("epic-bar: {}: {err}",path.display());
+                Config::default_config(modules)
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Config::default_config(modules),
+            Err(err) => {
+                eprintln!("epic-bar: could not read {}: {err}",path.display());
+                Config::default_config(modules)
+            }
+        }
+    }
+
+    // a theme given on the command line wins over the config
+    pub fn theme(&self, name: Option<&str>) -> Theme {
+        let name = name.or(self.theme.as_deref()).unwrap_or(theme::DEFAULT_THEME);
+        let mut theme = Theme::named(name).unwrap_or_else(|| {
+            eprintln!("epic-bar: unknown theme \"{name}\", one of {}",theme::names().join(", "));
+            Theme::default()
+        });
+
+        for (var,value) in &self.palette {
+            theme.set(var,value);
+        }
+        theme
+    }
+
+    pub fn bars_for(&self, connector: Option<&str>) -> Vec<BarConfig> {
+        self.bars.iter()
+            .filter(|bar| bar.monitors.is_empty()
+                || connector.is_some_and(|connector| bar.monitors.iter().any(|m| m == connector)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULES: KnownModules = &[
+        ("workspaces", &[("count",OptionKind::Int)]),
+        ("windows", &[]),
+        ("status", &[]),
+        ("cpu", &[]),
+        ("health", &[("warning",OptionKind::Int)]),
+        ("memory", &[]),
+        ("battery", &[]),
+        ("clock", &[("twelve_hour",OptionKind::Bool)]),
+        ("disk", &[("mounts",OptionKind::Strings)]),
+    ];
+
+    #[test]
+    fn default_is_current_layout() {
+        let config = Config::default_config(MODULES);
+
+        assert_eq!(config.bars.len(), 2);
+        assert_eq!(config.bars[0].edge, Edge::Top);
+        assert_eq!(config.bars[0].exclusive_zone, ExclusiveZone::Auto);
+        let right: Vec<&str> = config.bars[0].right.iter()
+            .map(|module| module.module.as_str())
+            .collect();
+        assert_eq!(right, vec!["status","cpu","health","memory","battery","clock"]);
+        assert_eq!(config.bars[1].left[0].module, "windows");
+    }
+
+    #[test]
+    fn modules_with_options() {
+        let config = Config::parse(r#"
+            [[bar]]
+            edge = "bottom"
+            layer = "overlay"
+            height = 28
+            exclusive_zone = 0
+            monitors = ["DP-1"]
+            center = [{ module = "workspaces", count = 5 }, { module = "health", warning = 150 }]
+            right = [{ module = "disk", mounts = ["/", "/home"] }, { module = "disk", mounts = "/" }, { module = "clock", twelve_hour = true }]
+        "#,MODULES).unwrap();
+
+        let bar = &config.bars[0];
+        assert_eq!(bar.layer, Layer::Overlay);
+        assert_eq!(bar.exclusive_zone, ExclusiveZone::Fixed(0));
+        assert_eq!(bar.center[0].int("count"), Some(5));
+        assert_eq!(bar.center[1].percent("warning",10), 100);
+        assert_eq!(bar.center[1].percent("critical",25), 25);
+        assert_eq!(bar.right[0].strings("mounts"), Some(vec!["/".to_string(),"/home".to_string()]));
+        assert_eq!(bar.right[1].strings("mounts"), Some(vec!["/".to_string()]));
+        assert_eq!(bar.right[1].strings("missing"), None);
+        assert_eq!(bar.right[2].bool("twelve_hour"), Some(true));
+        assert_eq!(bar.right[2].bool("missing"), None);
+        assert_eq!(config.bars_for(Some("DP-1")).len(), 1);
+        assert!(config.bars_for(Some("eDP-1")).is_empty());
+    }
+
+    #[test]
+    fn theme_only_keeps_default_bars() {
+        let config = Config::parse(r#"
+            theme = "light"
+
+            [palette]
+            accent = "rgba(200,120,40,0.8)"
+        "#,MODULES).unwrap();
+
+        assert_eq!(config.theme.as_deref(), Some("light"));
+        assert_eq!(config.bars.len(), Config::default_config(MODULES).bars.len());
+        assert_eq!(config.bars[0].edge, Edge::Top);
+    }
+
+    #[test]
+    fn all_bad_keys_are_listed() {
+        let err = Config::parse(r#"
+            colours = "dark"
+            theme = "solarized"
+
+            [palette]
+            fg = "white"
+            foreground = "black"
+
+            [[bar]]
+            edge = "top"
+            hieght = 30
+            left = ["workspaces", "cpuu", { module = "clock", format = "%H" }]
+        "#,MODULES).unwrap_err();
+
+        assert_eq!(err.problems, vec![
+            "colours: unknown key".to_string(),
+            "theme: unknown theme \"solarized\", one of dark, light, high-contrast".to_string(),
+            "palette.foreground: unknown variable".to_string(),
+            "bar[0].hieght: unknown key".to_string(),
+            "bar[0].left[1]: unknown module \"cpuu\"".to_string(),
+            "bar[0].left[2].format: unknown option for module \"clock\"".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn wrong_option_types_are_listed() {
+        let err = Config::parse(r#"
+            [[bar]]
+            edge = "top"
+            left = [{ module = "workspaces", count = "5" }, { module = "health", warning = 90.0 }]
+            right = [{ module = "disk", mounts = ["/", 2] }, { module = "clock", twelve_hour = true, date = "%d" }]
+        "#,MODULES).unwrap_err();
+
+        assert_eq!(err.problems, vec![
+            "bar[0].left[0].count: expected a whole number, found string".to_string(),
+            "bar[0].left[1].warning: expected a whole number, found float".to_string(),
+            "bar[0].right[0].mounts: expected a string or a list of strings, found array".to_string(),
+            "bar[0].right[1].date: unknown option for module \"clock\"".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn palette_over_theme() {
+        let config = Config::parse(r#"
+            theme = "light"
+
+            [palette]
+            accent = "orange"
+        "#,MODULES).unwrap();
+
+        let theme = config.theme(None);
+        assert_eq!(theme.get("accent"), Some("orange"));
+        assert_eq!(theme.get("fg"), Theme::named("light").unwrap().get("fg"));
+        assert_eq!(config.theme(Some("high-contrast")).get("font-size"), Some("18px"));
+    }
+
+    #[test]
+    fn bad_values_are_reported() {
+        let err = Config::parse(r#"
+            [[bar]]
+            edge = "left"
+        "#,MODULES).unwrap_err();
+
+        assert_eq!(err.problems.len(), 1);
+        assert!(err.problems[0].contains("left"));
+    }
+}
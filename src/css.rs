@@ -1,88 +1,169 @@
-// nice
-pub const CSS: &str = "
+use crate::theme::Theme;
+
+/* nice
+ * $name is replaced with the theme's value, see theme.rs
+ */
+pub const TEMPLATE: &str = "
 window {
-    font-family: 'Fira Sans', sans-serif; 
+    font-family: $font; 
 } 
 
 button {
-    font-family: 'Fira Sans', sans-serif; 
+    font-family: $font; 
     border-radius: 0px;
     margin: 0px;
     padding: 0px 3px; 
-    color: white; 
+    color: $fg; 
     background-color: rgba(0,0,0,0);
 }
 
 box { 
-    font-family: 'Fira Sans', sans-serif;
+    font-family: $font;
     border-radius: 0px;
     margin: 0px;
     padding: 0px 3px;
-    color: white; 
+    color: $fg; 
 }
 
 top-bar {
-    background-image: linear-gradient(
-        to bottom,
-        rgba(15,25,35,0.85)0%,
-        rgba(20,30,40,0.85)40%,
-        rgba(10,20,30,0.85)50%,
-        rgba(5,15,25,0.85)70%
-    );
+    background-image: $bar-background;
 }
 
 status-reveal-button { 
     font-size: 22px; 
-    border-right: 1px ridge white; 
+    border-right: 1px ridge $border; 
     padding: 0px 4px 0px 0px;
 } 
 
 battery-icon { 
     padding: 0px 0px 0px 4px;
-    font-size: 20px; 
-    color: white;
+    font-size: $font-size-icon; 
+    color: $fg;
 } 
 
 battery-label { 
-    font-size:16px; 
+    font-size: $font-size; 
     padding: 0px 0px; 
-    color: white;
+    color: $fg;
 } 
 
 mem-icon { 
     padding: 0px 2px 0px 4px;
-    font-size: 20px; 
-    color: white;
+    font-size: $font-size-icon; 
+    color: $fg;
 } 
 
 mem-label { 
-    font-size:16px; 
+    font-size: $font-size; 
     padding: 0px 4px 0px 0px; 
-    color: white;
-    border-right: 1px solid white;
+    color: $fg;
+    border-right: 1px solid $border;
 } 
 
+health-label {
+    font-size: $font-size;
+    padding: 0px 4px;
+    color: $fg;
+    border-right: 1px solid $border;
+}
+
+health-label.warning {
+    color: $warning;
+}
+
+health-label.critical {
+    color: $error;
+}
+
+mem-label.warning {
+    color: $warning;
+}
+
+mem-label.critical {
+    color: $error;
+}
+
 cpu-label { 
-    font-size:16px; 
+    font-size: $font-size; 
     padding: 0px 4px 0px 0px; 
-    color: white;
-    border-right: 1px solid white;
+    color: $fg;
+    border-right: 1px solid $border;
 } 
 
 cpu-load-label { 
-    font-size:16px; 
+    font-size: $font-size; 
     padding: 0px 2px; 
-    color: white;
+    color: $fg;
 } 
 
 
+cpu-button {
+    padding: 0px;
+}
+
+cpu-popover {
+    padding: 4px;
+}
+
+cpu-heading {
+    font-size: $font-size-small;
+    color: $fg-dim;
+    padding: 4px 0px 2px 0px;
+}
+
+cpu-cores, cpu-graph {
+    color: $accent;
+}
+
+cpu-pinned {
+    font-size: $font-size-small;
+    color: $warning;
+    padding: 2px 0px 0px 0px;
+}
+
+sensors-label {
+    font-size: $font-size;
+    padding: 0px 4px;
+    color: $fg;
+    border-right: 1px solid $border;
+}
+
+sensors-label.warning {
+    color: $warning;
+}
+
+sensors-label.critical {
+    color: $error;
+}
+
+disk-label {
+    font-size: $font-size;
+    padding: 0px 4px;
+    color: $fg;
+}
+
+disk-label.warning {
+    color: $warning;
+}
+
+disk-label.critical {
+    color: $error;
+}
+
+disk-io-label {
+    font-size: $font-size-small;
+    padding: 0px 4px;
+    color: $fg-dim;
+    border-right: 1px solid $border;
+}
+
 .occupied{
     background: linear-gradient(
         to bottom, 
-        rgba(40,44,52,0.7), 
-        rgba(30,33,40,0.7)
+        $occupied, 
+        $occupied-dark
     );
-    color: rgba(255,255,255,0.5);
+    color: $fg-dim;
     transition: all 0.2s ease; 
     text-shadow: 0 1px 1px rgba(0, 0, 0, 0.3);
 }
@@ -90,17 +171,17 @@ cpu-load-label {
 .occupied:hover{
     background: linear-gradient(
         to bottom, 
-        rgba(50,54,62,0.8),
-        rgba(40,43,50,0.8));
+        shade($occupied,1.2),
+        shade($occupied-dark,1.2));
 }
 
 .active {
     background: linear-gradient(
         to bottom, 
-        rgba(65,105,225,0.8),
-        rgba(45,85,205,0.8)
+        $accent,
+        $accent-dark
     ); 
-    color: rgba(255,255,255,1);
+    color: $fg;
     box-shadow: 
         inset 0 1px 0 rgba(255,255,255,0.1), 
         0 1px 3px rgba(0,0,0,0.2);
@@ -110,38 +191,129 @@ cpu-load-label {
 .active:hover{
     background: linear-gradient(
         to bottom, 
-        rgba(75,115,235,0.9),
-        rgba(55,95,215,0.9)
+        shade($accent,1.1),
+        shade($accent-dark,1.1)
     );
 }
 
 .empty-active {
     background: linear-gradient(
         to bottom, 
-        rgba(180,20,20,0.7),
-        rgba(150,15,15,0.7)
+        $empty-active,
+        shade($empty-active,0.85)
     );
 }
 
+network-label {
+    font-size: $font-size;
+    padding: 0px 4px 0px 0px;
+    color: $fg;
+    border-right: 1px solid $border;
+}
+
+throughput-label {
+    font-size: $font-size-small;
+    padding: 0px 2px 0px 4px;
+    color: $fg;
+}
+
+throughput-sparkline {
+    font-size: $font-size-small;
+    padding: 0px 4px 0px 0px;
+    color: $fg-dim;
+    border-right: 1px solid $border;
+}
+
+volume-label {
+    font-size: $font-size;
+    padding: 0px 4px 0px 2px;
+    color: $fg;
+    border-right: 1px solid $border;
+}
+
+mixer-button {
+    padding: 0px 4px 0px 2px;
+    border-right: 1px solid $border;
+}
+
+mixer-popover {
+    padding: 4px;
+}
+
+mixer-heading {
+    font-size: $font-size-small;
+    color: $fg-dim;
+    padding: 4px 0px 2px 0px;
+}
+
+mixer-label {
+    font-size: $font-size;
+    color: $fg;
+    padding: 0px 6px 0px 0px;
+}
+
+mixer-mute {
+    font-size: $font-size-small;
+    padding: 0px 6px;
+}
+
+mixer-mute:checked {
+    color: $error;
+}
+
+disconnected-label {
+    font-size: 14px;
+    padding: 0px 6px;
+    color: $error;
+}
+
+.status-error,
+.status-error * {
+    color: $error;
+}
+
+style-error-label {
+    font-size: 14px;
+    padding: 0px 6px;
+    color: $warning;
+}
+
 date-container { 
-    border-left: 1px solid white; 
-    font-size: 10px; padding: 0px 4px; color: white;
+    border-left: 1px solid $border; 
+    font-size: 10px; padding: 0px 4px; color: $fg;
+}
+
+date-container popover {
+    font-size: $font-size;
+}
+
+clock-next-event {
+    font-size: $font-size-small;
+    color: $accent;
+    padding: 0px 6px 0px 0px;
+}
+
+clock-popover {
+    padding: 4px;
+}
+
+clock-events {
+    padding: 4px 0px 0px 0px;
+}
+
+clock-event {
+    font-size: $font-size-small;
+    padding: 1px 0px;
 }
 
 bottom-bar {
-    background-image: linear-gradient(
-        to bottom,
-        rgba(15,25,35,0.85)0%,
-        rgba(20,30,40,0.85)40%,
-        rgba(10,20,30,0.85)50%,
-        rgba(5,15,25,0.85)60%
-    );
+    background-image: $bar-background;
     padding:0px 0 4px 0;
 }
 
 tag-label {
-    font-size: 12px; 
-    color: white; 
+    font-size: $font-size-small; 
+    color: $fg; 
     padding: 0px 3px; 
     border-right: 
     1px solid pink;
@@ -150,7 +322,7 @@ tag-label {
 active-window-box {
     text-shadow: 1px 1px 4px white, 0 0 1em blue, 0 0 0.2em blue; \
     transition-duration: .3s;
-    color: white; 
+    color: $fg; 
     font-size: 14px;
 }
 
@@ -160,9 +332,9 @@ active-window-box:hover {
 
 window-box {
     transition-duration: .3s; 
-    color: white; 
+    color: $fg; 
     font-size: 14px; 
-    border-right: 1px solid white;
+    border-right: 1px solid $border;
 }
 
 window-box:hover {
@@ -193,11 +365,60 @@ workspace-window-box-active {
 }
 
 workspace-window-box { 
-    border: 1px solid white; 
+    border: 1px solid $border; 
     margin: 0px 4px;
 } 
 
 
 ";
 
+// fill in the theme, unknown names are left as they are so GTK reports them
+pub fn render(theme: &Theme) -> String {
+    let mut css = String::with_capacity(TEMPLATE.len());
+    let mut rest = TEMPLATE;
+
+    while let Some(start) = rest.find('$') {
+        css.push_str(&rest[..start]);
+        rest = &rest[start+1..];
+
+        let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+
+        match theme.get(name) {
+            Some(value) => css.push_str(value),
+            None => {
+                css.push('$');
+                css.push_str(name);
+            }
+        }
+        rest = &rest[end..];
+    }
+    css.push_str(rest);
+
+    css
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme;
+
+    #[test]
+    fn every_theme_fills_template() {
+        for name in theme::names() {
+            let css = render(&Theme::named(name).unwrap());
+            assert!(!css.contains('$'), "theme {name} leaves a variable unset");
+        }
+    }
+
+    #[test]
+    fn palette_overrides_theme() {
+        let mut theme = Theme::default();
+        theme.set("fg","pink");
+
+        let css = render(&theme);
+        assert!(css.contains("color: pink;"));
+        assert!(css.contains("font-family: 'Fira Sans', sans-serif;"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap,HashSet},
+    ffi::CString,
+    fs,
+    io,
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::{Path,PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    status::StatusError,
+    system::SystemSource,
+};
+
+const MOUNT_INFO: &str = "/proc/self/mountinfo";
+const DISK_STATS: &str = "/proc/diskstats";
+const BLOCK: &str = "/sys/block";
+
+// /proc/diskstats counts in 512 byte sectors whatever the device uses
+const SECTOR: u64 = 512;
+
+// block devices that sit on top of other disks or in memory, counting them would count twice
+const VIRTUAL_DISKS: [&str;5] = ["loop", "ram", "zram", "dm-", "md"];
+
+// filesystems on another machine, statvfs on them can hang
+const NETWORK_FS: [&str;5] = ["nfs", "nfs4", "cifs", "smb3", "fuse.sshfs"];
+// local filesystems named by something other than a /dev node, a zfs dataset is `pool/dataset`
+const POOLED_FS: [&str;2] = ["zfs", "fuse.mergerfs"];
+
+/* Mount: one line of /proc/self/mountinfo
+ *  device: what is mounted, /dev/nvme0n1p2 or server:/export
+ *  fs_type: ext4, btrfs, ...
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+}
+
+/* Usage: statvfs of one mount, in bytes
+ * free counts the blocks kept for root, available does not
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+}
+
+// spaces and the like are written as \040 octal escapes
+fn unescape(field: &str) -> String {
+    let mut text = String::new();
+    let mut rest = field;
+    while let Some(at) = rest.find('\\') {
+        text.push_str(&rest[..at]);
+        let code = rest.get(at + 1..at + 4).and_then(|code| u8::from_str_radix(code,8).ok());
+        match code {
+            Some(code) => {
+                text.push(code as char);
+                rest = &rest[at + 4..];
+            },
+            None => {
+                text.push('\\');
+                rest = &rest[at + 1..];
+            },
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+impl Mount {
+    // id parent major:minor root mount_point options [optional...] - fs_type device super_options
+    fn parse(line: &str) -> Option<Self> {
+        let (mount,filesystem) = line.split_once(" - ")?;
+        let mount_point = mount.split_whitespace().nth(4)?;
+        let mut filesystem = filesystem.split_whitespace();
+
+        Some(Mount {
+            fs_type: filesystem.next()?.to_string(),
+            device: unescape(filesystem.next()?),
+            mount_point: PathBuf::from(unescape(mount_point)),
+        })
+    }
+
+    pub fn is_network(&self) -> bool {
+        NETWORK_FS.contains(&self.fs_type.as_str())
+    }
+
+    // disks, pools and network shares, not proc, tmpfs, snap squashfs images and so on
+    fn is_real(&self) -> bool {
+        (self.device.starts_with("/dev/") && self.fs_type != "squashfs")
+            || POOLED_FS.contains(&self.fs_type.as_str())
+            || self.is_network()
+    }
+}
+
+/* real filesystems in mount order, each device once
+ * btrfs subvolumes and bind mounts show the same space again under another path
+ */
+pub fn parse_mountinfo(mountinfo: &str) -> Vec<Mount> {
+    let mut devices = HashSet::new();
+    mountinfo.lines()
+        .filter_map(Mount::parse)
+        .filter(Mount::is_real)
+        .filter(|mount| devices.insert(mount.device.clone()))
+        .collect()
+}
+
+pub fn mounts(source: &SystemSource) -> Result<Vec<Mount>, StatusError> {
+    let mountinfo = source.read(MOUNT_INFO)
+        .map_err(|err| StatusError::Io(source.path(MOUNT_INFO),err))?;
+    Ok(parse_mountinfo(&mountinfo))
+}
+
+impl Usage {
+    pub fn read(path: &Path) -> Result<Self, StatusError> {
+        let error = |err| StatusError::Io(path.to_path_buf(),err);
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| error(io::Error::from(io::ErrorKind::InvalidInput)))?;
+
+        let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+        let result = unsafe { libc::statvfs(c_path.as_ptr(),&mut stat) };
+        if result < 0 {
+            return Err(error(io::Error::last_os_error()))
+        }
+
+        let block = stat.f_frsize as u64;
+        Ok(Usage {
+            total: stat.f_blocks as u64 * block,
+            free: stat.f_bfree as u64 * block,
+            available: stat.f_bavail as u64 * block,
+        })
+    }
+
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    // as df has it, of the space usable without root, so a full disk is 100%
+    pub fn percent(&self) -> u32 {
+        let usable = self.used() + self.available;
+        if usable == 0 {
+            return 0
+        }
+        (self.used() as f64 / usable as f64 * 100.0).ceil() as u32
+    }
+}
+
+// whole disks, partitions are left out as their disk already counts them
+fn physical_disks(source: &SystemSource) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(source.path(BLOCK)) else {
+        return Vec::new()
+    };
+    let mut disks: Vec<String> = entries.filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !VIRTUAL_DISKS.iter().any(|prefix| name.starts_with(prefix)))
+        .collect();
+    disks.sort();
+    disks
+}
+
+// bytes read and written since boot by disk name
+type Counters = HashMap<String,(u64,u64)>;
+
+fn parse_diskstats(diskstats: &str, disks: &[String]) -> Counters {
+    diskstats.lines()
+        .filter_map(|line| {
+            // major minor name reads merged sectors_read ms writes merged sectors_written ...
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let name = fields.get(2)?;
+            if !disks.iter().any(|disk| disk == name) {
+                return None
+            }
+            let read: u64 = fields.get(5)?.parse().ok()?;
+            let written: u64 = fields.get(9)?.parse().ok()?;
+            Some((name.to_string(),(read * SECTOR,written * SECTOR)))
+        })
+        .collect()
+}
+
+// one read of /proc/diskstats and the disks in it, taken apart from DiskIo so it can be read on another thread
+pub struct DiskStats {
+    text: String,
+    disks: Vec<String>,
+    taken: Instant,
+}
+
+impl DiskStats {
+    pub fn read(source: &SystemSource) -> Result<Self, StatusError> {
+        let text = source.read(DISK_STATS)
+            .map_err(|err| StatusError::Io(source.path(DISK_STATS),err))?;
+        Ok(DiskStats { text, disks: physical_disks(source), taken: Instant::now() })
+    }
+}
+
+/* read/write rates across the physical disks from two samples of /proc/diskstats,
+ * as Cpu does with /proc/stat
+ * disks are looked up on every sample, one plugged in or pulled out in between is
+ * left out of that rate rather than counted as all its traffic at once
+ */
+#[derive(Default)]
+pub struct DiskIo {
+    last: Option<(Instant,Counters)>,
+    pub read_rate: f64,
+    pub write_rate: f64,
+    pub read_total: u64,
+    pub write_total: u64,
+}
+
+impl DiskIo {
+    pub fn new() -> Self {
+        DiskIo::default()
+    }
+
+    pub fn add(&mut self, stats: &DiskStats) {
+        self.add_sample(&stats.text,&stats.disks,stats.taken);
+    }
+
+    fn add_sample(&mut self, diskstats: &str, disks: &[String], now: Instant) {
+        let counters = parse_diskstats(diskstats,disks);
+
+        if let Some((then,last)) = &self.last {
+            let seconds = now.duration_since(*then).as_secs_f64();
+            if seconds > 0.0 {
+                let (read,written) = counters.iter()
+                    .filter_map(|(disk,(read,written))| {
+                        let (last_read,last_written) = last.get(disk)?;
+                        Some((read.saturating_sub(*last_read),written.saturating_sub(*last_written)))
+                    })
+                    .fold((0,0),|(read,written),(r,w)| (read + r,written + w));
+                self.read_rate = read as f64 / seconds;
+                self.write_rate = written as f64 / seconds;
+            }
+        }
+
+        self.read_total = counters.values().map(|(read,_)| read).sum();
+        self.write_total = counters.values().map(|(_,written)| written).sum();
+        self.last = Some((now,counters));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    // laptop with / and /home on btrfs subvolumes, an efi partition and a usb stick
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    const LAPTOP_LATER: &str = "tests/fixtures/system/laptop-1s";
+
+    #[test]
+    fn real_filesystems() {
+        let mounts = mounts(&SystemSource::new(LAPTOP)).unwrap();
+        let points: Vec<&str> = mounts.iter().map(|mount| mount.mount_point.to_str().unwrap()).collect();
+
+        // /home is the same btrfs device as /, snaps and tmpfs are not disks
+        assert_eq!(points, ["/", "/boot", "/run/media/user/USB STICK", "/mnt/nas"]);
+        assert_eq!(mounts[0].fs_type, "btrfs");
+        assert_eq!(mounts[3].device, "nas.local:/export");
+        assert!(mounts[3].is_network());
+    }
+
+    #[test]
+    fn pools_are_local() {
+        let mounts = parse_mountinfo("\
+            30 1 0:26 / / rw,relatime shared:1 - zfs rpool/ROOT/default rw,xattr,posixacl\n\
+            31 30 0:27 / /home rw,relatime shared:2 - zfs rpool/home rw,xattr,posixacl\n\
+            40 30 0:35 / /srv/media rw,relatime shared:9 - fuse.mergerfs disks rw,user_id=0,group_id=0\n");
+        let points: Vec<&str> = mounts.iter().map(|mount| mount.mount_point.to_str().unwrap()).collect();
+
+        assert_eq!(points, ["/", "/home", "/srv/media"]);
+        assert!(mounts.iter().all(|mount| !mount.is_network()));
+    }
+
+    #[test]
+    fn escaped_mount_points() {
+        assert_eq!(unescape("a\\040b\\134c"), "a b\\c");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn usage() {
+        // 100 GiB with 5 GiB kept for root and 10 GiB available
+        let gib = 1 << 30;
+        let usage = Usage { total: 100 * gib, free: 15 * gib, available: 10 * gib };
+        assert_eq!(usage.used(), 85 * gib);
+        assert_eq!(usage.percent(), 90);
+
+        assert!(Usage::read(Path::new(".")).unwrap().total > 0);
+        assert!(matches!(Usage::read(Path::new("tests/fixtures/system/missing")), Err(StatusError::Io(..))));
+    }
+
+    #[test]
+    fn throughput_between_snapshots() {
+        let disks = physical_disks(&SystemSource::new(LAPTOP));
+        // loop, zram and the dm-crypt mapping on top of nvme0n1 are left out
+        assert_eq!(disks, ["nvme0n1", "sda"]);
+
+        let now = Instant::now();
+        let read = |root: &str| fs::read_to_string(format!("{root}{DISK_STATS}")).unwrap();
+        let mut io = DiskIo::new();
+        io.add_sample(&read(LAPTOP),&disks,now);
+        assert_eq!(io.read_rate, 0.0);
+        assert_eq!(io.read_total, 10_002_000 * SECTOR);
+
+        io.add_sample(&read(LAPTOP_LATER),&disks,now + Duration::from_secs(2));
+        // 4096 and 1024 sectors more over two seconds
+        assert_eq!(io.read_rate, 1024.0 * 1024.0);
+        assert_eq!(io.write_rate, 256.0 * 1024.0);
+
+        // the usb stick is pulled, only the nvme drive counts from here
+        io.add_sample(&read(LAPTOP_LATER),&disks[..1],now + Duration::from_secs(3));
+        assert_eq!((io.read_rate,io.write_rate), (0.0,0.0));
+        io.add_sample(&read(LAPTOP_LATER),&disks,now + Duration::from_secs(4));
+        assert_eq!(io.read_rate, 0.0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::io;
+
+use crate::{
+    status::{
+        self,
+        parse_error,
+        StatusError,
+    },
+    system::SystemSource,
+};
+
+const PRESSURE: &str = "/proc/pressure";
+const LOAD_AVERAGE: &str = "/proc/loadavg";
+
+/* PressureLine: share of time tasks were stalled waiting on a resource, in percent
+ * over the last 10, 60 and 300 seconds, and the total stall in µs
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureLine {
+    pub avg10: f32,
+    pub avg60: f32,
+    pub avg300: f32,
+    pub total: u64,
+}
+
+/* Pressure: one file of /proc/pressure
+ *  some: at least one task stalled
+ *  full: every task stalled at once, missing for cpu on kernels before 5.13
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pressure {
+    pub some: PressureLine,
+    pub full: Option<PressureLine>,
+}
+
+impl PressureLine {
+    // "some avg10=0.00 avg60=0.00 avg300=0.00 total=0", without the kind
+    fn parse(fields: &str) -> Option<Self> {
+        let mut line = PressureLine::default();
+        for field in fields.split_whitespace() {
+            let (key,value) = field.split_once('=')?;
+            match key {
+                "avg10" => line.avg10 = value.parse().ok()?,
+                "avg60" => line.avg60 = value.parse().ok()?,
+                "avg300" => line.avg300 = value.parse().ok()?,
+                "total" => line.total = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        Some(line)
+    }
+}
+
+impl Pressure {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut some = None;
+        let mut full = None;
+        for line in text.lines() {
+            match line.split_once(' ') {
+                Some(("some",fields)) => some = PressureLine::parse(fields),
+                Some(("full",fields)) => full = PressureLine::parse(fields),
+                _ => {}
+            }
+        }
+        Some(Pressure { some: some?, full })
+    }
+
+    // `resource` is cpu, memory or io, kernels without PSI have no /proc/pressure
+    pub fn read(source: &SystemSource, resource: &str) -> Result<Option<Self>, StatusError> {
+        let path = format!("{PRESSURE}/{resource}");
+        match source.read(&path) {
+            Ok(text) => Pressure::parse(&text)
+                .map(Some)
+                .ok_or_else(|| parse_error(source,&path,"no `some` line")),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(StatusError::Io(source.path(&path),err)),
+        }
+    }
+}
+
+/* LoadAverage: /proc/loadavg
+ * runnable and waiting tasks averaged over 1, 5 and 15 minutes, and right now
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+    pub running: u32,
+    pub tasks: u32,
+}
+
+impl LoadAverage {
+    // "0.52 0.58 0.59 2/1204 123456", the last being the newest pid
+    fn parse(text: &str) -> Option<Self> {
+        let mut fields = text.split_whitespace();
+        let mut average = || fields.next()?.parse().ok();
+        let (one,five,fifteen) = (average()?,average()?,average()?);
+        let (running,tasks) = fields.next()?.split_once('/')?;
+
+        Some(LoadAverage {
+            one,
+            five,
+            fifteen,
+            running: running.parse().ok()?,
+            tasks: tasks.parse().ok()?,
+        })
+    }
+}
+
+/* Health: how contended the machine is, where a load percentage only says how busy
+ * pressure: stall times by resource, empty on kernels without PSI
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub load: LoadAverage,
+    pub pressure: Vec<(&'static str,Pressure)>,
+}
+
+impl Health {
+    pub fn read(source: &SystemSource) -> Result<Self, StatusError> {
+        let load = LoadAverage::parse(&status::read(source,LOAD_AVERAGE)?)
+            .ok_or_else(|| parse_error(source,LOAD_AVERAGE,"not a load average"))?;
+
+        let mut pressure = Vec::new();
+        for resource in ["cpu","io","memory"] {
+            if let Some(stall) = Pressure::read(source,resource)? {
+                pressure.push((resource,stall));
+            }
+        }
+
+        Ok(Health { load, pressure })
+    }
+
+    // the resource some task waited on the most over the last 10 seconds, in percent
+    pub fn worst(&self) -> Option<(&'static str,f32)> {
+        self.pressure.iter()
+            .map(|(resource,stall)| (*resource,stall.some.avg10))
+            .max_by(|(_,a),(_,b)| a.total_cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    // a 64 core build server, the second snapshot also stands in for a kernel without PSI
+    const SERVER: &str = "tests/fixtures/system/server-64";
+    const SERVER_LATER: &str = "tests/fixtures/system/server-64-1s";
+
+    #[test]
+    fn health_of_a_laptop_and_a_build_server() {
+        let laptop = Health::read(&SystemSource::new(LAPTOP)).unwrap();
+        assert_eq!(laptop.load, LoadAverage { one: 0.52, five: 0.58, fifteen: 0.59, running: 2, tasks: 1204 });
+        let resources: Vec<&str> = laptop.pressure.iter().map(|(resource,_)| *resource).collect();
+        assert_eq!(resources, ["cpu", "io", "memory"]);
+        // waiting on the disk, not the cpu
+        assert_eq!(laptop.worst(), Some(("io",4.75)));
+
+        // more runnable tasks than cores, every one of them waiting its turn
+        let server = Health::read(&SystemSource::new(SERVER)).unwrap();
+        assert_eq!(server.load.one, 71.25);
+        assert_eq!(server.worst(), Some(("cpu",38.2)));
+
+        // no PSI in the kernel
+        let old = Health::read(&SystemSource::new(SERVER_LATER)).unwrap();
+        assert!(old.pressure.is_empty());
+        assert_eq!(old.worst(), None);
+    }
+
+    #[test]
+    fn pressure_lines() {
+        // cpu has no full line before 5.13
+        let cpu = Pressure::parse("some avg10=12.50 avg60=4.10 avg300=1.00 total=123456\n").unwrap();
+        assert_eq!(cpu.some, PressureLine { avg10: 12.5, avg60: 4.1, avg300: 1.0, total: 123456 });
+        assert_eq!(cpu.full, None);
+        assert_eq!(Pressure::parse("full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n"), None);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let source = SystemSource::new("tests/fixtures/system/missing");
+        assert!(matches!(Health::read(&source), Err(StatusError::Io(..))));
+    }
+}
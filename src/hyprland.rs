@@ -0,0 +1,536 @@
+use std::{
+    io::{self,Write,Read,BufRead,BufReader},
+    env,
+    fmt,
+    error,
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    thread,
+    time::Duration,
+    cell::{Cell,RefCell},
+    rc::Rc,
+};
+
+use gtk::glib;
+use serde::{
+    Deserialize,
+    de::DeserializeOwned,
+};
+
+use crate::workspaces::{
+    self,
+    Workspaces,
+};
+
+/* for this program:
+ *  socket:  request/reply commands (same as hyprctl)
+ *  socket2: stream of `EVENT>>DATA` lines for everything happening on the compositor
+ */
+
+// waiting between attempts to reach socket2 doubles up to the max
+const RECONNECT_MIN: Duration = Duration::from_millis(250);
+const RECONNECT_MAX: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum HyprlandError {
+    // environment variable needed to find the sockets is not set
+    NotRunning(&'static str),
+    Io(io::Error),
+    Json(serde_json::Error),
+    // hyprland did not reply `ok` to a dispatch
+    Dispatch(String),
+}
+
+impl fmt::Display for HyprlandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HyprlandError::NotRunning(var) =>
+                write!(f,"Hyprland is not running (${var} is not set)"),
+            HyprlandError::Io(err) => write!(f,"Hyprland IPC: {err}"),
+            HyprlandError::Json(err) => write!(f,"Unexpected reply from Hyprland: {err}"),
+            HyprlandError::Dispatch(reply) => write!(f,"Hyprland refused dispatch: {reply}"),
+        }
+    }
+}
+
+impl error::Error for HyprlandError {}
+
+impl From<io::Error> for HyprlandError {
+    fn from(err: io::Error) -> Self {
+        HyprlandError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for HyprlandError {
+    fn from(err: serde_json::Error) -> Self {
+        HyprlandError::Json(err)
+    }
+}
+
+/* HyprlandClient: the sockets of the running hyprland instance
+ *
+ * a new connection is made for every request as hyprland closes
+ * socket after replying
+ */
+#[derive(Clone, Debug)]
+pub struct HyprlandClient {
+    socket_dir: PathBuf
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceRef {
+    pub id: i32,
+    pub name: String
+}
+
+/* Client: an open window as reported by `j/clients`
+ *
+ * address: hex address with the 0x prefix, used to focus the window
+ * initial_title: title the window opened with, usually the program name
+ * focus_history_id: 0 meaning most recently focused
+ */
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Client {
+    pub address: String,
+    pub mapped: bool,
+    pub hidden: bool,
+    pub workspace: WorkspaceRef,
+    pub floating: bool,
+    pub monitor: i32,
+    pub class: String,
+    pub title: String,
+    pub initial_class: String,
+    pub initial_title: String,
+    pub pid: i32,
+    pub xwayland: bool,
+    pub pinned: bool,
+    #[serde(rename = "focusHistoryID")]
+    pub focus_history_id: i32,
+}
+
+impl Client {
+    // the name of the program, with special cases for ugly initialTitles
+    pub fn display_name(&self) -> String {
+        match &self.initial_title {
+            title if title.contains("Chromium") => "Chromium".to_string(),
+            title if title.contains("OBS") => "OBS Studio".to_string(),
+            title if title.contains(".pdf") =>
+                title.rsplit('/').next().unwrap_or("Document").to_string(),
+            title if title.contains("WhatsApp") => "WhatsApp".to_string(),
+            title => title.to_string()
+        }
+    }
+
+    // secondary title or "information" of the window
+    pub fn display_title(&self) -> String {
+        match &self.title {
+            title if title.contains(".pdf") =>
+                title.rsplit('/').next().unwrap_or("PDF Document").to_string(),
+            title => title.to_string()
+        }
+    }
+}
+
+/* Workspace: a workspace as reported by `j/workspaces`
+ *
+ * monitor: name of the output the workspace is on (e.g. DP-1)
+ * windows: number of windows on the workspace
+ */
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub monitor: String,
+    #[serde(rename = "monitorID")]
+    pub monitor_id: i32,
+    pub windows: u32,
+    pub hasfullscreen: bool,
+    pub lastwindow: String,
+    pub lastwindowtitle: String,
+}
+
+/* Monitor: an output as reported by `j/monitors`
+ *
+ * name: connector name, the same one GDK reports for the monitor
+ */
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Monitor {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: f64,
+    pub x: i32,
+    pub y: i32,
+    pub active_workspace: WorkspaceRef,
+    pub special_workspace: WorkspaceRef,
+    pub scale: f64,
+    pub focused: bool,
+    pub dpms_status: bool,
+    pub disabled: bool,
+}
+
+impl HyprlandClient {
+    pub fn from_env() -> Result<Self, HyprlandError> {
+        let runtime_dir = env::var("XDG_RUNTIME_DIR")
+            .map_err(|_| HyprlandError::NotRunning("XDG_RUNTIME_DIR"))?;
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .map_err(|_| HyprlandError::NotRunning("HYPRLAND_INSTANCE_SIGNATURE"))?;
+
+        Ok(HyprlandClient {
+            socket_dir: PathBuf::from(runtime_dir).join("hypr").join(signature)
+        })
+    }
+
+    fn connect(&self, num: &str) -> Result<UnixStream, HyprlandError> {
+        let path = self.socket_dir.join(format!(".socket{num}.sock"));
+        Ok(UnixStream::connect(path)?)
+    }
+
+    // connection to socket2 for reading events
+    pub fn events(&self) -> Result<UnixStream, HyprlandError> {
+        self.connect("2")
+    }
+
+    fn send(&self, cmd: &str) -> Result<String, HyprlandError> {
+        let mut sock = self.connect("")?;
+
+        sock.write_all(cmd.as_bytes())?;
+
+        let mut buff = String::new();
+        sock.read_to_string(&mut buff)?;
+        Ok(buff)
+    }
+
+    /* send a command on socket prefixed with `j/` so hyprland replies with JSON
+     * rather than the human readable output of hyprctl
+     */
+    fn request<T: DeserializeOwned>(&self, cmd: &str) -> Result<T, HyprlandError> {
+        let reply = self.send(&format!("j/{cmd}"))?;
+        Ok(serde_json::from_str(&reply)?)
+    }
+
+    pub fn dispatch(&self, args: &str) -> Result<(), HyprlandError> {
+        let reply = self.send(&format!("dispatch {args}"))?;
+
+        match reply.trim() {
+            "ok" => Ok(()),
+            _ => Err(HyprlandError::Dispatch(reply))
+        }
+    }
+
+    pub fn clients(&self) -> Result<Vec<Client>, HyprlandError> {
+        self.request("clients")
+    }
+
+    pub fn workspaces(&self) -> Result<Vec<Workspace>, HyprlandError> {
+        self.request("workspaces")
+    }
+
+    pub fn monitors(&self) -> Result<Vec<Monitor>, HyprlandError> {
+        self.request("monitors")
+    }
+
+    pub fn active_window(&self) -> Result<Option<Client>, HyprlandError> {
+        Ok(active_window_from(self.request("activewindow")?))
+    }
+
+    pub fn switch_window(&self, address: &str) -> Result<(), HyprlandError> {
+        self.dispatch(&format!("focuswindow address:{address}"))
+    }
+
+    pub fn switch_workspace(&self, tag: i32) -> Result<(), HyprlandError> {
+        self.dispatch(&format!("workspace {tag}"))
+    }
+
+    pub fn get_workspaces(&self) -> Result<Workspaces, HyprlandError> {
+        Ok(workspaces::assign_clients(
+            self.workspaces()?,
+            self.clients()?,
+            &self.monitors()?))
+    }
+}
+
+// hyprland replies with `{}` when nothing is focused
+fn active_window_from(value: serde_json::Value) -> Option<Client> {
+    match value.as_object() {
+        Some(object) if object.is_empty() => None,
+        _ => serde_json::from_value(value).ok()
+    }
+}
+
+/* HyprEvent: a single parsed line of socket2
+ *
+ * window addresses are as sent by hyprland (hex without the 0x prefix),
+ * workspaces and monitors are referred to by name
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum HyprEvent {
+    Workspace { name: String },
+    FocusedMonitor { monitor: String, workspace: String },
+    ActiveWindow { class: String, title: String },
+    ActiveWindowAddress { address: String },
+    OpenWindow { address: String, workspace: String, class: String, title: String },
+    CloseWindow { address: String },
+    MoveWindow { address: String, workspace: String },
+    WindowTitle { address: String, title: String },
+    Urgent { address: String },
+    CreateWorkspace { name: String },
+    DestroyWorkspace { name: String },
+    MoveWorkspace { name: String, monitor: String },
+    MonitorAdded { name: String },
+    MonitorRemoved { name: String },
+    // anything not needed by the bar yet
+    Other { name: String, data: String },
+    // not sent by hyprland, state of the connection to socket2
+    Connected,
+    Disconnected { reason: String },
+}
+
+impl HyprEvent {
+    /* parse a line of the form `EVENT>>DATA`
+     * only the last field of an event may contain commas (window titles),
+     * so data is split at most as many times as the event has fields
+     */
+    pub fn parse(line: &str) -> Option<Self> {
+        let (name, data) = line.split_once(">>")?;
+
+        let fields = |n: usize| -> Vec<String> {
+            let mut fields: Vec<String> = data
+                .splitn(n, ',')
+                .map(str::to_string)
+                .collect();
+            fields.resize(n, String::new());
+            fields
+        };
+
+        let event = match name {
+            "workspace" => HyprEvent::Workspace { name: data.to_string() },
+            "focusedmon" => {
+                let [monitor, workspace]: [String;2] = fields(2).try_into().ok()?;
+                HyprEvent::FocusedMonitor { monitor, workspace }
+            },
+            "activewindow" => {
+                let [class, title]: [String;2] = fields(2).try_into().ok()?;
+                HyprEvent::ActiveWindow { class, title }
+            },
+            "activewindowv2" => HyprEvent::ActiveWindowAddress { address: data.to_string() },
+            "openwindow" => {
+                let [address, workspace, class, title]: [String;4] = fields(4).try_into().ok()?;
+                HyprEvent::OpenWindow { address, workspace, class, title }
+            },
+            "closewindow" => HyprEvent::CloseWindow { address: data.to_string() },
+            "movewindow" => {
+                let [address, workspace]: [String;2] = fields(2).try_into().ok()?;
+                HyprEvent::MoveWindow { address, workspace }
+            },
+            // sent along with `windowtitle`, which has only the address
+            "windowtitlev2" => {
+                let [address, title]: [String;2] = fields(2).try_into().ok()?;
+                HyprEvent::WindowTitle { address, title }
+            },
+            "urgent" => HyprEvent::Urgent { address: data.to_string() },
+            "createworkspace" => HyprEvent::CreateWorkspace { name: data.to_string() },
+            "destroyworkspace" => HyprEvent::DestroyWorkspace { name: data.to_string() },
+            "moveworkspace" => {
+                let [name, monitor]: [String;2] = fields(2).try_into().ok()?;
+                HyprEvent::MoveWorkspace { name, monitor }
+            },
+            "monitoradded" => HyprEvent::MonitorAdded { name: data.to_string() },
+            "monitorremoved" => HyprEvent::MonitorRemoved { name: data.to_string() },
+            _ => HyprEvent::Other { name: name.to_string(), data: data.to_string() },
+        };
+
+        Some(event)
+    }
+}
+
+pub type SubscriptionId = usize;
+
+type Subscriber = Rc<dyn Fn(&HyprEvent)>;
+
+// subscribers hold widgets so they only ever live on the main thread
+thread_local! {
+    static SUBSCRIBERS: RefCell<Vec<(SubscriptionId,Subscriber)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<SubscriptionId> = const { Cell::new(0) };
+}
+
+/* call `f` on the main thread for every event received on socket2
+ * must be called from the main thread
+ */
+pub fn subscribe<F: Fn(&HyprEvent) + 'static>(f: F) -> SubscriptionId {
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    SUBSCRIBERS.with(|subs| subs.borrow_mut().push((id,Rc::new(f))));
+    id
+}
+
+pub fn unsubscribe(id: SubscriptionId) {
+    SUBSCRIBERS.with(|subs| subs.borrow_mut().retain(|(sub_id,_)| *sub_id != id));
+}
+
+fn dispatch(event: HyprEvent) {
+    // copy out so subscribers are free to (un)subscribe while handling
+    let subs: Vec<Subscriber> = SUBSCRIBERS.with(|subs| {
+        subs.borrow().iter().map(|(_,f)| f.clone()).collect()
+    });
+
+    for f in subs {
+        f(&event);
+    }
+}
+
+/* keep one connection to socket2 open for the lifetime of the program
+ * and hand every parsed event to the main loop
+ * if hyprland goes away keep retrying with backoff until it is back
+ */
+pub fn listen() {
+    thread::spawn(|| {
+        let ctx = glib::MainContext::default();
+        let mut backoff = RECONNECT_MIN;
+        let mut connected = None;
+
+        loop {
+            let reason = match HyprlandClient::from_env().and_then(|client| client.events()) {
+                Ok(sock) => {
+                    backoff = RECONNECT_MIN;
+                    connected = Some(true);
+                    ctx.invoke(|| dispatch(HyprEvent::Connected));
+
+                    // for some reason reading socket2 must be buffered
+                    let reader = BufReader::new(sock);
+                    for line in reader.lines() {
+                        let Ok(line) = line else {
+                            break
+                        };
+
+                        if let Some(event) = HyprEvent::parse(&line) {
+                            ctx.invoke(move || dispatch(event));
+                        }
+                    }
+                    "Hyprland closed the event socket".to_string()
+                },
+                Err(err) => err.to_string()
+            };
+
+            // only report once per disconnect, not on every retry
+            if connected != Some(false) {
+                eprintln!("epic-bar: {reason}, reconnecting");
+                connected = Some(false);
+                ctx.invoke(move || dispatch(HyprEvent::Disconnected { reason }));
+            }
+
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENTS: &str = include_str!("../tests/fixtures/hyprland/clients.json");
+    const WORKSPACES: &str = include_str!("../tests/fixtures/hyprland/workspaces.json");
+    const MONITORS: &str = include_str!("../tests/fixtures/hyprland/monitors.json");
+    const ACTIVE_WINDOW: &str = include_str!("../tests/fixtures/hyprland/activewindow.json");
+    const NO_ACTIVE_WINDOW: &str = include_str!("../tests/fixtures/hyprland/activewindow-empty.json");
+
+    #[test]
+    fn events_with_commas_in_titles() {
+        // only the first `>>` ends the event name, the last field takes the rest of the line
+        assert_eq!(HyprEvent::parse("openwindow>>5c1a0e5b3c80,1,firefox,Re: a, b, c >> d - Mozilla Firefox"),
+            Some(HyprEvent::OpenWindow {
+                address: "5c1a0e5b3c80".to_string(),
+                workspace: "1".to_string(),
+                class: "firefox".to_string(),
+                title: "Re: a, b, c >> d - Mozilla Firefox".to_string(),
+            }));
+        assert_eq!(HyprEvent::parse("activewindow>>kitty,vim a,b >> c"),
+            Some(HyprEvent::ActiveWindow { class: "kitty".to_string(), title: "vim a,b >> c".to_string() }));
+
+        assert_eq!(HyprEvent::parse("windowtitlev2>>5c1a0e5b3c80,cargo build, test >> log"),
+            Some(HyprEvent::WindowTitle {
+                address: "5c1a0e5b3c80".to_string(),
+                title: "cargo build, test >> log".to_string(),
+            }));
+        // the old form without the title is passed on whole
+        assert_eq!(HyprEvent::parse("windowtitle>>5c1a0e5b3c80"),
+            Some(HyprEvent::Other { name: "windowtitle".to_string(), data: "5c1a0e5b3c80".to_string() }));
+    }
+
+    #[test]
+    fn malformed_events() {
+        // missing fields are left empty rather than dropping the event
+        assert_eq!(HyprEvent::parse("openwindow>>5c1a0e5b3c80,2"),
+            Some(HyprEvent::OpenWindow {
+                address: "5c1a0e5b3c80".to_string(),
+                workspace: "2".to_string(),
+                class: String::new(),
+                title: String::new(),
+            }));
+        assert_eq!(HyprEvent::parse("movewindow>>"),
+            Some(HyprEvent::MoveWindow { address: String::new(), workspace: String::new() }));
+
+        assert_eq!(HyprEvent::parse("somefutureevent>>a,b"),
+            Some(HyprEvent::Other { name: "somefutureevent".to_string(), data: "a,b".to_string() }));
+
+        assert_eq!(HyprEvent::parse("workspace"), None);
+        assert_eq!(HyprEvent::parse(""), None);
+    }
+
+    #[test]
+    fn clients_with_keywords_in_titles() {
+        let clients: Vec<Client> = serde_json::from_str(CLIENTS).unwrap();
+
+        assert_eq!(clients.len(), 4);
+        let firefox = &clients[1];
+        assert_eq!(firefox.address, "0x5c1a0e5b3c80");
+        assert_eq!(firefox.workspace, WorkspaceRef { id: 1, name: "1".to_string() });
+        assert_eq!(firefox.focus_history_id, 0);
+        assert!(firefox.title.starts_with("title: initialClass: pid: \"quoted\""));
+        assert_eq!(firefox.display_name(), "Mozilla Firefox");
+    }
+
+    #[test]
+    fn pdf_titles_are_shortened() {
+        let clients: Vec<Client> = serde_json::from_str(CLIENTS).unwrap();
+
+        assert_eq!(clients[2].display_name(), "attention.pdf");
+        assert_eq!(clients[2].display_title(), "attention.pdf");
+    }
+
+    #[test]
+    fn workspaces_and_monitors() {
+        let workspaces: Vec<Workspace> = serde_json::from_str(WORKSPACES).unwrap();
+        let monitors: Vec<Monitor> = serde_json::from_str(MONITORS).unwrap();
+
+        assert_eq!(workspaces.len(), 4);
+        assert_eq!(workspaces[2].monitor, "DP-1");
+        assert_eq!(workspaces[3].id, -98);
+
+        assert_eq!(monitors.len(), 2);
+        assert!(monitors[0].focused);
+        assert_eq!(monitors[0].active_workspace.id, 1);
+        assert_eq!(monitors[1].name, "DP-1");
+        assert_eq!(monitors[1].scale, 1.5);
+    }
+
+    #[test]
+    fn active_window_may_be_empty() {
+        let active = active_window_from(serde_json::from_str(ACTIVE_WINDOW).unwrap());
+        assert_eq!(active.map(|client| client.address), Some("0x5c1a0e5b3c80".to_string()));
+
+        let none = active_window_from(serde_json::from_str(NO_ACTIVE_WINDOW).unwrap());
+        assert!(none.is_none());
+    }
+}
@@ -0,0 +1,885 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path,PathBuf},
+    rc::Rc,
+    str::FromStr,
+};
+
+use time::{
+    Date,
+    Duration,
+    Month,
+    OffsetDateTime,
+    PrimitiveDateTime,
+    Time,
+    Weekday,
+};
+
+use crate::{
+    status::StatusError,
+    system::SystemSource,
+    timezone::Zone,
+};
+
+// vdirsyncer keeps one directory per calendar under the configured one
+const MAX_DEPTH: usize = 2;
+
+/* Stamp: a DTSTART, EXDATE and the like
+ * all-day events only have a date, which is taken in the bar's own zone
+ * times are wall clock readings in their zone: UTC for a trailing Z, the one named by
+ * TZID, or the bar's own for floating times and TZIDs tzdata does not know
+ */
+#[derive(Debug, Clone)]
+enum Stamp {
+    Date(Date),
+    Time(PrimitiveDateTime, Rc<Zone>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/* Recurrence: an RRULE
+ * by_day: weekdays, with an ordinal in monthly and yearly rules as in -1FR for the last Friday
+ * BYSETPOS, BYYEARDAY, BYWEEKNO and rules more often than daily are not supported, those
+ * events only show their first occurrence
+ */
+#[derive(Debug, Clone)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<Stamp>,
+    by_day: Vec<(Option<i32>,Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<Month>,
+    week_start: Weekday,
+}
+
+/* Event: one VEVENT
+ * exceptions: EXDATEs, and the occurrences other VEVENTs with the same uid replace
+ * recurrence_id: set on such a replacement
+ */
+#[derive(Debug, Clone)]
+struct Event {
+    uid: String,
+    summary: String,
+    location: Option<String>,
+    start: Stamp,
+    duration: Duration,
+    rule: Option<Recurrence>,
+    exceptions: Vec<Stamp>,
+    recurrence_id: Option<Stamp>,
+    cancelled: bool,
+}
+
+// one occurrence of an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub summary: String,
+    pub location: Option<String>,
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    pub all_day: bool,
+}
+
+// every event from a set of .ics files and directories, and the ones that could not be read
+#[derive(Debug, Default)]
+pub struct Calendars {
+    events: Vec<Event>,
+    pub errors: Vec<StatusError>,
+}
+
+// a content line, NAME;PARAM=value:value with the name and parameter names upper case
+struct Property {
+    name: String,
+    params: Vec<(String,String)>,
+    value: String,
+}
+
+/* long lines are folded onto the following ones, each starting with a space or tab
+ * lines() takes the CRLF endings .ics files are meant to have as well as plain LF
+ */
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ','\t']),lines.last_mut()) {
+            (Some(rest),Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// splits at `separator` outside of double quotes, TZID="America/New_York" and such
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (at,c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[start..at]);
+            start = at + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // the first unquoted colon ends the name and parameters
+        let mut quoted = false;
+        let colon = line.char_indices()
+            .find(|(_,c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+                *c == ':' && !quoted
+            })?.0;
+
+        let mut head = split_unquoted(&line[..colon],';').into_iter();
+        let name = head.next()?.to_ascii_uppercase();
+        let params = head
+            .filter_map(|param| {
+                let (key,value) = param.split_once('=')?;
+                Some((key.to_ascii_uppercase(),value.trim_matches('"').to_string()))
+            })
+            .collect();
+
+        Some(Property { name, params, value: line[colon + 1..].to_string() })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(name,_)| name == key).map(|(_,value)| value.as_str())
+    }
+}
+
+// TEXT values escape commas, semicolons, backslashes and newlines
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn number<T: FromStr>(text: &str, range: Range<usize>) -> Option<T> {
+    text.get(range)?.parse().ok()
+}
+
+// 20261019
+fn parse_date(text: &str) -> Option<Date> {
+    if text.len() != 8 {
+        return None
+    }
+    let month = Month::try_from(number::<u8>(text,4..6)?).ok()?;
+    Date::from_calendar_date(number(text,0..4)?,month,number(text,6..8)?).ok()
+}
+
+// 20261019T093000, with the Z left to the caller
+fn parse_datetime(text: &str) -> Option<PrimitiveDateTime> {
+    let (date,time) = text.split_once('T')?;
+    if time.len() != 6 {
+        return None
+    }
+    let time = Time::from_hms(number(time,0..2)?,number(time,2..4)?,number(time,4..6)?).ok()?;
+    Some(parse_date(date)?.with_time(time))
+}
+
+/* P1D, PT1H30M, -PT15M, P2W
+ * days are taken as 24 hours, an event that spans the clocks changing ends an hour off
+ */
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (sign,text) = match text.strip_prefix('-') {
+        Some(text) => (-1,text),
+        None => (1,text.strip_prefix('+').unwrap_or(text)),
+    };
+    let text = text.strip_prefix('P')?;
+
+    let mut seconds: i64 = 0;
+    let mut digits = String::new();
+    for c in text.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                digits.push(c);
+                continue
+            },
+            'T' => continue,
+            'W' => 7 * 86400,
+            'D' => 86400,
+            'H' => 3600,
+            'M' => 60,
+            'S' => 1,
+            _ => return None,
+        };
+        seconds += digits.parse::<i64>().ok()? * unit;
+        digits.clear();
+    }
+    digits.is_empty().then_some(Duration::seconds(sign * seconds))
+}
+
+fn parse_weekday(text: &str) -> Option<Weekday> {
+    Some(match text {
+        "MO" => Weekday::Monday,
+        "TU" => Weekday::Tuesday,
+        "WE" => Weekday::Wednesday,
+        "TH" => Weekday::Thursday,
+        "FR" => Weekday::Friday,
+        "SA" => Weekday::Saturday,
+        "SU" => Weekday::Sunday,
+        _ => return None,
+    })
+}
+
+// 28 to 31
+fn month_length(year: i32, month: Month) -> u8 {
+    (28..=31).rev()
+        .find(|day| Date::from_calendar_date(year,month,*day).is_ok())
+        .unwrap_or(28)
+}
+
+// days of a month counted from its end as -1 for the last
+fn month_day(day: i32, length: u8) -> Option<u8> {
+    let day = if day < 0 { length as i32 + 1 + day } else { day };
+    (1..=length as i32).contains(&day).then_some(day as u8)
+}
+
+/* tzdata zones loaded so far, each once however many events use it
+ * errors: one for each TZID that had to be taken as the local zone
+ */
+struct Zones<'a> {
+    source: &'a SystemSource,
+    local: Rc<Zone>,
+    utc: Rc<Zone>,
+    loaded: HashMap<String,Rc<Zone>>,
+    errors: Vec<StatusError>,
+}
+
+impl<'a> Zones<'a> {
+    fn new(source: &'a SystemSource, local: &Zone) -> Self {
+        Zones {
+            source,
+            local: Rc::new(local.clone()),
+            utc: Rc::new(Zone::utc()),
+            loaded: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /* some clients prefix the tzdata name, as in /mozilla.org/20070129_1/Europe/Berlin,
+     * and Outlook uses Windows names which are taken as the local zone and reported
+     */
+    fn get(&mut self, tzid: &str) -> Rc<Zone> {
+        if let Some(zone) = self.loaded.get(tzid) {
+            return zone.clone()
+        }
+        let parts: Vec<&str> = tzid.split('/').filter(|part| !part.is_empty()).collect();
+        let zone = (0..parts.len())
+            .find_map(|start| Zone::load(self.source,&parts[start..].join("/")).ok())
+            .map(Rc::new)
+            .unwrap_or_else(|| {
+                self.errors.push(StatusError::Missing(format!("time zone `{tzid}`, its events are in local time")));
+                self.local.clone()
+            });
+        self.loaded.insert(tzid.to_string(),zone.clone());
+        zone
+    }
+
+    fn stamp(&mut self, value: &str, tzid: Option<&str>) -> Option<Stamp> {
+        if let Some(date) = parse_date(value) {
+            return Some(Stamp::Date(date))
+        }
+        let (value,utc) = match value.strip_suffix('Z') {
+            Some(value) => (value,true),
+            None => (value,false),
+        };
+        let zone = match (utc,tzid) {
+            (true,_) => self.utc.clone(),
+            (false,Some(tzid)) => self.get(tzid),
+            (false,None) => self.local.clone(),
+        };
+        Some(Stamp::Time(parse_datetime(value)?,zone))
+    }
+
+    fn property_stamp(&mut self, property: &Property) -> Option<Stamp> {
+        self.stamp(&property.value,property.param("TZID"))
+    }
+}
+
+impl Stamp {
+    // the same moment as a wall clock reading in `zone`, dates at midnight
+    fn wall(&self, zone: &Zone) -> PrimitiveDateTime {
+        match self {
+            Stamp::Date(date) => date.midnight(),
+            Stamp::Time(time,own) => {
+                let local = zone.to_local(own.from_local(*time));
+                PrimitiveDateTime::new(local.date(),local.time())
+            },
+        }
+    }
+}
+
+impl Recurrence {
+    fn parse(rule: &str, zones: &mut Zones) -> Option<Self> {
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            week_start: Weekday::Monday,
+        };
+
+        let mut frequency = None;
+        for part in rule.split(';') {
+            let (key,value) = part.split_once('=')?;
+            let values = || value.split(',');
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => frequency = Some(match value {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return None,
+                }),
+                "INTERVAL" => recurrence.interval = value.parse().ok().filter(|interval| *interval > 0)?,
+                "COUNT" => recurrence.count = Some(value.parse().ok()?),
+                "UNTIL" => recurrence.until = Some(zones.stamp(value,None)?),
+                "BYDAY" => recurrence.by_day = values()
+                    .map(|day| {
+                        let (ordinal,weekday) = day.split_at(day.len().checked_sub(2)?);
+                        let ordinal = match ordinal {
+                            "" => None,
+                            ordinal => Some(ordinal.parse().ok()?),
+                        };
+                        Some((ordinal,parse_weekday(weekday)?))
+                    })
+                    .collect::<Option<_>>()?,
+                "BYMONTHDAY" => recurrence.by_month_day = values().map(|day| day.parse().ok()).collect::<Option<_>>()?,
+                "BYMONTH" => recurrence.by_month = values()
+                    .map(|month| Month::try_from(month.parse::<u8>().ok()?).ok())
+                    .collect::<Option<_>>()?,
+                "WKST" => recurrence.week_start = parse_weekday(value)?,
+                _ => return None,
+            }
+        }
+
+        recurrence.frequency = frequency?;
+        Some(recurrence)
+    }
+
+    fn weekday_matches(&self, date: Date) -> bool {
+        self.by_day.is_empty() || self.by_day.iter().any(|(_,weekday)| *weekday == date.weekday())
+    }
+
+    // BYMONTH and BYMONTHDAY narrowing daily and weekly rules
+    fn matches(&self, date: Date) -> bool {
+        let length = month_length(date.year(),date.month());
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (self.by_month_day.is_empty() || self.by_month_day.iter().any(|day| month_day(*day,length) == Some(date.day())))
+    }
+
+    // the days of one month a monthly or yearly rule falls on, in order
+    fn month_days(&self, year: i32, month: Month, start: Date) -> Vec<Date> {
+        let length = month_length(year,month);
+        let date = |day| Date::from_calendar_date(year,month,day).ok();
+
+        let mut days: Vec<Date> = if !self.by_month_day.is_empty() {
+            self.by_month_day.iter()
+                .filter_map(|day| date(month_day(*day,length)?))
+                .filter(|day| self.weekday_matches(*day))
+                .collect()
+        } else if !self.by_day.is_empty() {
+            self.by_day.iter()
+                .flat_map(|(ordinal,weekday)| {
+                    let all: Vec<Date> = (1..=length)
+                        .filter_map(date)
+                        .filter(|day| day.weekday() == *weekday)
+                        .collect();
+                    match ordinal {
+                        None => all,
+                        Some(n) => (if *n < 0 { all.len() as i32 + n } else { n - 1 })
+                            .try_into().ok()
+                            .and_then(|index: usize| all.get(index).copied())
+                            .into_iter()
+                            .collect(),
+                    }
+                })
+                .collect()
+        } else {
+            // months without that day are skipped, the 31st only comes up in some
+            date(start.day()).into_iter().collect()
+        };
+        days.sort();
+        days.dedup();
+        days
+    }
+
+    /* the first day of the `n`th period after the one `start` is in, and the days the
+     * rule falls on in it
+     */
+    fn period(&self, start: Date, n: u32) -> Option<(Date,Vec<Date>)> {
+        let step = n as i64 * self.interval as i64;
+        match self.frequency {
+            Frequency::Daily => {
+                let day = start.checked_add(Duration::days(step))?;
+                let days = (self.matches(day) && self.weekday_matches(day)).then_some(day);
+                Some((day,days.into_iter().collect()))
+            },
+            Frequency::Weekly => {
+                let into_week = (start.weekday().number_days_from_monday() + 7 - self.week_start.number_days_from_monday()) % 7;
+                let week = start.checked_sub(Duration::days(into_week as i64))?.checked_add(Duration::weeks(step))?;
+
+                let mut days: Vec<Date> = (0..7)
+                    .filter_map(|day| week.checked_add(Duration::days(day)))
+                    .filter(|day| match self.by_day.is_empty() {
+                        true => day.weekday() == start.weekday(),
+                        false => self.weekday_matches(*day),
+                    })
+                    .filter(|day| self.matches(*day))
+                    .collect();
+                days.sort();
+                Some((week,days))
+            },
+            Frequency::Monthly => {
+                let index = start.year() as i64 * 12 + start.month() as i64 - 1 + step;
+                let year = i32::try_from(index.div_euclid(12)).ok()?;
+                let month = Month::try_from(index.rem_euclid(12) as u8 + 1).ok()?;
+                let first = Date::from_calendar_date(year,month,1).ok()?;
+                if !self.by_month.is_empty() && !self.by_month.contains(&month) {
+                    return Some((first,Vec::new()))
+                }
+                Some((first,self.month_days(year,month,start)))
+            },
+            Frequency::Yearly => {
+                let year = i32::try_from(start.year() as i64 + step).ok()?;
+                let first = Date::from_calendar_date(year,Month::January,1).ok()?;
+                let months = match self.by_month.is_empty() {
+                    true => vec![start.month()],
+                    false => self.by_month.clone(),
+                };
+                let mut days: Vec<Date> = months.into_iter()
+                    .flat_map(|month| self.month_days(year,month,start))
+                    .collect();
+                days.sort();
+                Some((first,days))
+            },
+        }
+    }
+
+    // how many whole periods from the one `start` is in come before the one `first` is in
+    fn periods_before(&self, start: Date, first: Date) -> u32 {
+        let months = |date: Date| date.year() as i64 * 12 + date.month() as i64;
+        let periods = match self.frequency {
+            Frequency::Daily => (first - start).whole_days(),
+            Frequency::Weekly => (first - start).whole_weeks(),
+            Frequency::Monthly => months(first) - months(start),
+            Frequency::Yearly => first.year() as i64 - start.year() as i64,
+        };
+        u32::try_from(periods.max(0) / self.interval as i64).unwrap_or(u32::MAX)
+    }
+
+    /* occurrences from `start` on that fall between `first` and `last`
+     * COUNT counts from the start, so only rules without one skip the periods before `first`
+     */
+    fn expand(&self, start: PrimitiveDateTime, zone: &Zone, first: Date, last: Date) -> Vec<PrimitiveDateTime> {
+        let until = self.until.as_ref().map(|until| match until {
+            // an all-day UNTIL includes that day
+            Stamp::Date(date) => date.with_time(Time::MIDNIGHT) + Duration::DAY - Duration::SECOND,
+            until => until.wall(zone),
+        });
+
+        let mut found = Vec::new();
+        let mut count = 0;
+        let skipped = match self.count {
+            Some(_) => 0,
+            None => self.periods_before(start.date(),first),
+        };
+        for n in skipped.. {
+            let Some((period,days)) = self.period(start.date(),n) else {
+                break
+            };
+            if period > last {
+                break
+            }
+            for day in days {
+                let time = day.with_time(start.time());
+                if time < start {
+                    continue
+                }
+                if until.is_some_and(|until| time > until) || self.count.is_some_and(|max| count >= max) {
+                    return found
+                }
+                count += 1;
+                if first <= day && day <= last {
+                    found.push(time);
+                }
+            }
+        }
+        found
+    }
+}
+
+impl Event {
+    fn parse(properties: &[Property], zones: &mut Zones) -> Option<Self> {
+        let find = |name: &str| properties.iter().find(|property| property.name == name);
+
+        let start = zones.property_stamp(find("DTSTART")?)?;
+        let end = find("DTEND").and_then(|end| zones.property_stamp(end));
+        let duration = match (&start,end) {
+            (Stamp::Date(start),Some(Stamp::Date(end))) => end - *start,
+            (Stamp::Time(start,start_zone),Some(Stamp::Time(end,end_zone))) => end_zone.from_local(end) - start_zone.from_local(*start),
+            _ => match find("DURATION").and_then(|duration| parse_duration(&duration.value)) {
+                Some(duration) => duration,
+                // an all-day event is the whole day, a meeting without an end has none
+                None if matches!(start,Stamp::Date(_)) => Duration::DAY,
+                None => Duration::ZERO,
+            },
+        };
+
+        let exceptions = properties.iter()
+            .filter(|property| property.name == "EXDATE")
+            .flat_map(|property| property.value.split(',')
+                .filter_map(|value| zones.stamp(value,property.param("TZID")))
+                .collect::<Vec<_>>())
+            .collect();
+
+        // a rule this does not understand still leaves the first occurrence
+        let rule = find("RRULE").and_then(|rule| Recurrence::parse(&rule.value,zones));
+
+        Some(Event {
+            uid: find("UID").map(|uid| uid.value.clone()).unwrap_or_default(),
+            summary: find("SUMMARY").map(|summary| unescape(&summary.value)).unwrap_or_else(|| "(no title)".to_string()),
+            location: find("LOCATION").map(|location| unescape(&location.value)).filter(|location| !location.is_empty()),
+            start,
+            duration: duration.max(Duration::ZERO),
+            rule,
+            exceptions,
+            recurrence_id: find("RECURRENCE-ID").and_then(|id| zones.property_stamp(id)),
+            cancelled: find("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED")),
+        })
+    }
+
+    // occurrences overlapping `from` to `to`
+    fn occurrences(&self, from: OffsetDateTime, to: OffsetDateTime, local: &Zone) -> Vec<Occurrence> {
+        let (start,zone,all_day) = match &self.start {
+            Stamp::Date(date) => (date.midnight(),local,true),
+            Stamp::Time(time,zone) => (*time,&**zone,false),
+        };
+
+        // a day either side for the difference between the zones
+        let first = (from - self.duration).date() - Duration::DAY;
+        let last = to.date() + Duration::DAY;
+        let starts = match &self.rule {
+            Some(rule) => rule.expand(start,zone,first,last),
+            None => vec![start],
+        };
+
+        let exceptions: Vec<PrimitiveDateTime> = self.exceptions.iter().map(|stamp| stamp.wall(zone)).collect();
+        starts.into_iter()
+            .filter(|start| !exceptions.contains(start))
+            .map(|start| Occurrence {
+                summary: self.summary.clone(),
+                location: self.location.clone(),
+                start: zone.from_local(start),
+                // all-day events go from midnight to midnight even where a day is 23 hours
+                end: match all_day {
+                    true => zone.from_local(start + self.duration),
+                    false => zone.from_local(start) + self.duration,
+                },
+                all_day,
+            })
+            .filter(|occurrence| occurrence.end > from && occurrence.start < to)
+            .collect()
+    }
+}
+
+// the VEVENTs of one file, their VALARMs left out
+fn parse_events(text: &str, zones: &mut Zones) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut components: Vec<String> = Vec::new();
+    let mut properties = Vec::new();
+
+    for property in unfold(text).iter().filter_map(|line| Property::parse(line)) {
+        match property.name.as_str() {
+            "BEGIN" => {
+                components.push(property.value.to_ascii_uppercase());
+            },
+            "END" => {
+                let ended = components.pop();
+                if ended.as_deref() == Some("VEVENT") {
+                    events.extend(Event::parse(&properties,zones));
+                    properties.clear();
+                }
+            },
+            _ if components.last().map(String::as_str) == Some("VEVENT") => properties.push(property),
+            _ => {}
+        }
+    }
+    events
+}
+
+// .ics files in `dir` and the calendar directories under it
+fn ics_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) -> Result<(), StatusError> {
+    let entries = fs::read_dir(dir).map_err(|err| StatusError::Io(dir.to_path_buf(),err))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(Result::ok).map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() && depth < MAX_DEPTH {
+            // one unreadable calendar does not hide the others
+            let _ = ics_files(&path,depth + 1,files);
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ics")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl Calendars {
+    /* `paths` are .ics files or directories of them, as a vdirsyncer store keeps
+     * `local` is the bar's own zone, for all-day events and floating times
+     */
+    pub fn load(source: &SystemSource, paths: &[PathBuf], local: &Zone) -> Self {
+        let mut zones = Zones::new(source,local);
+
+        let mut calendars = Calendars::default();
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                if let Err(err) = ics_files(path,0,&mut files) {
+                    calendars.errors.push(err);
+                }
+            } else {
+                files.push(path.clone());
+            }
+        }
+
+        for file in files {
+            match fs::read_to_string(&file) {
+                Ok(text) => calendars.events.extend(parse_events(&text,&mut zones)),
+                Err(err) => calendars.errors.push(StatusError::Io(file,err)),
+            }
+        }
+
+        // a moved or cancelled occurrence replaces that one of its series
+        let replaced: Vec<(String,Stamp)> = calendars.events.iter()
+            .filter_map(|event| Some((event.uid.clone(),event.recurrence_id.clone()?)))
+            .collect();
+        for event in calendars.events.iter_mut().filter(|event| event.rule.is_some() && event.recurrence_id.is_none()) {
+            event.exceptions.extend(replaced.iter()
+                .filter(|(uid,_)| *uid == event.uid)
+                .map(|(_,stamp)| stamp.clone()));
+        }
+        calendars.events.retain(|event| !event.cancelled);
+        calendars.errors.append(&mut zones.errors);
+        calendars
+    }
+
+    // every occurrence overlapping `from` to `to`, by start time
+    pub fn upcoming(&self, from: OffsetDateTime, to: OffsetDateTime, local: &Zone) -> Vec<Occurrence> {
+        let mut occurrences: Vec<Occurrence> = self.events.iter()
+            .flat_map(|event| event.occurrences(from,to,local))
+            .collect();
+        occurrences.sort_by_key(|occurrence| (occurrence.start,!occurrence.all_day));
+        occurrences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::{date,datetime};
+
+    // zoneinfo and a Europe/Berlin localtime
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    // a vdirsyncer store with a work and a personal calendar
+    const CALENDARS: &str = "tests/fixtures/calendars";
+
+    fn load(paths: &[&str]) -> (Calendars,Zone) {
+        let source = SystemSource::new(LAPTOP);
+        let local = Zone::load(&source,"Europe/Berlin").unwrap();
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        (Calendars::load(&source,&paths,&local),local)
+    }
+
+    fn summaries(occurrences: &[Occurrence]) -> Vec<(&str,OffsetDateTime)> {
+        occurrences.iter().map(|occurrence| (occurrence.summary.as_str(),occurrence.start)).collect()
+    }
+
+    #[test]
+    fn content_lines() {
+        let lines = unfold("DESCRIPTION:a long\r\n  line\r\n\t folded\r\nSUMMARY:next\n");
+        assert_eq!(lines, ["DESCRIPTION:a long line folded", "SUMMARY:next"]);
+
+        let property = Property::parse(r#"dtstart;tzid="Custom: zone";VALUE=DATE-TIME:20261019T093000"#).unwrap();
+        assert_eq!(property.name, "DTSTART");
+        assert_eq!(property.param("TZID"), Some("Custom: zone"));
+        assert_eq!(property.value, "20261019T093000");
+
+        assert_eq!(unescape(r"Gym\, Main St\; 2nd floor\nbring a towel\\"), "Gym, Main St; 2nd floor\nbring a towel\\");
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("-P1W2D"), Some(Duration::days(-9)));
+        assert_eq!(parse_duration("PT15"), None);
+    }
+
+    #[test]
+    fn upcoming_week() {
+        let (calendars,local) = load(&[CALENDARS]);
+        assert!(calendars.errors.is_empty());
+
+        // Monday 19 October 2026, 9:00 in Berlin
+        let now = datetime!(2026-10-19 07:00 UTC);
+        let upcoming = calendars.upcoming(now,now + Duration::days(8),&local);
+        assert_eq!(summaries(&upcoming), [
+            ("Standup",datetime!(2026-10-19 07:30 UTC)),
+            ("Gym",datetime!(2026-10-19 17:00 UTC)),
+            // all day in Berlin
+            ("Birthday",datetime!(2026-10-19 22:00 UTC)),
+            ("Standup",datetime!(2026-10-20 07:30 UTC)),
+            ("Gym",datetime!(2026-10-21 17:00 UTC)),
+            ("Offsite",datetime!(2026-10-21 22:00 UTC)),
+            // Wednesday's standup is off and Thursday's is moved
+            ("Standup (moved)",datetime!(2026-10-22 09:00 UTC)),
+            // 10:00 in New York
+            ("Planning",datetime!(2026-10-22 14:00 UTC)),
+            ("Standup",datetime!(2026-10-23 07:30 UTC)),
+            ("Gym",datetime!(2026-10-23 17:00 UTC)),
+            // still 9:30 in Berlin after the clocks went back
+            ("Standup",datetime!(2026-10-26 08:30 UTC)),
+        ]);
+
+        let gym = &upcoming[1];
+        assert_eq!(gym.location.as_deref(), Some("Gym, Main St"));
+        assert_eq!(gym.end - gym.start, Duration::HOUR);
+
+        let offsite = &upcoming[5];
+        assert!(offsite.all_day);
+        assert_eq!(offsite.end, datetime!(2026-10-23 22:00 UTC));
+    }
+
+    #[test]
+    fn events_already_running() {
+        let (calendars,local) = load(&[CALENDARS]);
+
+        // the offsite's second day, during the moved standup
+        let now = datetime!(2026-10-22 09:10 UTC);
+        let upcoming = calendars.upcoming(now,now + Duration::HOUR,&local);
+        assert_eq!(summaries(&upcoming), [
+            ("Offsite",datetime!(2026-10-21 22:00 UTC)),
+            ("Standup (moved)",datetime!(2026-10-22 09:00 UTC)),
+        ]);
+    }
+
+    #[test]
+    fn recurrence_limits() {
+        let (calendars,local) = load(&[CALENDARS]);
+        let month = |from: OffsetDateTime| calendars.upcoming(from,from + Duration::days(30),&local);
+
+        // planning has 10 occurrences, the gym stops on the 25th
+        let november = month(datetime!(2026-11-01 0:00 UTC));
+        assert!(november.iter().all(|occurrence| occurrence.summary == "Standup"));
+        assert_eq!(november.len(), 21);
+
+        // yearly, and the leap day one only every four years
+        let february = |year: i32| month(Date::from_calendar_date(year,Month::February,1).unwrap().midnight().assume_utc());
+        assert!(summaries(&february(2027)).iter().all(|(summary,_)| *summary == "Standup"));
+        assert!(summaries(&february(2028)).contains(&("Leap day",datetime!(2028-02-28 23:00 UTC))));
+    }
+
+    #[test]
+    fn rule_days() {
+        let source = SystemSource::new(LAPTOP);
+        let local = Zone::utc();
+        let mut zones = Zones::new(&source,&local);
+        let start = datetime!(2026-01-30 10:00);
+        let mut rule = |text: &str| {
+            Recurrence::parse(text,&mut zones).unwrap()
+                .expand(start,&local,date!(2026-01-01),date!(2026-12-31))
+                .into_iter()
+                .map(|time| time.date())
+                .collect::<Vec<_>>()
+        };
+
+        // the 30th is skipped in February, the last Friday is not
+        assert_eq!(rule("FREQ=MONTHLY;COUNT=3"), [date!(2026-01-30), date!(2026-03-30), date!(2026-04-30)]);
+        assert_eq!(rule("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3"), [date!(2026-01-30), date!(2026-02-27), date!(2026-03-27)]);
+        assert_eq!(rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,FR;UNTIL=20260220"), [
+            date!(2026-01-30), date!(2026-02-10), date!(2026-02-13),
+        ]);
+        assert_eq!(rule("FREQ=YEARLY;BYMONTH=3,6;BYMONTHDAY=-1"), [date!(2026-03-31), date!(2026-06-30)]);
+
+        // left to the first occurrence
+        let mut zones = Zones::new(&source,&local);
+        assert!(Recurrence::parse("FREQ=HOURLY",&mut zones).is_none());
+        assert!(Recurrence::parse("FREQ=MONTHLY;BYDAY=MO,TU;BYSETPOS=-1",&mut zones).is_none());
+    }
+
+    #[test]
+    fn old_series() {
+        let source = SystemSource::new(LAPTOP);
+        let local = Zone::utc();
+        let mut zones = Zones::new(&source,&local);
+        // a Thursday twenty years back
+        let start = datetime!(2006-10-19 10:00);
+        let mut rule = |text: &str| {
+            Recurrence::parse(text,&mut zones).unwrap()
+                .expand(start,&local,date!(2026-10-19),date!(2026-11-05))
+                .into_iter()
+                .map(|time| time.date())
+                .collect::<Vec<_>>()
+        };
+
+        // straight to the window, still in step with the start
+        assert_eq!(rule("FREQ=DAILY;INTERVAL=7"), [date!(2026-10-22), date!(2026-10-29), date!(2026-11-05)]);
+        assert_eq!(rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH"), [date!(2026-10-19), date!(2026-10-22), date!(2026-11-02), date!(2026-11-05)]);
+        assert_eq!(rule("FREQ=MONTHLY;INTERVAL=3;BYMONTHDAY=1,-1"), [date!(2026-10-31)]);
+        assert_eq!(rule("FREQ=YEARLY"), [date!(2026-10-19)]);
+        // COUNT still counts from 2006
+        assert!(rule("FREQ=DAILY;COUNT=100").is_empty());
+    }
+
+    #[test]
+    fn unknown_zones() {
+        let source = SystemSource::new(LAPTOP);
+        let local = Zone::load(&source,"Europe/Berlin").unwrap();
+        let mut zones = Zones::new(&source,&local);
+        let text = "BEGIN:VEVENT\nDTSTART;TZID=W. Europe Standard Time:20261019T093000\nEND:VEVENT\n\
+            BEGIN:VEVENT\nDTSTART;TZID=W. Europe Standard Time:20261020T093000\nEND:VEVENT\n\
+            BEGIN:VEVENT\nDTSTART;TZID=/mozilla.org/20070129_1/Europe/Berlin:20261021T093000\nEND:VEVENT\n";
+
+        // kept in local time, and reported once
+        let events = parse_events(text,&mut zones);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0].start, Stamp::Time(_,zone) if zone.name == "Europe/Berlin"));
+        assert!(matches!(&zones.errors[..], [StatusError::Missing(what)] if what.contains("W. Europe Standard Time")));
+    }
+
+    #[test]
+    fn missing_calendars() {
+        let (calendars,_) = load(&["tests/fixtures/missing", "tests/fixtures/calendars/work/standup.ics"]);
+        assert!(matches!(calendars.errors[..], [StatusError::Io(..)]));
+        assert_eq!(calendars.events.len(), 2);
+    }
+}
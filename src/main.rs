@@ -15,556 +15,191 @@
 *   along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 use std::{
-    thread,
-    time::Duration,
-    sync::mpsc,
+    env,
     rc::Rc,
-    cell::Cell,
-    path::Path,
-    fs,
-    io,
+    cell::RefCell,
+    time::Duration,
 };
 
 use gtk::{
     prelude::*,
     Application,
     ApplicationWindow,
-    Box,
-    Label,
-    Button,
-    Orientation,
-    Align,
-    CssProvider,
-    StyleProvider,
-    Image,
     gio,
-    gdk::Display,
-    glib::{
-        ControlFlow,
-        clone,
+    gdk::{
+        self,
+        Display,
     },
     glib
 };
 
-use gtk4_layer_shell::{
-    LayerShell,
-    Layer,
-    Edge,
-};
-
 mod workspaces;
+mod hyprland;
 mod status;
+mod memory;
+mod health;
+mod power;
+mod sensors;
+mod disk;
+mod system;
+mod topology;
+mod timezone;
+mod ical;
+mod netlink;
+mod network;
+mod units;
+mod pulse;
 mod css;
-
-use crate::status::Cpu;
+mod theme;
+mod style;
+mod config;
+mod bar;
+mod modules;
+
+use crate::{
+    hyprland::HyprEvent,
+    config::Config,
+    modules::Registry,
+};
 
 const APP_ID: &str = "org.gtk_rs.epic_bar";
-// This cannot keep going
-fn main() -> glib::ExitCode {
-    let app = Application::builder().application_id(APP_ID).build();
-    app.connect_activate(top_bar);
-    app.connect_activate(bottom_bar);
-    app.run()
-}
-
-fn top_bar(app: &Application) {
-
-    // default css props
-    let css_prov = CssProvider::new(); 
-    css_prov.load_from_string(css::CSS);
-
-    init_style(&css_prov);
-
-    let main_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .halign(Align::BaselineFill)
-        .hexpand(true)
-        .hexpand_set(true)
-        .homogeneous(false)
-        .css_name("main-box")
-        .build();
-
-    let workspace_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .spacing(0)
-        .css_name("workspaces-container")
-        .build();
-
-    let spacer = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(true)
-        .build();
-
-    let status_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(false)
-        .build();
-    
-    // button to reveal all statuses
-    let status_reveal_button = Button::builder()
-        .css_name("status-reveal-button")
-        .label("󰁚")
-        .build();
-    let has = has_battery().unwrap_or(false);
-
-    let battery_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(false)
-        .build();
-
-    let battery_image = Image::builder()
-        .file("assets/status/battery-missing.svg")
-        .css_name("icon-image")
-        .pixel_size(20)
-        .build();
-
-    // reveal text if clicked, or hovered
-    let battery_icon = Button::builder()
-        .css_name("battery-icon")
-        .name("battery-icon")
-        .visible(true)
-        .build();
-
-    let battery_label = Button::builder()
-        .label("N/A")
-        .hexpand(false)
-        .visible(true)
-        .css_name("battery-label")
-        .name("battery-label")
-        .build();
-
-
-    let mem_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(true)
-        .build();
-    
-    let mem_icon = Image::builder()
-        .file("assets/devices/memory.svg")
-        .css_name("mem-icon")
-        .visible(true)
-        .build();
-
-    let mem_label = Button::builder()
-        .label("N/A")
-        .hexpand(false)
-        .visible(true)
-        .css_name("mem-label")
-        .build();
-
-    let cpu_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(true)
-        .build();
-
-    let cpu_image = Image::builder()
-        .file("assets/status/indicator-cpufreq.svg")
-        .css_name("cpu-image")
-        .pixel_size(20)
-        .build();
-
-    let cpu_label = Button::builder()
-        .label("-------")
-        .hexpand(true)
-        .visible(true)
-        .css_name("cpu-label")
-        .build();
-    
-    let cpu_load_label = Label::builder()
-        .label("------")
-        .hexpand(true)
-        .css_name("cpu-load-label")
-        .build();
-
-    let date_container = Button::builder()
-        .css_name("date-container")
-        .build();
-
-    let date_label= Label::builder()
-        .lines(2)
-        .css_name("date-label")
-        .name("date-label")
-        .label("date\ntime")
-        .build();
-
-    cpu_container.append(&cpu_image);
-    cpu_container.append(&cpu_load_label);
-    cpu_container.append(&cpu_label);
-
-    mem_container.append(&mem_icon);
-    mem_container.append(&mem_label);
-
-    if has {
-        battery_icon.set_child(Some(&battery_image));
-        battery_container.append(&battery_icon);
-        battery_container.append(&battery_label);
-    }
-
-    date_container.set_child(Some(&date_label));
-
-    status_container.append(&status_reveal_button);
-    status_container.append(&cpu_container);
-    status_container.append(&mem_container);
-    status_container.append(&battery_container);
-
-    let toggle = Rc::new(Cell::new(false));
-
-    // init the container
-    for n in 1..workspaces::WORKSPACE_COUNT+1 {
-        let workspace_button = Button::builder()
-            .label(format!("{}",n))
-            .name(format!("{}",n))
-            .visible(true)
-            .focus_on_click(true)
-            .build();
-
-        let nclone = n.clone();
-
-        workspace_button.connect_clicked( move |_| {
-            gio::spawn_blocking(move || {
-                workspaces::switch_workspace(nclone);
-            });
-        });
-        workspace_container.append(&workspace_button);
-    };
-
-    populate_workspace_box(&workspace_container);
-
-    main_container.append(&workspace_container);
-    main_container.append(&spacer);
-    main_container.append(&status_container);
-    main_container.append(&date_container);
-
-    // create window and set title
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .css_name("top-bar")
-        .child(&main_container)
-        .build();
-
-    LayerShell::init_layer_shell(&window);
-    LayerShell::set_layer(&window,Layer::Top);
-
-    LayerShell::auto_exclusive_zone_enable(&window);
-
-    LayerShell::set_anchor(&window, Edge::Top, true);
-    LayerShell::set_anchor(&window, Edge::Left, true);
-    LayerShell::set_anchor(&window, Edge::Right, true);
-
-    window.set_decorated(true);
-    window.present();
-
-    let (tx,rx) = mpsc::channel();
-
-    let workspace_clone = workspace_container.clone();
-    // check if workspace activity in different thread to avoid blocking
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(50));
-            if workspaces::is_activity() {
-                tx.send(()).unwrap();
-            }
-        }
-    });
-    
-    let has = has.clone();
-    let battery_image = battery_image.clone();
-    let battery_label = battery_label.clone();
-    let date_container = date_container.clone();
-    let mem_label = mem_label.clone();
-    let cpu_label = cpu_label.clone();
-    let cpu_load_label = cpu_load_label.clone();
-    let cpu_image = cpu_image.clone();
-
-    // on main thread check if signal recieved that there is to update 
-
-    glib::source::timeout_add_local(Duration::from_millis(50),move || {
-
-
-        if let Ok(_) = rx.try_recv() {
-            populate_workspace_box(&workspace_clone);
-        }
-
-        ControlFlow::Continue
-    });
-
-    // persisting data to track cpu load over time
-    let cpu = status::Cpu::new();
-    let mut cpu = cpu.clone();
+// outputs come and go in bursts, wait for them to settle before rebuilding bars
+const OUTPUT_SETTLE: Duration = Duration::from_millis(250);
 
-    // update other stuff less frequently
-    glib::source::timeout_add_seconds_local(1,move || {
-
-        if has {
-            let battery = status::get_battery_info();
-            battery_label.set_label(&format!("{}%",battery.capacity));
-            let svg_path = std::path::Path::new(&battery.icon);
-            battery_image.set_from_file(Some(&svg_path));
-            let tooltip_str = battery.tooltip_text;
-            battery_image.set_tooltip_text(Some(&tooltip_str));
-        }
-
-        let dt = status::get_datetime();
-        date_container.set_label(&format!("{dt}"));
-
-        let memory = status::get_mem_info();
+// bars created for a single output
+struct OutputBars {
+    monitor: gdk::Monitor,
+    windows: Vec<ApplicationWindow>,
+}
 
-        mem_label.set_label(&memory.string);
+/* outputs: every output that currently has bars
+ * stale: connectors hyprland re-added (e.g. powered back on), bars are rebuilt
+ * hold: keeps running with no windows while every output is off
+ * registry: every module a bar can be built from
+ */
+#[derive(Default)]
+struct Bars {
+    outputs: Vec<OutputBars>,
+    stale: Vec<String>,
+    sync_pending: bool,
+    hold: Option<gio::ApplicationHoldGuard>,
+    registry: Registry,
+}
 
-        cpu_label.set_label(&Cpu::get_avg_freq());
-        let cpu_load = cpu.get_cpu_load();
-        cpu_load_label.set_label(&format!("{:.2}%",cpu_load));
+// This cannot keep going
+fn main() -> glib::ExitCode {
+    let mut args: Vec<String> = env::args().collect();
+    let theme = theme_arg(&mut args);
 
-        cpu_image.set_from_file(Some(&cpu.get_cpu_image()));
-        
-        ControlFlow::Continue
+    let app = Application::builder().application_id(APP_ID).build();
+    let bars = Rc::new(RefCell::new(Bars {
+        registry: Registry::builtin(),
+        ..Bars::default()
+    }));
+
+    let startup_bars = bars.clone();
+    app.connect_startup(move |app| {
+        // built-in css with the user's stylesheet on top, reloaded on save
+        let config = Config::load(&startup_bars.borrow().registry.known());
+        style::init(&config.theme(theme.as_deref()));
+
+        // one socket2 reader shared by every bar
+        hyprland::listen();
+        watch_outputs(app,&startup_bars);
     });
+    app.connect_activate(move |app| sync_bars(app,&bars));
+    app.run_with_args(&args)
 }
 
-fn init_style(provider: &impl IsA<StyleProvider>) {
-    let display = Display::default();
-    gtk::style_context_add_provider_for_display(
-        &display.unwrap(),
-        provider,
-        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
-}
-
-fn populate_workspace_box(workspace_container: &Box){
-    let workspaces = workspaces::get_workspaces();
-    let mut ws_opt = workspace_container.first_child();
-
-    while let Some(ref workspace) = ws_opt {
-        let tag :usize = workspace.widget_name().as_str().parse().unwrap();
-        let workspace_info_opt = workspaces.get(&tag);
-        // make sure each workspace button has only one active class at a time
-        if let Some(workspace_info) = workspace_info_opt {
-            workspace.set_visible(true);
-            if workspace_info.active && workspace_info.windows.is_empty() {
-                workspace.remove_css_class("occupied");
-                workspace.remove_css_class("active");
-                workspace.add_css_class("empty-active");
-            } else if workspace_info.active && !workspace_info.windows.is_empty() {
-                workspace.remove_css_class("occupied");
-                workspace.remove_css_class("empty-active");
-                workspace.add_css_class("active");
-            } else {
-                workspace.remove_css_class("active");
-                workspace.remove_css_class("empty-active");
-                workspace.add_css_class("occupied");
-            }
+// take out `--theme NAME`, gtk does not know it
+fn theme_arg(args: &mut Vec<String>) -> Option<String> {
+    let i = args.iter().position(|arg| arg == "--theme" || arg.starts_with("--theme="))?;
+    let arg = args.remove(i);
+
+    match arg.strip_prefix("--theme=") {
+        Some(name) => Some(name.to_string()),
+        None if i < args.len() => Some(args.remove(i)),
+        None => {
+            eprintln!("epic-bar: --theme needs a name, one of {}",theme::names().join(", "));
+            None
         }
-        else {
-            workspace.set_visible(false);
-        }    
-        ws_opt = workspace.next_sibling();
     }
 }
 
-fn bottom_bar(app: &Application) {
-    
-    let css_prov = CssProvider::new(); 
-    css_prov.load_from_string(css::CSS);
-
-    init_style(&css_prov);
-
+fn watch_outputs(app: &Application, bars: &Rc<RefCell<Bars>>) {
+    bars.borrow_mut().hold = Some(app.hold());
 
-    let main_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .halign(Align::BaselineFill)
-        .hexpand(true)
-        .hexpand_set(true)
-        .homogeneous(false)
-        .css_name("main-box")
-        .build();
+    let display = Display::default().expect("Could not connect to a display");
 
-
-    let workspace_windows_container = Box::builder()
-        .orientation(Orientation::Horizontal)
-        .hexpand(true)
-        .vexpand(true)
-        .homogeneous(false)
-        .css_name("workspace-windows-container")
-        .build();
-
-    // so minimum vertical space is occupied when displayed
-    let reserve = Button::builder()
-        .label(" ")
-        .build();
-
-    workspace_windows_container.append(&reserve);
-
-    // needed for no shrinking in screenshot mode
-    let fill = Box::builder()
-        .hexpand(true)
-        .halign(Align::BaselineFill)
-        .build();
-
-    let fill2 = Button::builder()
-        .hexpand(true)
-        .build();
-
-    main_container.append(&workspace_windows_container);
-    main_container.append(&fill);
-    main_container.append(&fill2);
-    let window = ApplicationWindow::builder()
-        .css_name("bottom-bar")
-        .resizable(true)
-        .application(app)
-        .child(&main_container)
-        .build();
-    LayerShell::init_layer_shell(&window);
-    LayerShell::set_layer(&window,Layer::Top);
-
-    LayerShell::auto_exclusive_zone_enable(&window);
-
-    LayerShell::set_anchor(&window, Edge::Bottom, true);
-    LayerShell::set_anchor(&window, Edge::Right, true);
-    LayerShell::set_anchor(&window, Edge::Left, true);
-
-    window.set_decorated(true);
-    window.present();
-    let (tx,rx) = mpsc::channel();
-
-    // check if workspace activity in different thread to avoid blocking
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(25));
-            if workspaces::is_activity() {
-                tx.send(()).unwrap();
-            }
-        }
+    let (app_clone,bars_clone) = (app.clone(),bars.clone());
+    display.monitors().connect_items_changed(move |_,_,_,_| {
+        schedule_sync(&app_clone,&bars_clone);
     });
 
-
-
-    glib::source::idle_add_local(move || {
-        thread::sleep(Duration::from_millis(25));
-
-         if let Ok(_) = rx.try_recv() {
-            populate_windows_container(&workspace_windows_container);
+    let (app_clone,bars_clone) = (app.clone(),bars.clone());
+    hyprland::subscribe(move |event| {
+        match event {
+            HyprEvent::MonitorAdded{name} => {
+                bars_clone.borrow_mut().stale.push(name.clone());
+                schedule_sync(&app_clone,&bars_clone);
+            },
+            HyprEvent::MonitorRemoved{..} => schedule_sync(&app_clone,&bars_clone),
+            _ => {}
         }
-
-        ControlFlow::Continue
     });
-
 }
 
-fn populate_windows_container(container: &Box) {
-    let workspaces = workspaces::get_workspaces();
-
-    let sorted: &mut Vec<_> = &mut workspaces
-        .into_iter()
-        .collect();
-
-    // sort by recently used
-    sorted.sort_by(|w1,w2| w1.1.order.cmp(&w2.1.order));
-
-    let mut tag_opt = container.first_child();
-
-    while let Some(tag) = tag_opt {
-        // empty container with everything
-        container.remove(&tag);   
-        tag_opt = container.first_child()
+fn schedule_sync(app: &Application, bars: &Rc<RefCell<Bars>>) {
+    if bars.borrow().sync_pending {
+        return
     }
+    bars.borrow_mut().sync_pending = true;
 
+    let (app,bars) = (app.clone(),bars.clone());
+    glib::timeout_add_local_once(OUTPUT_SETTLE,move || {
+        bars.borrow_mut().sync_pending = false;
+        sync_bars(&app,&bars);
+    });
+}
 
-    // start filling with occupied workspaces
-    for (tag,workspace) in sorted {
-        // Box will contain 
-        //  - label of tag
-        //  - button for each window
-        //      - child is box has icon
-        //          - Initial_title of application
-
-        let workspace_windows_css = if workspace.active && !workspace.windows.is_empty() {
-            "workspace-window-box-active"
-        } else if workspace.windows.is_empty() {
-            "workspace-window-box-empty"
-        } else {
-            "workspace-window-box"
-        };
-
-        let workspace_box = Box::builder()
-            .name(format!("{}",tag))
-            .css_name(workspace_windows_css)
-            .vexpand(true)
-            .build();
-
-        let tag_label = Label::builder()
-            .label(format!("{}",tag))
-            .css_name("tag-label")
-            .build();
-
-        workspace_box.append(&tag_label);
+// tear down bars of outputs that went away and build bars for new ones
+fn sync_bars(app: &Application, bars: &Rc<RefCell<Bars>>) {
+    let Some(display) = Display::default() else {
+        return
+    };
+    let monitors: Vec<gdk::Monitor> = display.monitors()
+        .iter::<gdk::Monitor>()
+        .flatten()
+        .collect();
+    let mut bars = bars.borrow_mut();
+    let config = Config::load(&bars.registry.known());
 
-        if workspace.windows.is_empty() {
+    let stale = std::mem::take(&mut bars.stale);
 
-            let window_button = Button::builder()
-                .css_name("window-box-empty")
-                .label("󰟢")
-                .build();
+    bars.outputs.retain(|output| {
+        let is_stale = output.monitor.connector()
+            .is_some_and(|connector| stale.iter().any(|name| name.as_str() == connector.as_str()));
+        let keep = monitors.contains(&output.monitor) && !is_stale;
 
-            workspace_box.append(&window_button);
+        if !keep {
+            for window in &output.windows {
+                window.destroy();
+            }
         }
+        keep
+    });
 
-
-        for window in &workspace.windows {
-
-            // creating box to be child of button
-            // check if active or not
-            let css_name = if window.order == 0 && workspace.order == 0 {
-                "active-window-box".to_owned()
-            } else  {
-                "window-box".to_owned()
-            };
-
-            let window_button = Button::builder()
-                .css_name(&css_name)
-                .tooltip_text(&format!("{}",window.info))
-                .build();
-
-            // box has icon then label
-            let icon_label_box = Box::builder()
-                .build();
-            
-            let icon = Image::builder()
-                .file(&format!("assets/apps/{}.svg",window.class))
-                .css_name("icon-image")
-                .pixel_size(20)
-                .build();
-
-            icon_label_box.append(&icon);
-
-            window_button.set_child(Some(&icon_label_box));
-            let address = window.address.clone();
-            // switch to clicked workspace
-            window_button.connect_clicked(move |_| {
-                workspaces::switch_window(&address)
-            });
-
-            let window_label = Label::builder()
-                .label(format!("{}",window.name))
-                .css_name("window-label")
-                .build();
-
-            icon_label_box.append(&window_label);
-
-            workspace_box.append(&window_button);
+    for monitor in monitors {
+        if bars.outputs.iter().any(|output| output.monitor == monitor) {
+            continue
         }
-        container.append(&workspace_box);
-    }
-}
 
-fn has_battery() -> io::Result<bool>{
-    let power_supply = Path::new("/sys/class/power_supply");
-    if power_supply.is_dir() {
-        for entry in fs::read_dir(power_supply)? {
-            let entry = entry?;
-            let path = entry.path();
-            let path_name = path.to_str().to_owned().unwrap();
-            return Ok(path_name.contains("BAT"))
+        let connector = monitor.connector();
+        let windows = config.bars_for(connector.as_deref())
+            .iter()
+            .map(|bar| bar::build_bar(app,&monitor,bar,&bars.registry))
+            .collect();
 
-        }
+        bars.outputs.push(OutputBars { monitor, windows });
     }
-        return Ok(false)
 }
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    fs,
+};
+
+use crate::{
+    health::Pressure,
+    status::{
+        self,
+        parse_error,
+        StatusError,
+    },
+    system::SystemSource,
+};
+
+const MEMORY_INFO: &str = "/proc/meminfo";
+const BLOCK: &str = "/sys/block";
+
+/* MemoryInfo: /proc/meminfo and where memory swaps to, in bytes
+ *
+ *  used: total less available, what cannot be handed back without swapping
+ *  cached: page cache and reclaimable slab, as `free` counts it
+ *  zram: compressed swap devices in RAM, already part of swap_total
+ *  pressure: None on kernels without PSI
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryInfo {
+    pub total: u64,
+    pub available: u64,
+    pub used: u64,
+    pub free: u64,
+    pub cached: u64,
+    pub buffers: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+    pub zram: Vec<Zram>,
+    pub pressure: Option<Pressure>,
+}
+
+/* Zram: one /sys/block/zramN, in bytes
+ *  stored: what was swapped out to it
+ *  compressed: what that takes compressed
+ *  used: what it takes in RAM with its own overhead
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zram {
+    pub name: String,
+    pub size: u64,
+    pub stored: u64,
+    pub compressed: u64,
+    pub used: u64,
+}
+
+// "Key:   1234 kB" lines, in bytes
+fn meminfo_bytes(meminfo: &str) -> HashMap<&str,u64> {
+    meminfo.lines()
+        .filter_map(|line| {
+            let (key,value) = line.split_once(':')?;
+            let mut value = value.split_whitespace();
+            let number: u64 = value.next()?.parse().ok()?;
+            // HugePages_Total and the like are counts, not sizes
+            let unit = if value.next() == Some("kB") { 1024 } else { 1 };
+            Some((key,number * unit))
+        })
+        .collect()
+}
+
+// every zram device that is set up, unused ones have a disksize of 0
+fn zram_devices(source: &SystemSource) -> Vec<Zram> {
+    let Ok(entries) = fs::read_dir(source.path(BLOCK)) else {
+        return Vec::new()
+    };
+    let mut names: Vec<String> = entries.filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("zram"))
+        .collect();
+    names.sort();
+
+    names.into_iter()
+        .filter_map(|name| {
+            let size: u64 = source.read(format!("{BLOCK}/{name}/disksize")).ok()?.trim().parse().ok()?;
+            // orig_data_size compr_data_size mem_used_total mem_limit ...
+            let stat: Vec<u64> = source.read(format!("{BLOCK}/{name}/mm_stat")).ok()?
+                .split_whitespace()
+                .take(3)
+                .filter_map(|field| field.parse().ok())
+                .collect();
+            let [stored,compressed,used] = stat[..] else {
+                return None
+            };
+            (size > 0).then_some(Zram { name, size, stored, compressed, used })
+        })
+        .collect()
+}
+
+impl MemoryInfo {
+    // /proc/meminfo alone, zram and pressure are left empty
+    pub fn parse(meminfo: &str) -> Option<Self> {
+        let values = meminfo_bytes(meminfo);
+        let value = |key| values.get(key).copied().unwrap_or(0);
+
+        let total = *values.get("MemTotal")?;
+        let free = *values.get("MemFree")?;
+        // kernels before 3.14 have no MemAvailable, free and page cache come close
+        let available = values.get("MemAvailable").copied()
+            .unwrap_or(free + value("Buffers") + value("Cached"));
+
+        Some(MemoryInfo {
+            total,
+            available,
+            used: total.saturating_sub(available),
+            free,
+            cached: value("Cached") + value("SReclaimable"),
+            buffers: value("Buffers"),
+            swap_total: value("SwapTotal"),
+            swap_used: value("SwapTotal").saturating_sub(value("SwapFree")),
+            zram: Vec::new(),
+            pressure: None,
+        })
+    }
+
+    pub fn read(source: &SystemSource) -> Result<Self, StatusError> {
+        let meminfo = status::read(source,MEMORY_INFO)?;
+        let mut info = MemoryInfo::parse(&meminfo)
+            .ok_or_else(|| parse_error(source,MEMORY_INFO,"no MemTotal or MemFree"))?;
+
+        info.zram = zram_devices(source);
+        info.pressure = Pressure::read(source,"memory")?;
+        Ok(info)
+    }
+
+    pub fn percent(&self) -> u32 {
+        percent_of(self.used,self.total)
+    }
+
+    pub fn available_percent(&self) -> u32 {
+        percent_of(self.available,self.total)
+    }
+
+    /* `format` with each {placeholder} filled in, unknown ones are left as they are
+     * sizes are GiB with one decimal: used, total, available, free, cached, buffers,
+     * swap_used, swap_total; percents are whole: percent, swap_percent
+     */
+    pub fn format(&self, format: &str) -> String {
+        let gib = |bytes: u64| format!("{:.1}",bytes as f64 / 1_073_741_824.0);
+        let values = [
+            ("used",gib(self.used)),
+            ("total",gib(self.total)),
+            ("available",gib(self.available)),
+            ("free",gib(self.free)),
+            ("cached",gib(self.cached)),
+            ("buffers",gib(self.buffers)),
+            ("swap_used",gib(self.swap_used)),
+            ("swap_total",gib(self.swap_total)),
+            ("percent",self.percent().to_string()),
+            ("swap_percent",percent_of(self.swap_used,self.swap_total).to_string()),
+        ];
+
+        values.iter().fold(format.to_string(),|text,(key,value)| text.replace(&format!("{{{key}}}"),value))
+    }
+}
+
+fn percent_of(part: u64, whole: u64) -> u32 {
+    if whole == 0 {
+        return 0
+    }
+    (part as f64 / whole as f64 * 100.0).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 16 thread laptop on battery, and only its /proc/stat a second later
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    const LAPTOP_LATER: &str = "tests/fixtures/system/laptop-1s";
+    // a kernel without PSI
+    const SERVER_LATER: &str = "tests/fixtures/system/server-64-1s";
+
+    #[test]
+    fn memory_usage() {
+        let memory = MemoryInfo::read(&SystemSource::new(LAPTOP)).unwrap();
+
+        // MemTotal less MemAvailable
+        assert_eq!(memory.format("{used}/{total} GiB"), "7.8/15.5 GiB");
+        assert_eq!(memory.format("{percent}% {swap_used}/{swap_total} {unknown}"), "50% 0.1/8.0 {unknown}");
+        assert_eq!(memory.available_percent(), 50);
+        // Cached and SReclaimable
+        assert_eq!(memory.cached, (5_620_144 + 310_232) * 1024);
+
+        // the swap is all zram, 86 MiB in 21.5 MiB
+        assert_eq!(memory.zram, [Zram {
+            name: "zram0".to_string(),
+            size: 8 << 30,
+            stored: 90_177_536,
+            compressed: 22_544_384,
+            used: 24_117_248,
+        }]);
+
+        let pressure = memory.pressure.unwrap();
+        assert_eq!(pressure.some.avg10, 1.52);
+        assert_eq!(pressure.full.map(|full| full.total), Some(20_196_431));
+    }
+
+    #[test]
+    fn memory_on_old_kernels() {
+        // 3.10, before MemAvailable and PSI
+        let memory = MemoryInfo::parse("MemTotal: 4096 kB\nMemFree: 1024 kB\nBuffers: 512 kB\nCached: 512 kB\n").unwrap();
+        assert_eq!(memory.available, 2048 * 1024);
+        assert_eq!(memory.percent(), 50);
+        assert_eq!(memory.swap_total, 0);
+
+        assert_eq!(Pressure::read(&SystemSource::new(SERVER_LATER),"memory").unwrap(), None);
+        assert_eq!(MemoryInfo::parse("HugePages_Total: 0\n"), None);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let Err(err) = MemoryInfo::read(&SystemSource::new(LAPTOP_LATER)) else {
+            panic!("laptop-1s only has /proc/stat");
+        };
+        assert_eq!(
+            err.to_string(),
+            "could not read tests/fixtures/system/laptop-1s/proc/meminfo: No such file or directory (os error 2)");
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use gtk::{
+    prelude::*,
+    Widget,
+    gio,
+    glib,
+};
+
+use crate::{
+    hyprland::{
+        HyprEvent,
+        HyprlandClient,
+        HyprlandError,
+    },
+    config::{
+        ModuleConfig,
+        OptionKind,
+    },
+    status::StatusError,
+};
+
+mod workspaces;
+mod windows;
+mod reveal;
+mod cpu;
+mod memory;
+mod battery;
+mod clock;
+mod network;
+mod throughput;
+mod volume;
+mod mixer;
+mod sensors;
+mod disk;
+mod health;
+
+/* Module: one self-contained widget of a bar
+ *
+ * the bar places `widget` and then drives the module:
+ *  interval: update is called right away and then this often
+ *  listens: hyprland events are passed to `event`
+ *  click/scroll: pointer input anywhere on the widget
+ *  cleanup: the bar is destroyed, undo whatever outlives its widgets (e.g. audio server subscriptions)
+ */
+pub trait Module {
+    fn widget(&self) -> Widget;
+
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+
+    fn update(&mut self) {}
+
+    fn listens(&self) -> bool {
+        false
+    }
+
+    fn event(&mut self, _event: &HyprEvent) {}
+
+    // button as in gdk, 1 being the primary button
+    fn click(&mut self, _button: u32) {}
+
+    // true if the scroll was used, negative dy is scrolling up
+    fn scroll(&mut self, _dy: f64) -> bool {
+        false
+    }
+
+    fn cleanup(&mut self) {}
+}
+
+/* what a module is built from
+ * connector: the output the bar is on, if known
+ */
+pub struct ModuleContext<'a> {
+    pub config: &'a ModuleConfig,
+    pub connector: Option<&'a str>,
+}
+
+pub type BoxedModule = Box<dyn Module>;
+
+// None when there is nothing to show (e.g. battery on a desktop)
+pub type Constructor = fn(&ModuleContext) -> Option<BoxedModule>;
+
+// modules import gtk::Box, this saves them spelling out std's
+fn boxed<M: Module + 'static>(module: M) -> Option<BoxedModule> {
+    Some(Box::new(module))
+}
+
+/* a failed reading shows on `widget` as its tooltip and the `status-error` class,
+ * the next successful one clears the class and sets its own tooltip
+ */
+fn set_error(widget: &impl IsA<Widget>, err: Option<&StatusError>) {
+    match err {
+        Some(err) => {
+            widget.add_css_class("status-error");
+            widget.set_tooltip_text(Some(&err.to_string()));
+        },
+        None => widget.remove_css_class("status-error"),
+    }
+}
+
+// hyprland replies only once the dispatch is done, keep it off the main loop
+fn dispatch<F>(f: F)
+where
+    F: FnOnce(HyprlandClient) -> Result<(), HyprlandError> + Send + 'static {
+
+    gio::spawn_blocking(move || {
+        if let Err(err) = HyprlandClient::from_env().and_then(f) {
+            eprintln!("epic-bar: could not dispatch to hyprland: {err}");
+        }
+    });
+}
+
+/* Worker: a blocking read done on a gio thread, its result handed to `done` on the main loop
+ * asking again while a read is running queues one more run instead of another thread,
+ * so a burst of events or a read slower than its timer only ever has one read waiting
+ */
+struct Worker<T> {
+    read: Arc<dyn Fn() -> T + Send + Sync>,
+    done: Rc<dyn Fn(T)>,
+    running: Rc<Cell<bool>>,
+    again: Rc<Cell<bool>>,
+}
+
+impl<T: Send + 'static> Worker<T> {
+    fn new<R,D>(read: R, done: D) -> Self
+    where
+        R: Fn() -> T + Send + Sync + 'static,
+        D: Fn(T) + 'static {
+
+        Worker {
+            read: Arc::new(read),
+            done: Rc::new(done),
+            running: Rc::new(Cell::new(false)),
+            again: Rc::new(Cell::new(false)),
+        }
+    }
+
+    fn run(&self) {
+        if self.running.replace(true) {
+            self.again.set(true);
+            return
+        }
+
+        let (read,done) = (self.read.clone(),self.done.clone());
+        let (running,again) = (self.running.clone(),self.again.clone());
+        glib::spawn_future_local(async move {
+            loop {
+                let read = read.clone();
+                match gio::spawn_blocking(move || read()).await {
+                    Ok(value) => done(value),
+                    Err(_) => eprintln!("epic-bar: a background read panicked"),
+                }
+                if !again.replace(false) {
+                    break
+                }
+            }
+            running.set(false);
+        });
+    }
+}
+
+struct Entry {
+    name: &'static str,
+    options: &'static [(&'static str, OptionKind)],
+    new: Constructor,
+}
+
+/* Registry: every module a config can name
+ * options are the keys its table accepts besides `module`, with the type of each
+ */
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub fn builtin() -> Self {
+        use OptionKind::*;
+
+        let mut registry = Registry::default();
+        registry.register("workspaces", &[("count",Int)], workspaces::new);
+        registry.register("windows", &[], windows::new);
+        registry.register("status", &[], reveal::new);
+        registry.register("cpu", &[], cpu::new);
+        registry.register("health", &[("warning",Int), ("critical",Int)], health::new);
+        registry.register("memory", &[("format",String), ("warning",Int), ("critical",Int)], memory::new);
+        registry.register("battery", &[], battery::new);
+        registry.register("clock", &[
+            ("date",String),
+            ("time",String),
+            ("twelve_hour",Bool),
+            ("timezones",Strings),
+            ("calendars",Strings),
+            ("days",Int),
+        ], clock::new);
+        registry.register("network", &[("interface",String)], network::new);
+        registry.register("throughput", &[("interface",String)], throughput::new);
+        registry.register("volume", &[("step",Int), ("max",Int)], volume::new);
+        registry.register("mixer", &[], mixer::new);
+        registry.register("sensors", &[("sensor",String)], sensors::new);
+        registry.register("disk", &[("mounts",Strings), ("warning",Int), ("critical",Int)], disk::new);
+        registry
+    }
+
+    // registering a name again replaces the earlier module
+    pub fn register(&mut self, name: &'static str, options: &'static [(&'static str, OptionKind)], new: Constructor) {
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.push(Entry { name, options, new });
+    }
+
+    // module names with their options, for checking the config
+    pub fn known(&self) -> Vec<(&'static str, &'static [(&'static str, OptionKind)])> {
+        self.entries.iter()
+            .map(|entry| (entry.name,entry.options))
+            .collect()
+    }
+
+    pub fn build(&self, context: &ModuleContext) -> Option<BoxedModule> {
+        let entry = self.entries.iter().find(|entry| entry.name == context.config.module)?;
+        (entry.new)(context)
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    path::Path,
+    time::Duration,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Button,
+    Image,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    power,
+    status,
+    system::SystemSource,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+struct Battery {
+    container: Box,
+    image: Image,
+    label: Button,
+    source: SystemSource,
+}
+
+// nothing to show on machines without a battery
+pub fn new(_context: &ModuleContext) -> Option<BoxedModule> {
+    let source = SystemSource::default();
+    let has_battery = power::power_supplies(&source)
+        .map(|supplies| !supplies.batteries.is_empty())
+        .unwrap_or(false);
+    if !has_battery {
+        return None
+    }
+
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(false)
+        .build();
+
+    let image = Image::builder()
+        .file("assets/status/battery-missing.svg")
+        .css_name("icon-image")
+        .pixel_size(20)
+        .build();
+
+    // reveal text if clicked, or hovered
+    let icon = Button::builder()
+        .css_name("battery-icon")
+        .name("battery-icon")
+        .visible(true)
+        .build();
+
+    let label = Button::builder()
+        .label("N/A")
+        .hexpand(false)
+        .visible(true)
+        .css_name("battery-label")
+        .name("battery-label")
+        .build();
+
+    icon.set_child(Some(&image));
+    container.append(&icon);
+    container.append(&label);
+
+    boxed(Battery { container, image, label, source })
+}
+
+impl Module for Battery {
+    fn widget(&self) -> Widget {
+        self.container.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    fn update(&mut self) {
+        let battery = match status::get_battery_info(&self.source) {
+            Ok(battery) => battery,
+            Err(err) => {
+                self.label.set_label("N/A");
+                self.image.set_from_file(Some("assets/status/battery-missing.svg"));
+                set_error(&self.image,Some(&err));
+                return
+            }
+        };
+        set_error(&self.image,None);
+
+        self.label.set_label(&format!("{}%",battery.capacity));
+        let svg_path = Path::new(&battery.icon);
+        self.image.set_from_file(Some(&svg_path));
+        let tooltip_str = battery.tooltip_text;
+        self.image.set_tooltip_text(Some(&tooltip_str));
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    env,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration,Instant},
+};
+
+use gtk::{
+    glib,
+    prelude::*,
+    Align,
+    Box,
+    Calendar,
+    Label,
+    MenuButton,
+    Orientation,
+    Popover,
+    Widget,
+};
+
+use time::{
+    macros::format_description,
+    OffsetDateTime,
+};
+
+use crate::{
+    ical::{
+        Calendars,
+        Occurrence,
+    },
+    status::{
+        self,
+        ClockFormat,
+        StatusError,
+    },
+    system::SystemSource,
+    timezone::Zone,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+// days of events in the popover
+const DAYS: i64 = 7;
+// a meeting shows on the bar this long before it starts
+const NEXT_EVENT: time::Duration = time::Duration::HOUR;
+// the .ics files are read again this often
+const CALENDAR_RELOAD: Duration = Duration::from_secs(60);
+
+// the occurrences coming up and the calendars that could not be read
+type Reading = (Vec<Occurrence>,Vec<StatusError>);
+
+/* the date and time in the local zone, other zones in the tooltip,
+ * clicking opens a calendar with ISO week numbers and the events coming up
+ * date/time: `time` format descriptions, `[year]/[month]/[day]` and `[hour]:[minute]:[second]`
+ * twelve_hour: the default time with AM/PM
+ * timezones: tzdata names as in `America/New_York`
+ * calendars: .ics files or directories of them, as in a vdirsyncer store
+ * days: how far ahead events are listed
+ */
+struct Clock {
+    button: MenuButton,
+    label: Label,
+    format: ClockFormat,
+    zone: Zone,
+    // why the zone is UTC rather than the local one
+    zone_error: Option<StatusError>,
+    world: Vec<Zone>,
+    next_label: Label,
+    agenda: Rc<Agenda>,
+    // none without calendars
+    reader: Option<Worker<Reading>>,
+    loaded: Option<Instant>,
+}
+
+// the events listed in the popover, read off the main loop
+struct Agenda {
+    events: Box,
+    event_format: ClockFormat,
+    zone: Zone,
+    days: i64,
+    upcoming: RefCell<Vec<Occurrence>>,
+}
+
+// `~/` as a shell has it
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"),env::var_os("HOME")) {
+        (Some(path),Some(home)) => PathBuf::from(home).join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let config = context.config;
+    let twelve_hour = config.bool("twelve_hour").unwrap_or(false);
+    let format = ClockFormat::new(
+        config.string("date").as_deref(),
+        config.string("time").as_deref(),
+        twelve_hour,
+    );
+    // Mon 19 Oct and 9:30, seconds would only be noise
+    let event_format = ClockFormat::new(
+        Some("[weekday repr:short] [day padding:none] [month repr:short]"),
+        Some(if twelve_hour { "[hour repr:12 padding:none]:[minute] [period]" } else { "[hour]:[minute]" }),
+        twelve_hour,
+    );
+
+    let source = SystemSource::default();
+    let days = config.int("days").unwrap_or(DAYS).max(1);
+    let (zone,zone_error) = match Zone::local(&source) {
+        Ok(zone) => (zone,None),
+        Err(err) => {
+            eprintln!("epic-bar: {err}, showing UTC");
+            (Zone::utc(),Some(err))
+        },
+    };
+
+    let world = config.strings("timezones")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|name| Zone::load(&source,name)
+            .inspect_err(|err| eprintln!("epic-bar: {err}"))
+            .ok())
+        .collect();
+
+    let calendars: Vec<PathBuf> = config.strings("calendars")
+        .unwrap_or_default()
+        .iter()
+        .map(|path| expand_home(path))
+        .collect();
+
+    let label = Label::builder()
+        .lines(2)
+        .css_name("date-label")
+        .name("date-label")
+        .label("date\ntime")
+        .build();
+
+    let next_label = Label::builder()
+        .visible(false)
+        .css_name("clock-next-event")
+        .build();
+
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .build();
+    container.append(&next_label);
+    container.append(&label);
+
+    let calendar = Calendar::builder()
+        .show_week_numbers(true)
+        .build();
+
+    let events = Box::builder()
+        .orientation(Orientation::Vertical)
+        .css_name("clock-events")
+        .build();
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .css_name("clock-popover")
+        .build();
+    content.append(&calendar);
+    if !calendars.is_empty() {
+        content.append(&events);
+    }
+
+    let popover = Popover::builder()
+        .child(&content)
+        .build();
+
+    // back to today on every open, wherever it was paged to last time
+    {
+        let zone = zone.clone();
+        popover.connect_show(move |_| {
+            let today = zone.to_local(OffsetDateTime::now_utc()).date();
+            if let Ok(today) = glib::DateTime::from_utc(today.year(),today.month() as i32,today.day() as i32,0,0,0.0) {
+                calendar.select_day(&today);
+            }
+        });
+    }
+
+    let button = MenuButton::builder()
+        .css_name("date-container")
+        .child(&container)
+        .popover(&popover)
+        .build();
+
+    let agenda = Rc::new(Agenda {
+        events,
+        event_format,
+        zone: zone.clone(),
+        days,
+        upcoming: RefCell::new(Vec::new()),
+    });
+
+    let reader = (!calendars.is_empty()).then(|| {
+        let zone = zone.clone();
+        let agenda = agenda.clone();
+        Worker::new(
+            move || {
+                let now = OffsetDateTime::now_utc();
+                let loaded = Calendars::load(&source,&calendars,&zone);
+                (loaded.upcoming(now,now + time::Duration::days(days),&zone),loaded.errors)
+            },
+            move |reading| agenda.show(reading))
+    });
+
+    boxed(Clock {
+        button,
+        label,
+        format,
+        zone,
+        zone_error,
+        world,
+        next_label,
+        agenda,
+        reader,
+        loaded: None,
+    })
+}
+
+// `America/New_York` as New York
+fn city(zone: &Zone) -> String {
+    zone.name.rsplit('/').next().unwrap_or(&zone.name).replace('_'," ")
+}
+
+/* the full date with its ISO week, the local zone and one line per other zone,
+ * marked where it is already tomorrow or still yesterday
+ */
+fn tooltip(clock: &Clock, now: OffsetDateTime) -> String {
+    let local = clock.zone.to_local(now);
+    let date = local.format(format_description!("[weekday], [day padding:none] [month repr:long] [year], week [week_number repr:iso padding:none]"))
+        .unwrap_or_default();
+
+    let mut lines = vec![date,format!("{} {}",clock.zone.name,clock.zone.abbreviation(now))];
+    for zone in &clock.world {
+        let there = zone.to_local(now);
+        let Ok(time) = clock.format.format(there).map(|dt| dt.time) else {
+            continue
+        };
+        let day = match there.date().cmp(&local.date()) {
+            Ordering::Greater => " tomorrow",
+            Ordering::Less => " yesterday",
+            Ordering::Equal => "",
+        };
+        lines.push(format!("{} {time} {}{day}",city(zone),zone.abbreviation(now)));
+    }
+    lines.join("\n")
+}
+
+fn event_label(text: &str) -> Label {
+    Label::builder()
+        .label(text)
+        .halign(Align::Start)
+        .css_name("clock-event")
+        .build()
+}
+
+impl Agenda {
+    // `Mon 19 Oct 9:30 Standup`, `all day` in place of the time for all-day events
+    fn describe(&self, occurrence: &Occurrence) -> String {
+        let Ok(start) = self.event_format.format(self.zone.to_local(occurrence.start)) else {
+            return occurrence.summary.clone()
+        };
+        let time = if occurrence.all_day { "all day".to_string() } else { start.time };
+        let mut text = format!("{} {time} {}",start.date,occurrence.summary);
+        if let Some(location) = &occurrence.location {
+            text.push_str(&format!(" ({location})"));
+        }
+        text
+    }
+
+    // lists what is coming up in the popover
+    fn show(&self, (upcoming,errors): Reading) {
+        let mut child = self.events.first_child();
+        while let Some(widget) = child {
+            self.events.remove(&widget);
+            child = self.events.first_child();
+        }
+
+        for err in &errors {
+            let label = event_label(&err.to_string());
+            label.add_css_class("status-error");
+            self.events.append(&label);
+        }
+        if upcoming.is_empty() {
+            self.events.append(&event_label(&format!("No events in the next {} days",self.days)));
+        }
+        for occurrence in &upcoming {
+            self.events.append(&event_label(&self.describe(occurrence)));
+        }
+        self.upcoming.replace(upcoming);
+    }
+}
+
+impl Clock {
+    /* the next meeting once it is less than NEXT_EVENT away, until it is over
+     * all-day events are left to the popover, they would be on the bar all day
+     */
+    fn show_next_event(&self, now: OffsetDateTime) {
+        let upcoming = self.agenda.upcoming.borrow();
+        let next = upcoming.iter()
+            .find(|occurrence| !occurrence.all_day && occurrence.end > now && occurrence.start - now <= NEXT_EVENT);
+
+        let Some(next) = next else {
+            self.next_label.set_visible(false);
+            return
+        };
+        let text = match (next.start - now).whole_seconds() {
+            ..=0 => format!("󰃰 {} now",next.summary),
+            // rounded up, a meeting 30 seconds away is not in 0 minutes
+            seconds => format!("󰃰 {} in {} min",next.summary,(seconds + 59) / 60),
+        };
+        self.next_label.set_label(&text);
+        self.next_label.set_tooltip_text(Some(&self.agenda.describe(next)));
+        self.next_label.set_visible(true);
+    }
+}
+
+impl Module for Clock {
+    fn widget(&self) -> Widget {
+        self.button.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    fn update(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        match status::get_datetime(&self.zone,&self.format) {
+            Ok(dt) => {
+                self.label.set_label(&format!("{dt}"));
+                set_error(&self.button,self.zone_error.as_ref());
+                if self.zone_error.is_none() {
+                    self.button.set_tooltip_text(Some(&tooltip(self,now)));
+                }
+            },
+            Err(err) => {
+                self.label.set_label("--/--/--\n--:--:--");
+                set_error(&self.button,Some(&err));
+            }
+        }
+
+        let Some(reader) = &self.reader else {
+            return
+        };
+        if self.loaded.is_none_or(|loaded| loaded.elapsed() >= CALENDAR_RELOAD) {
+            reader.run();
+            self.loaded = Some(Instant::now());
+        }
+        self.show_next_event(now);
+    }
+}
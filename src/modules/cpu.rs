@@ -0,0 +1,335 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk::{
+    cairo::Context,
+    prelude::*,
+    Align,
+    Box,
+    DrawingArea,
+    Image,
+    Label,
+    MenuButton,
+    Orientation,
+    Popover,
+    Widget,
+};
+
+use crate::{
+    status::{
+        Cpu,
+        CpuHistory,
+        StatusError,
+    },
+    system::SystemSource,
+    topology::{
+        format_frequency,
+        CpuTopology,
+        Frequencies,
+    },
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+// seconds a core has to stay busy to be called out in the popover
+const PINNED_SECONDS: usize = 10;
+
+/* load, average frequency and an icon for how busy the cpu is
+ * clicking opens a popover with the load of each core and of the last minute
+ */
+struct CpuModule {
+    button: MenuButton,
+    container: Box,
+    image: Image,
+    load_label: Label,
+    freq_label: Label,
+    popover: Popover,
+    cores: DrawingArea,
+    graph: DrawingArea,
+    pinned: Label,
+    // persisting data to track cpu load over time
+    cpu: Cpu,
+    history: Rc<RefCell<CpuHistory>>,
+    // read on the first update, again after any failed reading
+    topology: Option<CpuTopology>,
+    source: SystemSource,
+}
+
+pub fn new(_context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(true)
+        .build();
+
+    let image = Image::builder()
+        .file("assets/status/indicator-cpufreq.svg")
+        .css_name("cpu-image")
+        .pixel_size(20)
+        .build();
+
+    let freq_label = Label::builder()
+        .label("-------")
+        .hexpand(true)
+        .css_name("cpu-label")
+        .build();
+
+    let load_label = Label::builder()
+        .label("------")
+        .hexpand(true)
+        .css_name("cpu-load-label")
+        .build();
+
+    container.append(&image);
+    container.append(&load_label);
+    container.append(&freq_label);
+
+    let history = Rc::new(RefCell::new(CpuHistory::default()));
+
+    let cores = DrawingArea::builder()
+        .content_width(240)
+        .content_height(80)
+        .css_name("cpu-cores")
+        .build();
+    {
+        let history = history.clone();
+        cores.set_draw_func(move |area,cr,width,height| {
+            draw_cores(area,cr,width as f64,height as f64,&history.borrow());
+        });
+    }
+
+    let graph = DrawingArea::builder()
+        .content_width(240)
+        .content_height(80)
+        .css_name("cpu-graph")
+        .build();
+    {
+        let history = history.clone();
+        graph.set_draw_func(move |area,cr,width,height| {
+            draw_history(area,cr,width as f64,height as f64,&history.borrow());
+        });
+    }
+
+    let pinned = Label::builder()
+        .halign(Align::Start)
+        .visible(false)
+        .css_name("cpu-pinned")
+        .build();
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .css_name("cpu-popover")
+        .build();
+    content.append(&heading("Cores"));
+    content.append(&cores);
+    content.append(&pinned);
+    content.append(&heading("Last minute, average and busiest core"));
+    content.append(&graph);
+
+    let popover = Popover::builder()
+        .child(&content)
+        .build();
+
+    let button = MenuButton::builder()
+        .css_name("cpu-button")
+        .child(&container)
+        .popover(&popover)
+        .build();
+
+    let source = SystemSource::default();
+
+    boxed(CpuModule {
+        button,
+        container,
+        image,
+        load_label,
+        freq_label,
+        popover,
+        cores,
+        graph,
+        pinned,
+        cpu: Cpu::new(),
+        history,
+        topology: None,
+        source,
+    })
+}
+
+fn heading(title: &str) -> Label {
+    Label::builder()
+        .label(title)
+        .halign(Align::Start)
+        .css_name("cpu-heading")
+        .build()
+}
+
+fn set_color(area: &DrawingArea, cr: &Context, alpha: f64) {
+    let color = area.color();
+    cr.set_source_rgba(
+        color.red() as f64,
+        color.green() as f64,
+        color.blue() as f64,
+        color.alpha() as f64 * alpha,
+    );
+}
+
+/* one bar per core from the newest sample, over a faint full-height track
+ * cores busy for PINNED_SECONDS are drawn solid, the rest a little dimmer
+ */
+fn draw_cores(area: &DrawingArea, cr: &Context, width: f64, height: f64, history: &CpuHistory) {
+    let cores = history.latest();
+    if cores.is_empty() {
+        return
+    }
+
+    let pinned = history.pinned(PINNED_SECONDS);
+    let slot = width / cores.len() as f64;
+    // thin bars on many cores still leave a gap between them
+    let gap = if slot > 4.0 { 1.0 } else { 0.0 };
+
+    for (n,(core,load)) in cores.iter().enumerate() {
+        let x = n as f64 * slot + gap;
+        let bar = height * (*load as f64 / 100.0).clamp(0.0,1.0);
+
+        set_color(area,cr,0.15);
+        cr.rectangle(x,0.0,slot - gap * 2.0,height);
+        let _ = cr.fill();
+
+        set_color(area,cr,if pinned.contains(core) { 1.0 } else { 0.6 });
+        cr.rectangle(x,height - bar,slot - gap * 2.0,bar);
+        let _ = cr.fill();
+    }
+}
+
+/* the last minute scrolling in from the right
+ * average load filled, the busiest core of each second as a line above it
+ */
+fn draw_history(area: &DrawingArea, cr: &Context, width: f64, height: f64, history: &CpuHistory) {
+    let averages: Vec<f32> = history.averages().collect();
+    if averages.is_empty() {
+        return
+    }
+
+    let step = width / (history.capacity() - 1) as f64;
+    let start = width - (averages.len() - 1) as f64 * step;
+    let y = |load: f32| height - height * (load as f64 / 100.0).clamp(0.0,1.0);
+
+    cr.move_to(start,height);
+    for (n,load) in averages.iter().enumerate() {
+        cr.line_to(start + n as f64 * step,y(*load));
+    }
+    cr.line_to(width,height);
+    cr.close_path();
+    set_color(area,cr,0.4);
+    let _ = cr.fill();
+
+    for (n,load) in history.busiest().enumerate() {
+        let x = start + n as f64 * step;
+        if n == 0 {
+            cr.move_to(x,y(load));
+        } else {
+            cr.line_to(x,y(load));
+        }
+    }
+    set_color(area,cr,1.0);
+    cr.set_line_width(1.5);
+    let _ = cr.stroke();
+}
+
+// min/avg/max, and each kind of core on hybrid cpus
+fn frequency_tooltip(freqs: &Frequencies) -> String {
+    let mut text = format!(
+        "min {}\navg {}\nmax {}",
+        format_frequency(freqs.min),
+        format_frequency(freqs.avg),
+        format_frequency(freqs.max),
+    );
+    if let (Some(performance),Some(efficiency)) = (freqs.performance,freqs.efficiency) {
+        text.push_str(&format!(
+            "\nP-cores {}\nE-cores {}",
+            format_frequency(performance),
+            format_frequency(efficiency),
+        ));
+    }
+    text
+}
+
+impl CpuModule {
+    fn show_pinned(&self) {
+        let pinned = self.history.borrow().pinned(PINNED_SECONDS);
+        self.pinned.set_visible(!pinned.is_empty());
+        if pinned.is_empty() {
+            return
+        }
+
+        let cores: Vec<String> = pinned.iter().map(|core| format!("cpu{core}")).collect();
+        self.pinned.set_label(&format!("{} busy for over {PINNED_SECONDS} s",cores.join(", ")));
+    }
+
+    fn frequencies(&mut self) -> Result<Frequencies, StatusError> {
+        let topology = match self.topology.take() {
+            Some(topology) => topology,
+            None => CpuTopology::read(&self.source)?,
+        };
+        let freqs = topology.frequencies(&self.source);
+        // policies come and go with hotplugged clusters, the next update reads them again
+        if freqs.is_ok() {
+            self.topology = Some(topology);
+        }
+        freqs
+    }
+}
+
+impl Module for CpuModule {
+    fn widget(&self) -> Widget {
+        self.button.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    fn update(&mut self) {
+        let freq = self.frequencies();
+        let load = self.cpu.get_cpu_load(&self.source);
+
+        match &freq {
+            Ok(freq) => {
+                self.freq_label.set_label(&format_frequency(freq.avg));
+                self.freq_label.set_tooltip_text(Some(&frequency_tooltip(freq)));
+            },
+            Err(_) => {
+                self.freq_label.set_label("N/A");
+                self.freq_label.set_tooltip_text(None);
+            },
+        }
+        match &load {
+            Ok(load) => {
+                self.load_label.set_label(&format!("{:.2}%",load));
+                self.image.set_from_file(Some(&self.cpu.get_cpu_image()));
+                self.history.borrow_mut().push(&self.cpu);
+            },
+            Err(_) => self.load_label.set_label("N/A"),
+        }
+
+        // the history keeps filling while closed, only drawing waits
+        if self.popover.is_visible() {
+            self.show_pinned();
+            self.cores.queue_draw();
+            self.graph.queue_draw();
+        }
+
+        set_error(&self.container,load.as_ref().err().or(freq.as_ref().err()));
+        if freq.is_ok() && load.is_ok() {
+            self.container.set_tooltip_text(None);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    cell::RefCell,
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    disk::{
+        self,
+        DiskIo,
+        DiskStats,
+        Mount,
+        Usage,
+    },
+    status::StatusError,
+    system::SystemSource,
+    units,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+// percent full
+const WARNING: u32 = 85;
+const CRITICAL: u32 = 95;
+
+/* how full each configured mount is and the read/write rates of the disks,
+ * every real filesystem in the tooltip
+ * mounts: mount points to show, `/` if left out
+ * warning/critical: a mount is marked once it is this percent full
+ */
+struct DiskModule {
+    view: Rc<DiskView>,
+    // statvfs on a share that went away blocks until it times out, nothing is read on the main loop
+    reader: Worker<Reading>,
+}
+
+struct DiskView {
+    container: Box,
+    mounts: Vec<(PathBuf,Label)>,
+    io_label: Label,
+    io: RefCell<DiskIo>,
+    warning: u32,
+    critical: u32,
+}
+
+/* everything one update shows
+ * usages: of the configured mounts, in order
+ * filesystems: every real one for the tooltip, network shares without a usage
+ */
+struct Reading {
+    usages: Vec<Result<Usage,StatusError>>,
+    stats: Result<DiskStats,StatusError>,
+    filesystems: Result<Vec<(Mount,Option<Result<Usage,StatusError>>)>,StatusError>,
+}
+
+fn read(paths: &[PathBuf], source: &SystemSource) -> Reading {
+    let filesystems = disk::mounts(source).map(|mounts| mounts.into_iter()
+        .map(|mount| {
+            let usage = (!mount.is_network()).then(|| Usage::read(&mount.mount_point));
+            (mount,usage)
+        })
+        .collect());
+
+    Reading {
+        usages: paths.iter().map(|path| Usage::read(path)).collect(),
+        stats: DiskStats::read(source),
+        filesystems,
+    }
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(false)
+        .build();
+
+    let mounts: Vec<(PathBuf,Label)> = context.config.strings("mounts")
+        .unwrap_or_else(|| vec!["/".to_string()])
+        .into_iter()
+        .map(|mount| {
+            let label = Label::builder()
+                .label(format!("{mount} N/A"))
+                .css_name("disk-label")
+                .build();
+            container.append(&label);
+            (PathBuf::from(mount),label)
+        })
+        .collect();
+
+    let io_label = Label::builder()
+        .label("N/A")
+        .css_name("disk-io-label")
+        .build();
+    container.append(&io_label);
+
+    let paths: Vec<PathBuf> = mounts.iter().map(|(path,_)| path.clone()).collect();
+    let view = Rc::new(DiskView {
+        container,
+        mounts,
+        io_label,
+        io: RefCell::new(DiskIo::new()),
+        warning: context.config.percent("warning",WARNING),
+        critical: context.config.percent("critical",CRITICAL),
+    });
+
+    let source = SystemSource::default();
+    let reader = {
+        let view = view.clone();
+        Worker::new(move || read(&paths,&source),move |reading| view.show(reading))
+    };
+
+    boxed(DiskModule { view, reader })
+}
+
+fn usage_text(usage: &Usage) -> String {
+    format!("{}/{} ({}%)",
+        units::format_bytes(usage.used() as f64),
+        units::format_bytes(usage.total as f64),
+        usage.percent())
+}
+
+// one line per filesystem
+fn tooltip(filesystems: &[(Mount,Option<Result<Usage,StatusError>>)], io: &DiskIo) -> String {
+    let mut lines: Vec<String> = filesystems.iter()
+        .map(|(mount,usage)| {
+            let usage = match usage {
+                None => "network share".to_string(),
+                Some(Ok(usage)) => usage_text(usage),
+                Some(Err(err)) => err.to_string(),
+            };
+            format!("{} ({}, {}): {usage}",mount.mount_point.display(),mount.fs_type,mount.device)
+        })
+        .collect();
+
+    lines.push(format!("Read: {}",units::format_bytes(io.read_total as f64)));
+    lines.push(format!("Written: {}",units::format_bytes(io.write_total as f64)));
+    lines.join("\n")
+}
+
+impl DiskView {
+    fn show(&self, reading: Reading) {
+        for ((mount,label),usage) in self.mounts.iter().zip(reading.usages) {
+            label.remove_css_class("warning");
+            label.remove_css_class("critical");
+
+            match usage {
+                Ok(usage) => {
+                    set_error(label,None);
+                    label.set_label(&format!("{} {}%",mount.display(),usage.percent()));
+                    label.set_tooltip_text(Some(&usage_text(&usage)));
+
+                    if usage.percent() >= self.critical {
+                        label.add_css_class("critical");
+                    } else if usage.percent() >= self.warning {
+                        label.add_css_class("warning");
+                    }
+                },
+                Err(err) => {
+                    label.set_label(&format!("{} N/A",mount.display()));
+                    set_error(label,Some(&err));
+                },
+            }
+        }
+
+        let mut io = self.io.borrow_mut();
+        match reading.stats {
+            Ok(stats) => {
+                io.add(&stats);
+                set_error(&self.io_label,None);
+                self.io_label.set_label(&format!("R {} W {}",
+                    units::format_rate(io.read_rate),
+                    units::format_rate(io.write_rate)));
+
+                let text = match &reading.filesystems {
+                    Ok(filesystems) => tooltip(filesystems,&io),
+                    Err(err) => err.to_string(),
+                };
+                self.io_label.set_tooltip_text(Some(&text));
+            },
+            Err(err) => {
+                self.io_label.set_label("N/A");
+                set_error(&self.io_label,Some(&err));
+            },
+        }
+    }
+}
+
+impl Module for DiskModule {
+    fn widget(&self) -> Widget {
+        self.view.container.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(2))
+    }
+
+    fn update(&mut self) {
+        self.reader.run();
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use gtk::{
+    prelude::*,
+    Label,
+    Widget,
+};
+
+use crate::{
+    health::Health,
+    system::SystemSource,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+// percent of the last 10 seconds some task stalled on cpu, io or memory
+const WARNING: u32 = 10;
+const CRITICAL: u32 = 25;
+
+/* the 1 minute load average, coloured by the worst pressure stall
+ * warning/critical: stall percents where it turns the warning and error colour
+ */
+struct HealthModule {
+    label: Label,
+    warning: f32,
+    critical: f32,
+    source: SystemSource,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let label = Label::builder()
+        .label("N/A")
+        .css_name("health-label")
+        .build();
+
+    boxed(HealthModule {
+        label,
+        warning: context.config.percent("warning",WARNING) as f32,
+        critical: context.config.percent("critical",CRITICAL) as f32,
+        source: SystemSource::default(),
+    })
+}
+
+fn tooltip(health: &Health) -> String {
+    let load = &health.load;
+    let mut lines = vec![
+        format!("Load {:.2} {:.2} {:.2} (1m / 5m / 15m)",load.one,load.five,load.fifteen),
+        format!("{} of {} tasks runnable",load.running,load.tasks),
+    ];
+
+    if health.pressure.is_empty() {
+        lines.push("No pressure stall information in this kernel".to_string());
+    } else {
+        lines.push("Stalled (10s / 1m / 5m):".to_string());
+    }
+    for (resource,stall) in &health.pressure {
+        lines.push(format!("{resource} {:.2}% / {:.2}% / {:.2}%",
+            stall.some.avg10,
+            stall.some.avg60,
+            stall.some.avg300));
+    }
+    lines.join("\n")
+}
+
+impl Module for HealthModule {
+    fn widget(&self) -> Widget {
+        self.label.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(2))
+    }
+
+    fn update(&mut self) {
+        self.label.remove_css_class("warning");
+        self.label.remove_css_class("critical");
+
+        let health = match Health::read(&self.source) {
+            Ok(health) => health,
+            Err(err) => {
+                self.label.set_label("N/A");
+                set_error(&self.label,Some(&err));
+                return
+            }
+        };
+        set_error(&self.label,None);
+
+        self.label.set_label(&format!("󰓅 {:.2}",health.load.one));
+        self.label.set_tooltip_text(Some(&tooltip(&health)));
+
+        match health.worst() {
+            Some((_,stall)) if stall >= self.critical => self.label.add_css_class("critical"),
+            Some((_,stall)) if stall >= self.warning => self.label.add_css_class("warning"),
+            _ => {}
+        }
+    }
+}
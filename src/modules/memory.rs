@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use gtk::{
+    prelude::*,
+    Box,
+    Button,
+    Image,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    memory::MemoryInfo,
+    system::SystemSource,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+const FORMAT: &str = "{used}/{total} GiB";
+// percent of memory still available
+const WARNING: u32 = 20;
+const CRITICAL: u32 = 10;
+
+/* memory in use, with the breakdown, swap and pressure in the tooltip
+ * format: placeholders as in MemoryInfo::format
+ * warning/critical: the label is marked once less than this percent is available
+ */
+struct Memory {
+    container: Box,
+    label: Button,
+    format: String,
+    warning: u32,
+    critical: u32,
+    source: SystemSource,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(true)
+        .build();
+
+    let icon = Image::builder()
+        .file("assets/devices/memory.svg")
+        .css_name("mem-icon")
+        .visible(true)
+        .build();
+
+    let label = Button::builder()
+        .label("N/A")
+        .hexpand(false)
+        .visible(true)
+        .css_name("mem-label")
+        .build();
+
+    container.append(&icon);
+    container.append(&label);
+
+    boxed(Memory {
+        container,
+        label,
+        format: context.config.string("format").unwrap_or_else(|| FORMAT.to_string()),
+        warning: context.config.percent("warning",WARNING),
+        critical: context.config.percent("critical",CRITICAL),
+        source: SystemSource::default(),
+    })
+}
+
+fn tooltip(memory: &MemoryInfo) -> String {
+    let mut text = memory.format(
+        "Used {used} GiB ({percent}%)\n\
+         Available {available} GiB\n\
+         Cached {cached} GiB\n\
+         Buffers {buffers} GiB");
+
+    if memory.swap_total > 0 {
+        text.push_str(&memory.format("\nSwap {swap_used}/{swap_total} GiB ({swap_percent}%)"));
+    }
+
+    let mib = |bytes: u64| bytes as f64 / 1_048_576.0;
+    for zram in &memory.zram {
+        text.push_str(&format!("\n{} {:.0} MiB in {:.0} MiB",zram.name,mib(zram.stored),mib(zram.used)));
+        if zram.compressed > 0 {
+            text.push_str(&format!(" ({:.1}x)",zram.stored as f64 / zram.compressed as f64));
+        }
+    }
+
+    if let Some(pressure) = memory.pressure {
+        text.push_str(&format!(
+            "\nStalled {:.2}% / {:.2}% / {:.2}% (10s / 1m / 5m)",
+            pressure.some.avg10,
+            pressure.some.avg60,
+            pressure.some.avg300,
+        ));
+    }
+    text
+}
+
+impl Module for Memory {
+    fn widget(&self) -> Widget {
+        self.container.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    fn update(&mut self) {
+        self.label.remove_css_class("warning");
+        self.label.remove_css_class("critical");
+
+        match MemoryInfo::read(&self.source) {
+            Ok(memory) => {
+                self.label.set_label(&memory.format(&self.format));
+                set_error(&self.container,None);
+                self.container.set_tooltip_text(Some(&tooltip(&memory)));
+
+                let available = memory.available_percent();
+                if available < self.critical {
+                    self.label.add_css_class("critical");
+                } else if available < self.warning {
+                    self.label.add_css_class("warning");
+                }
+            },
+            Err(err) => {
+                self.label.set_label("N/A");
+                set_error(&self.container,Some(&err));
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    cell::{Cell,RefCell},
+    rc::Rc,
+};
+
+use gtk::{
+    prelude::*,
+    Align,
+    Box,
+    GestureClick,
+    Image,
+    Label,
+    MenuButton,
+    Orientation,
+    Popover,
+    PropagationPhase,
+    Scale,
+    ToggleButton,
+    Widget,
+};
+
+use crate::pulse::{
+    self,
+    AudioState,
+    Stream,
+    StreamKind,
+    SubscriptionId,
+};
+
+use super::{
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+/* microphone in use indicator and a popover with a slider per application
+ * the mic icon only shows while some application is recording
+ */
+struct Mixer {
+    button: MenuButton,
+    subscription: SubscriptionId,
+}
+
+// one stream in the popover, kept across updates so a slider being dragged survives
+struct Row {
+    container: Box,
+    label: Label,
+    scale: Scale,
+    mute: ToggleButton,
+    stream: Rc<RefCell<Stream>>,
+    // the pointer is down on the slider
+    held: Rc<Cell<bool>>,
+}
+
+struct Popup {
+    playback: Box,
+    record: Box,
+    rows: RefCell<Vec<Row>>,
+    // set while widgets are updated from the server, so it is not sent back
+    updating: Rc<Cell<bool>>,
+}
+
+pub fn new(_context: &ModuleContext) -> Option<BoxedModule> {
+    let icons = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .build();
+
+    let mic = Image::builder()
+        .file("assets/status/mic-on.svg")
+        .css_name("icon-image")
+        .pixel_size(20)
+        .visible(false)
+        .build();
+
+    let mixer = Image::builder()
+        .file("assets/status/audio-on.svg")
+        .css_name("icon-image")
+        .pixel_size(20)
+        .build();
+
+    icons.append(&mic);
+    icons.append(&mixer);
+
+    let popup = Rc::new(Popup {
+        playback: section("Playback"),
+        record: section("Recording"),
+        rows: RefCell::new(Vec::new()),
+        updating: Rc::new(Cell::new(false)),
+    });
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .css_name("mixer-popover")
+        .build();
+    content.append(&popup.playback);
+    content.append(&popup.record);
+
+    let popover = Popover::builder()
+        .child(&content)
+        .build();
+
+    let button = MenuButton::builder()
+        .css_name("mixer-button")
+        .child(&icons)
+        .popover(&popover)
+        .build();
+
+    let subscription = {
+        let button = button.clone();
+        pulse::subscribe(move |state| {
+            show(state,&button,&mic);
+            popup.sync(state.as_ref().ok());
+        })
+    };
+
+    boxed(Mixer { button, subscription })
+}
+
+fn section(title: &str) -> Box {
+    let section = Box::builder()
+        .orientation(Orientation::Vertical)
+        .build();
+
+    let heading = Label::builder()
+        .label(title)
+        .halign(Align::Start)
+        .css_name("mixer-heading")
+        .build();
+    section.append(&heading);
+
+    section
+}
+
+fn show(state: &Result<AudioState,String>, button: &MenuButton, mic: &Image) {
+    let state = match state {
+        Ok(state) => state,
+        Err(err) => {
+            mic.set_visible(false);
+            button.set_tooltip_text(Some(err));
+            return
+        }
+    };
+
+    let recording: Vec<&str> = state.capturing()
+        .map(|stream| stream.name.as_str())
+        .collect();
+
+    mic.set_visible(!recording.is_empty());
+    if recording.is_empty() {
+        button.set_tooltip_text(Some("Mixer"));
+    } else {
+        button.set_tooltip_text(Some(&format!("Recording: {}",recording.join(", "))));
+    }
+}
+
+impl Row {
+    fn new(initial: &Stream, updating: &Rc<Cell<bool>>) -> Self {
+        let container = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .css_name("mixer-row")
+            .build();
+
+        let label = Label::builder()
+            .halign(Align::Start)
+            .width_chars(16)
+            .max_width_chars(16)
+            .ellipsize(gtk::pango::EllipsizeMode::End)
+            .css_name("mixer-label")
+            .build();
+
+        let scale = Scale::with_range(Orientation::Horizontal,0.0,100.0,1.0);
+        scale.set_width_request(160);
+        scale.set_draw_value(true);
+
+        let mute = ToggleButton::builder()
+            .label("mute")
+            .css_name("mixer-mute")
+            .build();
+
+        container.append(&label);
+        container.append(&scale);
+        container.append(&mute);
+
+        let stream = Rc::new(RefCell::new(initial.clone()));
+
+        // watching ahead of the scale's own gestures, without taking the press from them
+        let held = Rc::new(Cell::new(false));
+        let press = GestureClick::builder()
+            .propagation_phase(PropagationPhase::Capture)
+            .build();
+        {
+            let held = held.clone();
+            press.connect_begin(move |_,_| held.set(true));
+        }
+        {
+            let held = held.clone();
+            press.connect_end(move |_,_| held.set(false));
+        }
+        scale.add_controller(press);
+
+        {
+            let (stream,updating) = (stream.clone(),updating.clone());
+            scale.connect_value_changed(move |scale| {
+                if updating.get() {
+                    return
+                }
+                let stream = stream.borrow();
+                let (kind,index) = (stream.kind,stream.index);
+                let volume = stream.volume_at(scale.value().round() as u32);
+                pulse::change(move |connection| connection.set_stream_volume(kind,index,&volume));
+            });
+        }
+
+        {
+            let (stream,updating) = (stream.clone(),updating.clone());
+            mute.connect_toggled(move |mute| {
+                if updating.get() {
+                    return
+                }
+                let (kind,index) = (stream.borrow().kind,stream.borrow().index);
+                let muted = mute.is_active();
+                pulse::change(move |connection| connection.set_stream_mute(kind,index,muted));
+            });
+        }
+
+        let row = Row { container, label, scale, mute, stream, held };
+        row.set(initial.clone());
+        row
+    }
+
+    fn set(&self, stream: Stream) {
+        self.label.set_label(&stream.name);
+        self.label.set_tooltip_text(Some(&stream.name));
+        // the server echoes every step of a drag rounded to a percent, the knob would jump under the pointer
+        if !self.held.get() {
+            self.scale.set_value(stream.percent() as f64);
+        }
+        self.scale.set_sensitive(stream.has_volume);
+        self.mute.set_active(stream.muted);
+        self.stream.replace(stream);
+    }
+
+    fn is(&self, stream: &Stream) -> bool {
+        let current = self.stream.borrow();
+        current.kind == stream.kind && current.index == stream.index
+    }
+}
+
+impl Popup {
+    fn section(&self, kind: StreamKind) -> &Box {
+        match kind {
+            StreamKind::Playback => &self.playback,
+            StreamKind::Record => &self.record,
+        }
+    }
+
+    // add, update and drop rows to match the server, new streams go last in their section
+    fn sync(&self, state: Option<&AudioState>) {
+        let streams = state.map(|state| state.streams.as_slice()).unwrap_or_default();
+        let mut rows = self.rows.borrow_mut();
+        self.updating.set(true);
+
+        rows.retain(|row| {
+            let keep = streams.iter().any(|stream| row.is(stream));
+            if !keep {
+                self.section(row.stream.borrow().kind).remove(&row.container);
+            }
+            keep
+        });
+
+        for stream in streams {
+            match rows.iter().find(|row| row.is(stream)) {
+                Some(row) => row.set(stream.clone()),
+                None => {
+                    let row = Row::new(stream,&self.updating);
+                    self.section(stream.kind).append(&row.container);
+                    rows.push(row);
+                }
+            }
+        }
+
+        self.updating.set(false);
+        self.record.set_visible(streams.iter().any(|stream| stream.kind == StreamKind::Record));
+    }
+}
+
+impl Module for Mixer {
+    fn widget(&self) -> Widget {
+        self.button.clone().upcast()
+    }
+
+    // as with Volume, the subscriber keeps the button and so this module alive
+    fn cleanup(&mut self) {
+        pulse::unsubscribe(self.subscription);
+    }
+}
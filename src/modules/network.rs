@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use gtk::{
+    prelude::*,
+    Box,
+    Image,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    netlink::NetlinkError,
+    network::{
+        self,
+        Link,
+    },
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+const SIGNAL_ICONS: [&str;5] = [
+    "assets/status/network-wireless-signal-none.svg",
+    "assets/status/network-wireless-signal-weak.svg",
+    "assets/status/network-wireless-signal-ok.svg",
+    "assets/status/network-wireless-signal-good.svg",
+    "assets/status/network-wireless-signal-excellent.svg",
+];
+
+/* ssid or interface name with an icon for the signal strength
+ * interface: only show this interface instead of picking one
+ */
+struct Network {
+    container: Box,
+    // the netlink dumps wait up to a second for the kernel, they are not done on the main loop
+    links: Worker<Result<Vec<Link>,NetlinkError>>,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(false)
+        .build();
+
+    let image = Image::builder()
+        .file("assets/status/network-wireless-off.svg")
+        .css_name("icon-image")
+        .pixel_size(20)
+        .build();
+
+    let label = Label::builder()
+        .label("N/A")
+        .css_name("network-label")
+        .build();
+
+    container.append(&image);
+    container.append(&label);
+
+    let interface = context.config.string("interface");
+    let links = {
+        let container = container.clone();
+        Worker::new(
+            || network::get_links(true),
+            move |links| show(links,&container,&image,&label,interface.as_deref()))
+    };
+
+    boxed(Network {
+        container,
+        links,
+    })
+}
+
+fn icon(link: Option<&Link>) -> &'static str {
+    match link {
+        None => "assets/status/network-wireless-off.svg",
+        Some(Link { wireless: Some(wireless), .. }) if wireless.ssid.is_some() =>
+            SIGNAL_ICONS[wireless.strength() as usize],
+        Some(Link { wireless: Some(_), .. }) => "assets/status/network-wireless-disconnected.svg",
+        Some(_) => "assets/apps/network-defaultroute.svg",
+    }
+}
+
+fn tooltip(link: &Link) -> String {
+    let mut lines = vec![link.name.clone()];
+
+    if let Some(wireless) = &link.wireless {
+        if let Some(ssid) = &wireless.ssid {
+            lines.push(format!("SSID: {ssid}"));
+        }
+        if let Some(signal) = wireless.signal {
+            lines.push(format!("Signal: {signal} dBm"));
+        }
+        if let Some(bitrate) = wireless.bitrate {
+            lines.push(format!("Bitrate: {:.1} Mb/s",bitrate as f32 / 10.0));
+        }
+        if let Some(frequency) = wireless.frequency {
+            lines.push(format!("Frequency: {:.3} GHz",frequency as f32 / 1000.0));
+        }
+    }
+
+    for address in &link.addresses {
+        lines.push(address.to_string());
+    }
+
+    lines.join("\n")
+}
+
+fn show(
+    links: Result<Vec<Link>,NetlinkError>,
+    container: &Box,
+    image: &Image,
+    label: &Label,
+    interface: Option<&str>) {
+
+    let links = match links {
+        Ok(links) => links,
+        Err(err) => {
+            image.set_from_file(Some(icon(None)));
+            label.set_label("N/A");
+            set_error(container,Some(&err.into()));
+            return
+        }
+    };
+    set_error(container,None);
+
+    let link = network::primary_link(&links,interface)
+        .filter(|link| link.up);
+
+    image.set_from_file(Some(icon(link)));
+
+    match link {
+        Some(link) => {
+            let name = link.wireless.as_ref()
+                .and_then(|wireless| wireless.ssid.clone())
+                .unwrap_or_else(|| link.name.clone());
+            label.set_label(&name);
+            container.set_tooltip_text(Some(&tooltip(link)));
+        },
+        None => {
+            label.set_label("offline");
+            container.set_tooltip_text(None);
+        }
+    }
+}
+
+impl Module for Network {
+    fn widget(&self) -> Widget {
+        self.container.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(2))
+    }
+
+    fn update(&mut self) {
+        self.links.run();
+    }
+}
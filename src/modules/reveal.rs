@@ -0,0 +1,32 @@
+use gtk::{
+    prelude::*,
+    Button,
+    Widget,
+};
+
+use super::{
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+// button to reveal all statuses
+struct Reveal {
+    button: Button,
+}
+
+pub fn new(_context: &ModuleContext) -> Option<BoxedModule> {
+    let button = Button::builder()
+        .css_name("status-reveal-button")
+        .label("󰁚")
+        .build();
+
+    boxed(Reveal { button })
+}
+
+impl Module for Reveal {
+    fn widget(&self) -> Widget {
+        self.button.clone().upcast()
+    }
+}
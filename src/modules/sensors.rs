@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use gtk::{
+    prelude::*,
+    Label,
+    Widget,
+};
+
+use crate::{
+    sensors::{
+        self,
+        Level,
+        Sensors,
+        Temperature,
+    },
+    status::StatusError,
+    system::SystemSource,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+/* one temperature, coloured by the sensor's own limits, with the fans in the tooltip
+ * sensor: `chip label` as in `k10temp Tctl`, the cpu package if left out
+ */
+struct SensorsModule {
+    label: Label,
+    sensor: Option<String>,
+    source: SystemSource,
+}
+
+// nothing to show on machines without any temperature sensor
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let source = SystemSource::default();
+    let found = sensors::sensors(&source);
+    if found.temperatures.is_empty() {
+        return None
+    }
+
+    let sensor = context.config.string("sensor");
+    if let Some(name) = sensor.as_deref().filter(|name| found.find(name).is_none()) {
+        let known: Vec<String> = found.temperatures.iter().map(Temperature::name).collect();
+        eprintln!("epic-bar: no sensor `{name}`, found: {}",known.join(", "));
+    }
+
+    let label = Label::builder()
+        .label("N/A")
+        .css_name("sensors-label")
+        .build();
+
+    boxed(SensorsModule { label, sensor, source })
+}
+
+fn tooltip(temp: &Temperature, sensors: &Sensors) -> String {
+    let mut text = temp.name();
+    if let Some(max) = temp.max {
+        text.push_str(&format!("\nhigh {max:.0}°C"));
+    }
+    if let Some(crit) = temp.crit {
+        text.push_str(&format!("\ncritical {crit:.0}°C"));
+    }
+
+    for fan in &sensors.fans {
+        text.push_str(&format!("\n{} {}: {} RPM",fan.chip,fan.label,fan.rpm));
+    }
+    text
+}
+
+impl Module for SensorsModule {
+    fn widget(&self) -> Widget {
+        self.label.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(2))
+    }
+
+    fn update(&mut self) {
+        let sensors = sensors::sensors(&self.source);
+        let temp = match &self.sensor {
+            Some(name) => sensors.find(name),
+            None => sensors.preferred(),
+        };
+
+        self.label.remove_css_class("warning");
+        self.label.remove_css_class("critical");
+
+        let Some(temp) = temp else {
+            self.label.set_label("N/A");
+            let name = self.sensor.clone().unwrap_or_else(|| "a temperature sensor".to_string());
+            set_error(&self.label,Some(&StatusError::Missing(name)));
+            return
+        };
+        set_error(&self.label,None);
+
+        self.label.set_label(&format!("󰔏 {:.0}°C",temp.celsius));
+        self.label.set_tooltip_text(Some(&tooltip(temp,&sensors)));
+        match temp.level() {
+            Level::Warning => self.label.add_css_class("warning"),
+            Level::Critical => self.label.add_css_class("critical"),
+            Level::Normal => {}
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    netlink::NetlinkError,
+    network::{
+        self,
+        Link,
+        NetDev,
+        Throughput,
+    },
+    status::StatusError,
+    system::SystemSource,
+    units,
+};
+
+use super::{
+    set_error,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+type Reading = (Result<Vec<Link>,NetlinkError>,Result<NetDev,StatusError>);
+
+/* rx/tx rates of the default route interface with a sparkline of received traffic
+ * interface: pin to this interface instead
+ */
+struct ThroughputModule {
+    view: Rc<ThroughputView>,
+    // the route dump waits up to a second for the kernel, it is not done on the main loop
+    reader: Worker<Reading>,
+}
+
+struct ThroughputView {
+    container: Box,
+    label: Label,
+    sparkline: Label,
+    interface: Option<String>,
+    // the interface being sampled, replaced when the default route moves
+    throughput: RefCell<Option<Throughput>>,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(false)
+        .build();
+
+    let label = Label::builder()
+        .label("N/A")
+        .css_name("throughput-label")
+        .build();
+
+    let sparkline = Label::builder()
+        .css_name("throughput-sparkline")
+        .build();
+
+    container.append(&label);
+    container.append(&sparkline);
+
+    let view = Rc::new(ThroughputView {
+        container,
+        label,
+        sparkline,
+        interface: context.config.string("interface"),
+        throughput: RefCell::new(None),
+    });
+
+    let source = SystemSource::default();
+    let reader = {
+        let view = view.clone();
+        Worker::new(
+            move || (network::get_links(false),NetDev::read(&source)),
+            move |reading| view.show(reading))
+    };
+
+    boxed(ThroughputModule { view, reader })
+}
+
+fn tooltip(link: &Link, throughput: &Throughput) -> String {
+    let mut lines = vec![
+        format!("{} ({})",link.name,if link.up { "up" } else { "down" }),
+    ];
+    if link.default_route {
+        lines.push("default route".to_string());
+    }
+    lines.push(format!("Received: {}",units::format_bytes(throughput.rx_total as f64)));
+    lines.push(format!("Sent: {}",units::format_bytes(throughput.tx_total as f64)));
+
+    lines.join("\n")
+}
+
+impl ThroughputView {
+    fn show(&self, (links,net_dev): Reading) {
+        let links = match links {
+            Ok(links) => links,
+            Err(err) => {
+                self.label.set_label("N/A");
+                self.sparkline.set_label("");
+                set_error(&self.container,Some(&err.into()));
+                return
+            }
+        };
+
+        let mut throughput = self.throughput.borrow_mut();
+        let Some(link) = network::route_link(&links,self.interface.as_deref()) else {
+            self.label.set_label("offline");
+            self.sparkline.set_label("");
+            set_error(&self.container,None);
+            self.container.set_tooltip_text(None);
+            *throughput = None;
+            return
+        };
+
+        if throughput.as_ref().is_none_or(|throughput| throughput.interface() != link.name) {
+            *throughput = Some(Throughput::new(&link.name));
+        }
+        let Some(throughput) = throughput.as_mut() else {
+            return
+        };
+
+        match net_dev {
+            Ok(net_dev) => throughput.add(&net_dev),
+            Err(err) => {
+                self.label.set_label(&format!("{} N/A",link.name));
+                self.sparkline.set_label("");
+                set_error(&self.container,Some(&err));
+                return
+            }
+        }
+        set_error(&self.container,None);
+
+        if link.up {
+            self.label.set_label(&format!("{} ↓ {} ↑ {}",
+                link.name,
+                units::format_rate(throughput.rx_rate),
+                units::format_rate(throughput.tx_rate)));
+        } else {
+            self.label.set_label(&format!("{} down",link.name));
+        }
+        self.sparkline.set_label(&network::sparkline(throughput.history.iter().map(|(rx,_)| *rx)));
+        self.container.set_tooltip_text(Some(&tooltip(link,throughput)));
+    }
+}
+
+impl Module for ThroughputModule {
+    fn widget(&self) -> Widget {
+        self.view.container.clone().upcast()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    fn update(&mut self) {
+        self.reader.run();
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Image,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::pulse::{
+    self,
+    AudioState,
+    Device,
+    DeviceKind,
+    SubscriptionId,
+};
+
+use super::{
+    Module,
+    ModuleContext,
+    BoxedModule,
+    boxed,
+};
+
+const DEFAULT_STEP: u32 = 5;
+const DEFAULT_MAX: u32 = 100;
+
+/* default sink volume and mute state, kept current by server events
+ * scroll changes the volume, a click toggles mute
+ * step: percent per scroll step
+ * max: highest percent scrolling goes to, above 100 amplifies
+ */
+struct Volume {
+    container: Box,
+    // the sink the last event reported, changes are made against it
+    sink: Rc<RefCell<Option<Device>>>,
+    step: u32,
+    max: u32,
+    subscription: SubscriptionId,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(false)
+        .build();
+
+    let image = Image::builder()
+        .file("assets/status/audio-off.svg")
+        .css_name("icon-image")
+        .pixel_size(20)
+        .build();
+
+    let label = Label::builder()
+        .label("N/A")
+        .css_name("volume-label")
+        .build();
+
+    container.append(&image);
+    container.append(&label);
+
+    let sink = Rc::new(RefCell::new(None));
+
+    let subscription = {
+        let (container,sink) = (container.clone(),sink.clone());
+        pulse::subscribe(move |state| show(state,&container,&image,&label,&sink))
+    };
+
+    let option = |key: &str, default: u32| context.config.int(key)
+        .and_then(|value| u32::try_from(value).ok())
+        .unwrap_or(default);
+
+    boxed(Volume {
+        container,
+        sink,
+        step: option("step",DEFAULT_STEP).max(1),
+        max: option("max",DEFAULT_MAX),
+        subscription,
+    })
+}
+
+fn icon(sink: &Device) -> &'static str {
+    match sink.percent() {
+        _ if sink.muted => "assets/status/audio-volume-muted.svg",
+        0..=33 => "assets/status/audio-volume-low.svg",
+        34..=66 => "assets/status/audio-volume-medium.svg",
+        67..=100 => "assets/status/audio-volume-high.svg",
+        _ => "assets/status/audio-volume-high-warning.svg",
+    }
+}
+
+fn show(
+    state: &Result<AudioState,String>,
+    container: &Box,
+    image: &Image,
+    label: &Label,
+    sink: &RefCell<Option<Device>>) {
+
+    let device = match state {
+        Ok(AudioState { sink: Some(device), .. }) => device,
+        Ok(_) => {
+            image.set_from_file(Some("assets/status/audio-off.svg"));
+            label.set_label("N/A");
+            container.set_tooltip_text(Some("no output device"));
+            sink.replace(None);
+            return
+        },
+        Err(err) => {
+            image.set_from_file(Some("assets/status/audio-off.svg"));
+            label.set_label("N/A");
+            container.set_tooltip_text(Some(err));
+            sink.replace(None);
+            return
+        }
+    };
+
+    image.set_from_file(Some(icon(device)));
+    if device.muted {
+        label.set_label("muted");
+    } else {
+        label.set_label(&format!("{}%",device.percent()));
+    }
+    container.set_tooltip_text(Some(&device.description));
+    sink.replace(Some(device.clone()));
+}
+
+impl Module for Volume {
+    fn widget(&self) -> Widget {
+        self.container.clone().upcast()
+    }
+
+    fn click(&mut self, button: u32) {
+        if button != 1 {
+            return
+        }
+        let Some(sink) = self.sink.borrow().clone() else {
+            return
+        };
+
+        pulse::change(move |connection| connection.set_mute(DeviceKind::Sink,sink.index,!sink.muted));
+    }
+
+    fn scroll(&mut self, dy: f64) -> bool {
+        let Some(sink) = self.sink.borrow().clone() else {
+            return false
+        };
+
+        let current = sink.percent();
+        let percent = if dy < 0.0 {
+            (current + self.step).min(self.max.max(current))
+        } else {
+            current.saturating_sub(self.step)
+        };
+        if percent == current {
+            return true
+        }
+
+        let volume = sink.volume_at(percent);
+        pulse::change(move |connection| connection.set_volume(DeviceKind::Sink,sink.index,&volume));
+        true
+    }
+
+    // the subscriber holds the widgets, which hold this module, so Drop is never reached
+    fn cleanup(&mut self) {
+        pulse::unsubscribe(self.subscription);
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Button,
+    Image,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    hyprland::{
+        Client,
+        HyprEvent,
+        HyprlandClient,
+        HyprlandError,
+    },
+    workspaces::Workspaces,
+};
+
+use super::{
+    dispatch,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+type WindowState = Result<(Workspaces,Option<Client>),HyprlandError>;
+
+// open windows of every workspace on this output, most recently used first
+struct Windows {
+    list: Rc<WindowList>,
+    resync: Worker<WindowState>,
+}
+
+/* what the bar shows, kept so focus and title changes are applied without asking hyprland
+ * buttons: by address with the 0x prefix, as `j/clients` has it
+ */
+struct WindowList {
+    container: Box,
+    connector: Option<String>,
+    buttons: RefCell<HashMap<String,(Client,Button)>>,
+    active: RefCell<Option<String>>,
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .hexpand(true)
+        .vexpand(true)
+        .homogeneous(false)
+        .css_name("workspace-windows-container")
+        .build();
+
+    let list = Rc::new(WindowList {
+        container,
+        connector: context.connector.map(str::to_string),
+        buttons: RefCell::new(HashMap::new()),
+        active: RefCell::new(None),
+    });
+
+    let resync = {
+        let list = list.clone();
+        Worker::new(
+            || HyprlandClient::from_env().and_then(|client| {
+                Ok((client.get_workspaces()?,client.active_window()?))
+            }),
+            move |state| list.populate(state))
+    };
+    // bars made after startup should not wait for the next event
+    resync.run();
+
+    boxed(Windows { list, resync })
+}
+
+// event addresses come without the 0x prefix
+fn client_address(address: &str) -> String {
+    format!("0x{address}")
+}
+
+fn window_button(window: &Client, active: bool) -> Button {
+    // creating box to be child of button
+    // check if active or not
+    let css_name = if active {
+        "active-window-box"
+    } else  {
+        "window-box"
+    };
+
+    let window_button = Button::builder()
+        .css_name(css_name)
+        .tooltip_text(window.display_title())
+        .build();
+
+    // box has icon then label
+    let icon_label_box = Box::builder()
+        .build();
+
+    let icon = Image::builder()
+        .file(format!("assets/apps/{}.svg",window.class))
+        .css_name("icon-image")
+        .pixel_size(20)
+        .build();
+
+    icon_label_box.append(&icon);
+
+    window_button.set_child(Some(&icon_label_box));
+    let address = window.address.clone();
+    // switch to clicked workspace
+    window_button.connect_clicked(move |_| {
+        let address = address.clone();
+        dispatch(move |client| client.switch_window(&address));
+    });
+
+    let window_label = Label::builder()
+        .label(window.display_name())
+        .css_name("window-label")
+        .build();
+
+    icon_label_box.append(&window_label);
+
+    window_button
+}
+
+impl WindowList {
+    fn populate(&self, state: WindowState) {
+        let container = &self.container;
+        let mut tag_opt = container.first_child();
+
+        while let Some(tag) = tag_opt {
+            // empty container with everything
+            container.remove(&tag);
+            tag_opt = container.first_child()
+        }
+        let mut buttons = self.buttons.borrow_mut();
+        buttons.clear();
+
+        let (workspaces,active_window) = match state {
+            Ok(state) => state,
+            Err(err) => {
+                let disconnected_label = Label::builder()
+                    .label("󰖪 compositor disconnected")
+                    .css_name("disconnected-label")
+                    .tooltip_text(err.to_string())
+                    .build();
+                container.append(&disconnected_label);
+                self.active.replace(None);
+                return
+            }
+        };
+        let active_address = active_window.map(|window| window.address);
+
+        let sorted: &mut Vec<_> = &mut workspaces
+            .into_iter()
+            .filter(|(_,workspace)| workspace.on_monitor(self.connector.as_deref()))
+            .collect();
+
+        // sort by recently used
+        sorted.sort_by(|w1,w2| w1.1.order.cmp(&w2.1.order));
+
+
+        // start filling with occupied workspaces
+        for (tag,workspace) in sorted.drain(..) {
+            // Box will contain
+            //  - label of tag
+            //  - button for each window
+            //      - child is box has icon
+            //          - Initial_title of application
+
+            let workspace_windows_css = if workspace.active && !workspace.windows.is_empty() {
+                "workspace-window-box-active"
+            } else if workspace.windows.is_empty() {
+                "workspace-window-box-empty"
+            } else {
+                "workspace-window-box"
+            };
+
+            let workspace_box = Box::builder()
+                .name(format!("{}",tag))
+                .css_name(workspace_windows_css)
+                .vexpand(true)
+                .build();
+
+            let tag_label = Label::builder()
+                .label(format!("{}",tag))
+                .css_name("tag-label")
+                .build();
+
+            workspace_box.append(&tag_label);
+
+            if workspace.windows.is_empty() {
+
+                let window_button = Button::builder()
+                    .css_name("window-box-empty")
+                    .label("󰟢")
+                    .build();
+
+                workspace_box.append(&window_button);
+            }
+
+
+            for window in workspace.windows {
+                let window_button = window_button(&window,active_address.as_ref() == Some(&window.address));
+                workspace_box.append(&window_button);
+                buttons.insert(window.address.clone(),(window,window_button));
+            }
+            container.append(&workspace_box);
+        }
+        self.active.replace(active_address);
+    }
+
+    // the button's css name says whether it is focused, so it is made again in place
+    fn restyle(&self, address: &str, active: bool) {
+        let mut buttons = self.buttons.borrow_mut();
+        let Some((window,button)) = buttons.get_mut(address) else {
+            return
+        };
+        let Some(parent) = button.parent().and_downcast::<Box>() else {
+            return
+        };
+
+        let replacement = window_button(window,active);
+        parent.insert_child_after(&replacement,button.prev_sibling().as_ref());
+        parent.remove(button);
+        *button = replacement;
+    }
+
+    // windows on other outputs have no button, they only take the focus away
+    fn focus(&self, address: String) {
+        let last = self.active.replace(Some(address.clone()));
+        if last.as_ref() == Some(&address) {
+            return
+        }
+        if let Some(last) = last {
+            self.restyle(&last,false);
+        }
+        self.restyle(&address,true);
+    }
+
+    fn retitle(&self, address: &str, title: &str) {
+        if let Some((window,button)) = self.buttons.borrow_mut().get_mut(address) {
+            window.title = title.to_string();
+            button.set_tooltip_text(Some(&window.display_title()));
+        }
+    }
+}
+
+impl Module for Windows {
+    fn widget(&self) -> Widget {
+        self.list.container.clone().upcast()
+    }
+
+    fn listens(&self) -> bool {
+        true
+    }
+
+    /* focus and title changes are applied to what is shown,
+     * anything that moves windows between workspaces is read again off the main loop
+     */
+    fn event(&mut self, event: &HyprEvent) {
+        match event {
+            HyprEvent::ActiveWindowAddress{address} => self.list.focus(client_address(address)),
+            HyprEvent::WindowTitle{address,title} => self.list.retitle(&client_address(address),title),
+            HyprEvent::Connected
+            | HyprEvent::Disconnected{..}
+            | HyprEvent::Workspace{..}
+            | HyprEvent::FocusedMonitor{..}
+            | HyprEvent::OpenWindow{..}
+            | HyprEvent::CloseWindow{..}
+            | HyprEvent::MoveWindow{..}
+            | HyprEvent::DestroyWorkspace{..}
+            | HyprEvent::MoveWorkspace{..} => self.resync.run(),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+};
+
+use gtk::{
+    prelude::*,
+    Box,
+    Button,
+    Label,
+    Orientation,
+    Widget,
+};
+
+use crate::{
+    hyprland::{
+        HyprEvent,
+        HyprlandClient,
+        HyprlandError,
+    },
+    workspaces::{
+        self,
+        Workspaces,
+    },
+};
+
+use super::{
+    dispatch,
+    Module,
+    ModuleContext,
+    BoxedModule,
+    Worker,
+    boxed,
+};
+
+/* one button per workspace, the label takes their place while hyprland can not be reached
+ * on a bar tied to an output the buttons are that output's workspaces, whatever their ids
+ * count: fixed tags 1 to count on a bar whose output is not known
+ */
+struct WorkspacesModule {
+    view: Rc<WorkspaceButtons>,
+    resync: Worker<Result<Workspaces,HyprlandError>>,
+}
+
+// shown: the last workspaces read, for scrolling through this output's
+struct WorkspaceButtons {
+    container: Box,
+    buttons: Box,
+    disconnected_label: Label,
+    connector: Option<String>,
+    shown: RefCell<Option<Workspaces>>,
+}
+
+fn workspace_button(n: i32) -> Button {
+    let workspace_button = Button::builder()
+        .label(format!("{}",n))
+        .name(format!("{}",n))
+        .visible(true)
+        .focus_on_click(true)
+        .build();
+
+    workspace_button.connect_clicked( move |_| {
+        dispatch(move |client| client.switch_workspace(n));
+    });
+    workspace_button
+}
+
+pub fn new(context: &ModuleContext) -> Option<BoxedModule> {
+    let count = context.config.int("count")
+        .and_then(|count| i32::try_from(count).ok())
+        .unwrap_or(workspaces::WORKSPACE_COUNT);
+
+    let container = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .build();
+
+    let buttons = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(0)
+        .css_name("workspaces-container")
+        .build();
+
+    // without an output the tags are fixed, otherwise populate builds them
+    if context.connector.is_none() {
+        for n in 1..count+1 {
+            buttons.append(&workspace_button(n));
+        }
+    }
+
+    let disconnected_label = Label::builder()
+        .label("󰖪 compositor disconnected")
+        .css_name("disconnected-label")
+        .visible(false)
+        .build();
+
+    container.append(&buttons);
+    container.append(&disconnected_label);
+
+    let view = Rc::new(WorkspaceButtons {
+        container,
+        buttons,
+        disconnected_label,
+        connector: context.connector.map(str::to_string),
+        shown: RefCell::new(None),
+    });
+
+    let resync = {
+        let view = view.clone();
+        Worker::new(get_workspaces,move |workspaces| view.refresh(workspaces))
+    };
+    resync.run();
+
+    boxed(WorkspacesModule { view, resync })
+}
+
+fn get_workspaces() -> Result<Workspaces, HyprlandError> {
+    HyprlandClient::from_env()?.get_workspaces()
+}
+
+impl WorkspaceButtons {
+    // degrade to a label instead of exiting when hyprland is unavailable
+    fn refresh(&self, workspaces: Result<Workspaces,HyprlandError>) {
+        match workspaces {
+            Ok(workspaces) => {
+                self.disconnected_label.set_visible(false);
+                self.buttons.set_visible(true);
+                self.populate(&workspaces);
+                self.shown.replace(Some(workspaces));
+            },
+            Err(err) => {
+                self.shown.replace(None);
+                self.disconnected_label.set_tooltip_text(Some(&err.to_string()));
+                self.disconnected_label.set_visible(true);
+                self.buttons.set_visible(false);
+            }
+        }
+    }
+
+    // the buttons of a bar tied to an output follow the workspaces on it
+    fn rebuild(&self, workspaces: &Workspaces) {
+        let tags = workspaces::tags_on(workspaces,self.connector.as_deref());
+
+        let mut current: Vec<i32> = Vec::new();
+        let mut ws_opt = self.buttons.first_child();
+        while let Some(workspace) = ws_opt {
+            current.extend(workspace.widget_name().as_str().parse::<i32>().ok());
+            ws_opt = workspace.next_sibling();
+        }
+        if current == tags {
+            return
+        }
+
+        while let Some(workspace) = self.buttons.first_child() {
+            self.buttons.remove(&workspace);
+        }
+        for tag in tags {
+            self.buttons.append(&workspace_button(tag));
+        }
+    }
+
+    fn populate(&self, workspaces: &Workspaces) {
+        if self.connector.is_some() {
+            self.rebuild(workspaces);
+        }
+        let mut ws_opt = self.buttons.first_child();
+
+        while let Some(ref workspace) = ws_opt {
+            let tag :i32 = workspace.widget_name().as_str().parse().unwrap();
+            let workspace_info_opt = workspaces.get(&tag)
+                .filter(|workspace_info| workspace_info.on_monitor(self.connector.as_deref()));
+            // make sure each workspace button has only one active class at a time
+            if let Some(workspace_info) = workspace_info_opt {
+                workspace.set_visible(true);
+                if workspace_info.active && workspace_info.windows.is_empty() {
+                    workspace.remove_css_class("occupied");
+                    workspace.remove_css_class("active");
+                    workspace.add_css_class("empty-active");
+                } else if workspace_info.active && !workspace_info.windows.is_empty() {
+                    workspace.remove_css_class("occupied");
+                    workspace.remove_css_class("empty-active");
+                    workspace.add_css_class("active");
+                } else {
+                    workspace.remove_css_class("active");
+                    workspace.remove_css_class("empty-active");
+                    workspace.add_css_class("occupied");
+                }
+            }
+            else {
+                workspace.set_visible(false);
+            }
+            ws_opt = workspace.next_sibling();
+        }
+    }
+}
+
+impl Module for WorkspacesModule {
+    fn widget(&self) -> Widget {
+        self.view.container.clone().upcast()
+    }
+
+    fn listens(&self) -> bool {
+        true
+    }
+
+    // only events that change which workspaces are occupied or focused, read off the main loop
+    fn event(&mut self, event: &HyprEvent) {
+        match event {
+            HyprEvent::Connected
+            | HyprEvent::Disconnected{..}
+            | HyprEvent::Workspace{..}
+            | HyprEvent::FocusedMonitor{..}
+            | HyprEvent::OpenWindow{..}
+            | HyprEvent::CloseWindow{..}
+            | HyprEvent::MoveWindow{..}
+            | HyprEvent::CreateWorkspace{..}
+            | HyprEvent::DestroyWorkspace{..}
+            | HyprEvent::MoveWorkspace{..} => self.resync.run(),
+            _ => {}
+        }
+    }
+
+    /* cycle through the workspaces of this output, not the focused one
+     * a bar without an output leaves it to hyprland's focused monitor
+     */
+    fn scroll(&mut self, dy: f64) -> bool {
+        let forward = dy >= 0.0;
+        let Some(connector) = self.view.connector.as_deref() else {
+            let args = if forward { "workspace m+1" } else { "workspace m-1" };
+            dispatch(move |client| client.dispatch(args));
+            return true
+        };
+
+        let mut shown = self.view.shown.borrow_mut();
+        let Some(workspaces) = shown.as_mut() else {
+            return true
+        };
+        let Some(tag) = workspaces::step(workspaces,connector,forward) else {
+            return true
+        };
+        // until hyprland's event arrives, so a fast scroll keeps going
+        for workspace in workspaces.values_mut().filter(|workspace| workspace.on_monitor(Some(connector))) {
+            workspace.active = workspace.tag == tag;
+        }
+        dispatch(move |client| client.switch_workspace(tag));
+        true
+    }
+}
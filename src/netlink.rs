@@ -0,0 +1,280 @@
+use std::{
+    fmt,
+    io,
+    mem,
+    os::fd::{
+        AsRawFd,
+        FromRawFd,
+        OwnedFd,
+    },
+};
+
+/* just enough netlink to ask the kernel about links and radios
+ *
+ * a reply is a run of messages, each a 16 byte header followed by a payload
+ * that is usually a fixed struct then attributes:
+ *  [len u16][type u16][data, padded to 4 bytes]
+ */
+pub const ROUTE: i32 = libc::NETLINK_ROUTE;
+pub const GENERIC: i32 = libc::NETLINK_GENERIC;
+
+pub const F_REQUEST: u16 = libc::NLM_F_REQUEST as u16;
+pub const F_DUMP: u16 = libc::NLM_F_DUMP as u16;
+const F_MULTI: u16 = libc::NLM_F_MULTI as u16;
+
+const NLMSG_ERROR: u16 = libc::NLMSG_ERROR as u16;
+const NLMSG_DONE: u16 = libc::NLMSG_DONE as u16;
+
+const HEADER_LEN: usize = 16;
+const RECV_BUFFER: usize = 64 * 1024;
+
+// nested and byte order flags share the type field
+const ATTR_TYPE_MASK: u16 = 0x3fff;
+
+#[derive(Debug)]
+pub enum NetlinkError {
+    Io(io::Error),
+    Malformed(&'static str),
+    // errno the kernel answered with, as a positive number
+    Kernel(i32),
+}
+
+impl fmt::Display for NetlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetlinkError::Io(err) => write!(f,"netlink socket: {err}"),
+            NetlinkError::Malformed(what) => write!(f,"malformed netlink reply: {what}"),
+            NetlinkError::Kernel(errno) => write!(f,"netlink request failed: {}",
+                io::Error::from_raw_os_error(*errno)),
+        }
+    }
+}
+
+impl From<io::Error> for NetlinkError {
+    fn from(err: io::Error) -> Self {
+        NetlinkError::Io(err)
+    }
+}
+
+pub struct Message<'a> {
+    pub kind: u16,
+    pub flags: u16,
+    pub seq: u32,
+    pub payload: &'a [u8],
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub fn u16_at(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_ne_bytes(buf.get(at..at+2)?.try_into().ok()?))
+}
+
+pub fn u32_at(buf: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(buf.get(at..at+4)?.try_into().ok()?))
+}
+
+// every message in buf, done and error messages included
+fn headers(buf: &[u8]) -> Result<Vec<Message<'_>>, NetlinkError> {
+    let mut messages = Vec::new();
+    let mut rest = buf;
+
+    while rest.len() >= HEADER_LEN {
+        let len = u32_at(rest,0).unwrap_or(0) as usize;
+        if len < HEADER_LEN || len > rest.len() {
+            return Err(NetlinkError::Malformed("message length"))
+        }
+
+        messages.push(Message {
+            kind: u16_at(rest,4).unwrap_or(0),
+            flags: u16_at(rest,6).unwrap_or(0),
+            seq: u32_at(rest,8).unwrap_or(0),
+            payload: &rest[HEADER_LEN..len],
+        });
+        rest = &rest[align(len).min(rest.len())..];
+    }
+
+    Ok(messages)
+}
+
+// messages carrying data, an error reply fails the whole request
+pub fn messages(buf: &[u8]) -> Result<Vec<Message<'_>>, NetlinkError> {
+    let mut messages = Vec::new();
+
+    for message in headers(buf)? {
+        match message.kind {
+            NLMSG_DONE => break,
+            NLMSG_ERROR => {
+                let errno = u32_at(message.payload,0)
+                    .ok_or(NetlinkError::Malformed("error message"))? as i32;
+                // zero is an acknowledgement
+                if errno != 0 {
+                    return Err(NetlinkError::Kernel(-errno))
+                }
+            },
+            _ => messages.push(message),
+        }
+    }
+
+    Ok(messages)
+}
+
+#[derive(Clone, Copy)]
+pub struct Attribute<'a> {
+    pub kind: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> Attribute<'a> {
+    pub fn u8(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    pub fn i8(&self) -> Option<i8> {
+        self.u8().map(|byte| byte as i8)
+    }
+
+    pub fn u16(&self) -> Option<u16> {
+        u16_at(self.data,0)
+    }
+
+    pub fn u32(&self) -> Option<u32> {
+        u32_at(self.data,0)
+    }
+
+    // strings from the kernel are nul terminated, ssids are not and may not be utf-8
+    pub fn string(&self) -> String {
+        let end = self.data.iter().position(|byte| *byte == 0).unwrap_or(self.data.len());
+        String::from_utf8_lossy(&self.data[..end]).into_owned()
+    }
+
+    pub fn nested(&self) -> Vec<Attribute<'a>> {
+        attributes(self.data)
+    }
+}
+
+// a truncated attribute ends the list instead of failing it
+pub fn attributes(buf: &[u8]) -> Vec<Attribute<'_>> {
+    let mut attributes = Vec::new();
+    let mut rest = buf;
+
+    while rest.len() >= 4 {
+        let len = u16_at(rest,0).unwrap_or(0) as usize;
+        if len < 4 || len > rest.len() {
+            break
+        }
+
+        attributes.push(Attribute {
+            kind: u16_at(rest,2).unwrap_or(0) & ATTR_TYPE_MASK,
+            data: &rest[4..len],
+        });
+        rest = &rest[align(len).min(rest.len())..];
+    }
+
+    attributes
+}
+
+pub fn find<'a>(attributes: &[Attribute<'a>], kind: u16) -> Option<Attribute<'a>> {
+    attributes.iter().find(|attribute| attribute.kind == kind).copied()
+}
+
+// append an attribute to a request being built
+pub fn push_attribute(buf: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    let len = 4 + data.len();
+    buf.extend_from_slice(&(len as u16).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(buf.len() + align(len) - len, 0);
+}
+
+pub struct Socket {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl Socket {
+    pub fn open(protocol: i32) -> Result<Self, NetlinkError> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, protocol)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into())
+        }
+        // closed on drop from here on
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error().into())
+        }
+
+        // the kernel answers right away, never block the bar on it
+        let timeout = libc::timeval { tv_sec: 1, tv_usec: 0 };
+        unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t);
+        }
+
+        Ok(Socket { fd, seq: 0 })
+    }
+
+    /* send one request and return every reply message as received
+     * a dump is read until its done message, anything else until the first reply
+     */
+    pub fn request(&mut self, kind: u16, flags: u16, payload: &[u8]) -> Result<Vec<u8>, NetlinkError> {
+        self.seq = self.seq.wrapping_add(1);
+
+        let mut request = Vec::with_capacity(HEADER_LEN + payload.len());
+        request.extend_from_slice(&((HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+        request.extend_from_slice(&kind.to_ne_bytes());
+        request.extend_from_slice(&(flags | F_REQUEST).to_ne_bytes());
+        request.extend_from_slice(&self.seq.to_ne_bytes());
+        request.extend_from_slice(&0u32.to_ne_bytes());
+        request.extend_from_slice(payload);
+
+        let sent = unsafe {
+            libc::send(self.fd.as_raw_fd(), request.as_ptr() as *const libc::c_void, request.len(), 0)
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error().into())
+        }
+
+        let mut reply = Vec::new();
+        let mut buf = vec![0u8; RECV_BUFFER];
+
+        loop {
+            let received = unsafe {
+                libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+            };
+            if received < 0 {
+                return Err(io::Error::last_os_error().into())
+            }
+            let chunk = &buf[..received as usize];
+
+            let mut done = false;
+            for message in headers(chunk)?.iter().filter(|message| message.seq == self.seq) {
+                done |= message.kind == NLMSG_DONE
+                    || message.kind == NLMSG_ERROR
+                    || message.flags & F_MULTI == 0;
+            }
+
+            reply.extend_from_slice(chunk);
+            if done {
+                return Ok(reply)
+            }
+        }
+    }
+}
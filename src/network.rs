@@ -0,0 +1,593 @@
+use std::{
+    collections::VecDeque,
+    net::{
+        IpAddr,
+        Ipv4Addr,
+        Ipv6Addr,
+    },
+    time::Instant,
+};
+
+use crate::{
+    netlink::{
+        self,
+        Attribute,
+        NetlinkError,
+        Socket,
+    },
+    status::StatusError,
+    system::SystemSource,
+};
+
+// rtnetlink, linux/rtnetlink.h and linux/if_link.h
+const RTM_NEWLINK: u16 = 16;
+const RTM_GETLINK: u16 = 18;
+const RTM_NEWADDR: u16 = 20;
+const RTM_GETADDR: u16 = 22;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_GETROUTE: u16 = 26;
+const IFLA_IFNAME: u16 = 3;
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const RTA_OIF: u16 = 4;
+const RTA_PRIORITY: u16 = 6;
+const RTA_TABLE: u16 = 15;
+const RT_TABLE_MAIN: u32 = 254;
+const RTN_UNICAST: u8 = 1;
+const IFINFOMSG_LEN: usize = 16;
+const IFADDRMSG_LEN: usize = 8;
+const RTMSG_LEN: usize = 12;
+
+const NET_DEV: &str = "/proc/net/dev";
+
+// generic netlink controller, linux/genetlink.h
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const GENLMSGHDR_LEN: usize = 4;
+
+// linux/nl80211.h
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+const NL80211_STA_INFO_TX_BITRATE: u16 = 8;
+const NL80211_RATE_INFO_BITRATE: u16 = 1;
+const NL80211_RATE_INFO_BITRATE32: u16 = 5;
+
+/* Link: a network interface as rtnetlink reports it
+ * up: administratively up and with a carrier
+ * default_route: carries the default route with the lowest metric
+ * wireless: only for interfaces nl80211 knows about
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub index: u32,
+    pub name: String,
+    pub up: bool,
+    pub loopback: bool,
+    pub default_route: bool,
+    pub addresses: Vec<IpAddr>,
+    pub wireless: Option<Wireless>,
+}
+
+/* ssid: None while not associated
+ * frequency: MHz
+ * signal: dBm of the access point
+ * bitrate: transmit rate in units of 100 kbit/s
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Wireless {
+    pub ssid: Option<String>,
+    pub frequency: Option<u32>,
+    pub signal: Option<i8>,
+    pub bitrate: Option<u32>,
+}
+
+impl Wireless {
+    // 0 to 4 bars, same cut offs most network managers use
+    pub fn strength(&self) -> u8 {
+        match self.signal {
+            Some(dbm) if dbm >= -55 => 4,
+            Some(dbm) if dbm >= -67 => 3,
+            Some(dbm) if dbm >= -75 => 2,
+            Some(dbm) if dbm >= -85 => 1,
+            _ => 0,
+        }
+    }
+}
+
+pub fn parse_links(buf: &[u8]) -> Result<Vec<Link>, NetlinkError> {
+    let mut links = Vec::new();
+
+    for message in netlink::messages(buf)?.iter().filter(|message| message.kind == RTM_NEWLINK) {
+        // struct ifinfomsg: family, pad, type, index, flags, change
+        let (Some(index),Some(flags)) = (netlink::u32_at(message.payload,4),netlink::u32_at(message.payload,8)) else {
+            return Err(NetlinkError::Malformed("ifinfomsg"))
+        };
+        let attributes = netlink::attributes(&message.payload[IFINFOMSG_LEN.min(message.payload.len())..]);
+
+        links.push(Link {
+            index,
+            name: netlink::find(&attributes,IFLA_IFNAME)
+                .map(|name| name.string())
+                .unwrap_or_default(),
+            up: flags & libc::IFF_UP as u32 != 0 && flags & libc::IFF_RUNNING as u32 != 0,
+            loopback: flags & libc::IFF_LOOPBACK as u32 != 0,
+            default_route: false,
+            addresses: Vec::new(),
+            wireless: None,
+        });
+    }
+
+    Ok(links)
+}
+
+// (interface index, address) for every address of every interface
+pub fn parse_addresses(buf: &[u8]) -> Result<Vec<(u32,IpAddr)>, NetlinkError> {
+    let mut addresses = Vec::new();
+
+    for message in netlink::messages(buf)?.iter().filter(|message| message.kind == RTM_NEWADDR) {
+        // struct ifaddrmsg: family, prefixlen, flags, scope, index
+        let (Some(family),Some(index)) = (message.payload.first(),netlink::u32_at(message.payload,4)) else {
+            return Err(NetlinkError::Malformed("ifaddrmsg"))
+        };
+        let attributes = netlink::attributes(&message.payload[IFADDRMSG_LEN.min(message.payload.len())..]);
+
+        // on point to point links IFA_ADDRESS is the other end
+        let address = netlink::find(&attributes,IFA_LOCAL)
+            .or_else(|| netlink::find(&attributes,IFA_ADDRESS))
+            .and_then(|address| ip_address(*family,&address));
+
+        if let Some(address) = address {
+            addresses.push((index,address));
+        }
+    }
+
+    Ok(addresses)
+}
+
+/* interface index of the default route in the main table
+ * lowest metric wins, IPv4 before IPv6 when they tie
+ */
+pub fn parse_default_route(buf: &[u8]) -> Result<Option<u32>, NetlinkError> {
+    let mut best: Option<(u32,bool,u32)> = None;
+
+    for message in netlink::messages(buf)?.iter().filter(|message| message.kind == RTM_NEWROUTE) {
+        // struct rtmsg: family, dst_len, src_len, tos, table, protocol, scope, type, flags
+        let payload = message.payload;
+        if payload.len() < RTMSG_LEN {
+            return Err(NetlinkError::Malformed("rtmsg"))
+        }
+        let (family,dst_len,table,kind) = (payload[0],payload[1],payload[4],payload[7]);
+        if dst_len != 0 || kind != RTN_UNICAST {
+            continue
+        }
+
+        let attributes = netlink::attributes(&payload[RTMSG_LEN..]);
+        // tables above 255 only show up in RTA_TABLE
+        let table = netlink::find(&attributes,RTA_TABLE)
+            .and_then(|table| table.u32())
+            .unwrap_or(table as u32);
+        let Some(index) = netlink::find(&attributes,RTA_OIF).and_then(|index| index.u32()) else {
+            continue
+        };
+        if table != RT_TABLE_MAIN {
+            continue
+        }
+
+        let metric = netlink::find(&attributes,RTA_PRIORITY)
+            .and_then(|metric| metric.u32())
+            .unwrap_or(0);
+        let route = (metric,family as i32 != libc::AF_INET,index);
+        if best.is_none_or(|best| route < best) {
+            best = Some(route);
+        }
+    }
+
+    Ok(best.map(|(_,_,index)| index))
+}
+
+fn ip_address(family: u8, attribute: &Attribute) -> Option<IpAddr> {
+    match family as i32 {
+        libc::AF_INET => {
+            let octets: [u8;4] = attribute.data.try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        },
+        libc::AF_INET6 => {
+            let octets: [u8;16] = attribute.data.try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        },
+        _ => None
+    }
+}
+
+fn genl_attributes<'a>(payload: &'a [u8]) -> Vec<Attribute<'a>> {
+    netlink::attributes(&payload[GENLMSGHDR_LEN.min(payload.len())..])
+}
+
+pub fn parse_family_id(buf: &[u8]) -> Result<Option<u16>, NetlinkError> {
+    Ok(netlink::messages(buf)?.iter()
+        .filter(|message| message.kind == GENL_ID_CTRL)
+        .find_map(|message| netlink::find(&genl_attributes(message.payload),CTRL_ATTR_FAMILY_ID))
+        .and_then(|id| id.u16()))
+}
+
+// (interface index, ssid and frequency) of every wireless interface that has an index
+pub fn parse_interfaces(buf: &[u8], family: u16) -> Result<Vec<(u32,Wireless)>, NetlinkError> {
+    let mut interfaces = Vec::new();
+
+    for message in netlink::messages(buf)?.iter().filter(|message| message.kind == family) {
+        let attributes = genl_attributes(message.payload);
+        // p2p devices have no netdev behind them
+        let Some(index) = netlink::find(&attributes,NL80211_ATTR_IFINDEX).and_then(|index| index.u32()) else {
+            continue
+        };
+
+        interfaces.push((index,Wireless {
+            ssid: netlink::find(&attributes,NL80211_ATTR_SSID)
+                .map(|ssid| String::from_utf8_lossy(ssid.data).into_owned()),
+            frequency: netlink::find(&attributes,NL80211_ATTR_WIPHY_FREQ)
+                .and_then(|frequency| frequency.u32()),
+            ..Wireless::default()
+        }));
+    }
+
+    Ok(interfaces)
+}
+
+// (signal, bitrate) of the access point a station interface is associated with
+pub fn parse_station(buf: &[u8], family: u16) -> Result<(Option<i8>,Option<u32>), NetlinkError> {
+    let station = netlink::messages(buf)?.iter()
+        .filter(|message| message.kind == family)
+        .find_map(|message| netlink::find(&genl_attributes(message.payload),NL80211_ATTR_STA_INFO)
+            .map(|info| info.nested()));
+
+    let Some(info) = station else {
+        return Ok((None,None))
+    };
+
+    let signal = netlink::find(&info,NL80211_STA_INFO_SIGNAL).and_then(|signal| signal.i8());
+    // the 16 bit rate saturates above 6.5 Gbit/s
+    let bitrate = netlink::find(&info,NL80211_STA_INFO_TX_BITRATE).and_then(|rate| {
+        let rate = rate.nested();
+        netlink::find(&rate,NL80211_RATE_INFO_BITRATE32).and_then(|rate| rate.u32())
+            .or_else(|| netlink::find(&rate,NL80211_RATE_INFO_BITRATE)
+                .and_then(|rate| rate.u16())
+                .map(u32::from))
+    });
+
+    Ok((signal,bitrate))
+}
+
+fn genl_request(cmd: u8) -> Vec<u8> {
+    // struct genlmsghdr: cmd, version, reserved
+    vec![cmd, 1, 0, 0]
+}
+
+fn wireless_info(socket: &mut Socket) -> Result<Vec<(u32,Wireless)>, NetlinkError> {
+    let mut request = genl_request(CTRL_CMD_GETFAMILY);
+    netlink::push_attribute(&mut request,CTRL_ATTR_FAMILY_NAME,b"nl80211\0");
+
+    // no cfg80211 loaded, so no radios
+    let reply = socket.request(GENL_ID_CTRL,0,&request)?;
+    let family = match parse_family_id(&reply) {
+        Ok(family) => family,
+        Err(NetlinkError::Kernel(libc::ENOENT)) => None,
+        Err(err) => return Err(err),
+    };
+    let Some(family) = family else {
+        return Ok(Vec::new())
+    };
+
+    let reply = socket.request(family,netlink::F_DUMP,&genl_request(NL80211_CMD_GET_INTERFACE))?;
+    let mut interfaces = parse_interfaces(&reply,family)?;
+
+    for (index,wireless) in interfaces.iter_mut().filter(|(_,wireless)| wireless.ssid.is_some()) {
+        let mut request = genl_request(NL80211_CMD_GET_STATION);
+        netlink::push_attribute(&mut request,NL80211_ATTR_IFINDEX,&index.to_ne_bytes());
+
+        let reply = socket.request(family,netlink::F_DUMP,&request)?;
+        (wireless.signal,wireless.bitrate) = parse_station(&reply,family)?;
+    }
+
+    Ok(interfaces)
+}
+
+// every interface with its addresses, and radio details for wireless ones
+// radios: also ask nl80211, which takes a few more requests
+pub fn get_links(radios: bool) -> Result<Vec<Link>, NetlinkError> {
+    let mut route = Socket::open(netlink::ROUTE)?;
+
+    // struct ifinfomsg, ifaddrmsg and rtmsg, zeroed to ask for every family
+    let mut links = parse_links(&route.request(RTM_GETLINK,netlink::F_DUMP,&[0;IFINFOMSG_LEN])?)?;
+    let addresses = parse_addresses(&route.request(RTM_GETADDR,netlink::F_DUMP,&[0;IFADDRMSG_LEN])?)?;
+    let default_route = parse_default_route(&route.request(RTM_GETROUTE,netlink::F_DUMP,&[0;RTMSG_LEN])?)?;
+
+    for (index,address) in addresses {
+        if let Some(link) = links.iter_mut().find(|link| link.index == index) {
+            link.addresses.push(address);
+        }
+    }
+
+    for link in links.iter_mut() {
+        link.default_route = Some(link.index) == default_route;
+    }
+
+    if !radios {
+        return Ok(links)
+    }
+
+    let mut generic = Socket::open(netlink::GENERIC)?;
+    for (index,wireless) in wireless_info(&mut generic)? {
+        if let Some(link) = links.iter_mut().find(|link| link.index == index) {
+            link.wireless = Some(wireless);
+        }
+    }
+
+    Ok(links)
+}
+
+/* the interface to show: the named one, else the first connected
+ * wireless interface, else the first connected one that is not loopback
+ */
+pub fn primary_link<'a>(links: &'a [Link], name: Option<&str>) -> Option<&'a Link> {
+    if let Some(name) = name {
+        return links.iter().find(|link| link.name == name)
+    }
+
+    links.iter()
+        .find(|link| link.up && link.wireless.as_ref().is_some_and(|wireless| wireless.ssid.is_some()))
+        .or_else(|| links.iter().find(|link| link.up && !link.loopback))
+}
+
+/* interface: the named one, else the one carrying the default route,
+ * else the first connected one that is not loopback
+ */
+pub fn route_link<'a>(links: &'a [Link], name: Option<&str>) -> Option<&'a Link> {
+    if let Some(name) = name {
+        return links.iter().find(|link| link.name == name)
+    }
+
+    links.iter()
+        .find(|link| link.default_route)
+        .or_else(|| links.iter().find(|link| link.up && !link.loopback))
+}
+
+// (interface, received bytes, transmitted bytes) from /proc/net/dev
+pub fn parse_net_dev(text: &str) -> Vec<(String,u64,u64)> {
+    text.lines()
+        // two header lines
+        .skip(2)
+        .filter_map(|line| {
+            let (name,counters) = line.split_once(':')?;
+            let counters: Vec<u64> = counters.split_whitespace()
+                .map(|counter| counter.parse().unwrap_or(0))
+                .collect();
+            // 8 receive counters then 8 transmit counters
+            Some((name.trim().to_string(),*counters.first()?,*counters.get(8)?))
+        })
+        .collect()
+}
+
+// one read of /proc/net/dev, taken apart from Throughput so it can be read on another thread
+pub struct NetDev {
+    text: String,
+    taken: Instant,
+}
+
+impl NetDev {
+    pub fn read(source: &SystemSource) -> Result<Self, StatusError> {
+        let text = source.read(NET_DEV)
+            .map_err(|err| StatusError::Io(source.path(NET_DEV),err))?;
+        Ok(NetDev { text, taken: Instant::now() })
+    }
+}
+
+/* rx/tx rates of one interface from two samples of /proc/net/dev, like Cpu with /proc/stat
+ * history: newest rate last, at most HISTORY_LEN samples
+ */
+pub struct Throughput {
+    interface: String,
+    last: Option<(Instant,u64,u64)>,
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+    pub rx_total: u64,
+    pub tx_total: u64,
+    pub history: VecDeque<(f64,f64)>,
+}
+
+pub const HISTORY_LEN: usize = 16;
+
+impl Throughput {
+    pub fn new(interface: &str) -> Self {
+        Throughput {
+            interface: interface.to_string(),
+            last: None,
+            rx_rate: 0.0,
+            tx_rate: 0.0,
+            rx_total: 0,
+            tx_total: 0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    pub fn add(&mut self, net_dev: &NetDev) {
+        self.add_sample(&net_dev.text,net_dev.taken);
+    }
+
+    fn add_sample(&mut self, net_dev: &str, now: Instant) {
+        let Some((_,rx,tx)) = parse_net_dev(net_dev).into_iter()
+            .find(|(name,_,_)| *name == self.interface) else {
+            return
+        };
+
+        if let Some((then,last_rx,last_tx)) = self.last {
+            let seconds = now.duration_since(then).as_secs_f64();
+            if seconds > 0.0 {
+                // counters start over when the interface is recreated
+                self.rx_rate = rx.saturating_sub(last_rx) as f64 / seconds;
+                self.tx_rate = tx.saturating_sub(last_tx) as f64 / seconds;
+
+                if self.history.len() == HISTORY_LEN {
+                    self.history.pop_front();
+                }
+                self.history.push_back((self.rx_rate,self.tx_rate));
+            }
+        }
+
+        self.last = Some((now,rx,tx));
+        (self.rx_total,self.tx_total) = (rx,tx);
+    }
+}
+
+// one block per value, scaled to the largest
+pub fn sparkline(values: impl Iterator<Item = f64> + Clone) -> String {
+    const BLOCKS: [char;8] = ['▁','▂','▃','▄','▅','▆','▇','█'];
+
+    let max = values.clone().fold(0.0,f64::max);
+    values
+        .map(|value| if max > 0.0 {
+            BLOCKS[((value / max) * (BLOCKS.len() - 1) as f64).round() as usize]
+        } else {
+            BLOCKS[0]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // recorded from a container: lo, two ifb devices that are down and eth0
+    const GETLINK: &[u8] = include_bytes!("../tests/fixtures/netlink/getlink.bin");
+    const GETADDR: &[u8] = include_bytes!("../tests/fixtures/netlink/getaddr.bin");
+    // nl80211 replies for a laptop on a 5 GHz network, family id 0x1d
+    const FAMILY: &[u8] = include_bytes!("../tests/fixtures/netlink/nl80211-family.bin");
+    const INTERFACES: &[u8] = include_bytes!("../tests/fixtures/netlink/nl80211-interfaces.bin");
+    const STATION: &[u8] = include_bytes!("../tests/fixtures/netlink/nl80211-station.bin");
+    const GETROUTE: &[u8] = include_bytes!("../tests/fixtures/netlink/getroute.bin");
+    const NET_DEV: &str = include_str!("../tests/fixtures/system/laptop/proc/net/dev");
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+
+    #[test]
+    fn links_and_addresses() {
+        let links = parse_links(GETLINK).unwrap();
+        let names: Vec<&str> = links.iter().map(|link| link.name.as_str()).collect();
+        assert_eq!(names, vec!["lo","ifb0","ifb1","eth0"]);
+
+        assert!(links[0].loopback && links[0].up);
+        assert!(!links[1].up);
+        assert!(links[3].up && !links[3].loopback);
+
+        let addresses = parse_addresses(GETADDR).unwrap();
+        let eth0: Vec<String> = addresses.iter()
+            .filter(|(index,_)| *index == links[3].index)
+            .map(|(_,address)| address.to_string())
+            .collect();
+        assert_eq!(eth0, vec!["192.0.2.2","fd00::2","fe80::fc:ff:fe00:1"]);
+    }
+
+    #[test]
+    fn nl80211_replies() {
+        let family = parse_family_id(FAMILY).unwrap().unwrap();
+        assert_eq!(family, 0x1d);
+
+        // the p2p device without an interface index is left out
+        let interfaces = parse_interfaces(INTERFACES,family).unwrap();
+        assert_eq!(interfaces.len(), 1);
+        assert_eq!(interfaces[0].0, 3);
+        assert_eq!(interfaces[0].1.ssid.as_deref(), Some("Café 5G"));
+        assert_eq!(interfaces[0].1.frequency, Some(5180));
+
+        let (signal,bitrate) = parse_station(STATION,family).unwrap();
+        assert_eq!(signal, Some(-52));
+        assert_eq!(bitrate, Some(8667));
+    }
+
+    #[test]
+    fn wireless_preferred_over_wired() {
+        let mut links = parse_links(GETLINK).unwrap();
+        assert_eq!(primary_link(&links,None).unwrap().name, "eth0");
+
+        links[1].up = true;
+        links[1].wireless = Some(Wireless {
+            ssid: Some("Café 5G".to_string()),
+            signal: Some(-70),
+            ..Wireless::default()
+        });
+        let link = primary_link(&links,None).unwrap();
+        assert_eq!(link.name, "ifb0");
+        assert_eq!(link.wireless.as_ref().unwrap().strength(), 2);
+
+        assert_eq!(primary_link(&links,Some("lo")).unwrap().name, "lo");
+        assert!(primary_link(&links,Some("wlan9")).is_none());
+    }
+
+    #[test]
+    fn kernel_errors_are_returned() {
+        // NLMSG_ERROR with -ENOENT
+        let mut reply = vec![36,0,0,0, 2,0, 0,1, 1,0,0,0, 0,0,0,0];
+        reply.extend_from_slice(&(-libc::ENOENT).to_ne_bytes());
+        reply.extend_from_slice(&[0;16]);
+
+        assert!(matches!(parse_family_id(&reply), Err(NetlinkError::Kernel(libc::ENOENT))));
+    }
+
+    #[test]
+    fn default_route_interface() {
+        let links = parse_links(GETLINK).unwrap();
+        let eth0 = links.iter().find(|link| link.name == "eth0").unwrap();
+
+        // main table routes only, the local table has no default route
+        assert_eq!(parse_default_route(GETROUTE).unwrap(), Some(eth0.index));
+        assert_eq!(route_link(&links,Some("ifb1")).unwrap().name, "ifb1");
+    }
+
+    #[test]
+    fn net_dev_counters() {
+        let counters = parse_net_dev(NET_DEV);
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters[3], ("eth0".to_string(),5100,7024));
+    }
+
+    #[test]
+    fn rates_from_two_samples() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new("eth0");
+        throughput.add_sample(NET_DEV,start);
+        assert!(throughput.history.is_empty());
+
+        let later = NET_DEV.replace("5100","1053700").replace("7024","59452");
+        throughput.add_sample(&later,start + std::time::Duration::from_secs(2));
+
+        assert_eq!(throughput.rx_rate, 524300.0);
+        assert_eq!(throughput.tx_rate, 26214.0);
+    }
+
+    #[test]
+    fn sample_from_source() {
+        let mut throughput = Throughput::new("eth0");
+        throughput.add(&NetDev::read(&SystemSource::new(LAPTOP)).unwrap());
+        assert_eq!((throughput.rx_total,throughput.tx_total), (5100,7024));
+
+        let Err(err) = NetDev::read(&SystemSource::new("tests/fixtures/system/arm-big-little")) else {
+            panic!("no /proc/net/dev in the fixture")
+        };
+        assert!(matches!(err, StatusError::Io(path,_) if path.ends_with("proc/net/dev")));
+    }
+
+    #[test]
+    fn sparkline_scales_to_peak() {
+        assert_eq!(sparkline([0.0,50.0,100.0].into_iter()), "▁▅█");
+        assert_eq!(sparkline([0.0,0.0].into_iter()), "▁▁");
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    fs,
+    io,
+    path::Path,
+    time::Duration,
+};
+
+use crate::system::SystemSource;
+
+pub const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+    Unknown,
+}
+
+impl Status {
+    fn parse(status: &str) -> Self {
+        match status {
+            "Charging" => Status::Charging,
+            "Discharging" => Status::Discharging,
+            "Not charging" => Status::NotCharging,
+            "Full" => Status::Full,
+            _ => Status::Unknown,
+        }
+    }
+}
+
+/* Battery: one system battery from sysfs
+ *
+ * drivers report either energy_* and power_now (µWh, µW) or charge_* and
+ * current_now (µAh, µA), charge is turned into energy with the voltage so
+ * batteries of both kinds can be added up, without a voltage it is left as is
+ *  capacity: percent as the driver reports it
+ *  now/full: what is stored and what fits
+ *  rate: charge or discharge rate, always positive
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Battery {
+    pub name: String,
+    pub status: Status,
+    pub capacity: Option<u32>,
+    pub now: Option<f64>,
+    pub full: Option<f64>,
+    pub rate: Option<f64>,
+}
+
+// a charger, `AC`, `ADP1`, `ACAD` and so on depending on the machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mains {
+    pub name: String,
+    pub online: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PowerSupplies {
+    pub batteries: Vec<Battery>,
+    pub mains: Vec<Mains>,
+}
+
+// one sysfs attribute, trimmed
+pub(crate) fn read(dir: &Path, key: &str) -> Option<String> {
+    fs::read_to_string(dir.join(key)).ok().map(|value| value.trim().to_string())
+}
+
+fn number(dir: &Path, key: &str) -> Option<f64> {
+    read(dir,key)?.parse().ok()
+}
+
+impl Battery {
+    fn read(dir: &Path, name: String) -> Self {
+        let (now,full,rate) = match number(dir,"energy_now") {
+            Some(now) => (Some(now),number(dir,"energy_full"),number(dir,"power_now")),
+            None => {
+                // design voltage is steady, voltage_now moves with the charge
+                let volts = number(dir,"voltage_min_design")
+                    .or_else(|| number(dir,"voltage_now"))
+                    .map(|microvolts| microvolts / 1_000_000.0);
+                // no voltage, no energy: charge would be added up with other batteries' energy
+                let energy = |key| Some(number(dir,key)? * volts?);
+                (energy("charge_now"),energy("charge_full"),energy("current_now"))
+            }
+        };
+        // the charge still gives a percentage when the driver has none
+        let charged = || match (number(dir,"charge_now"),number(dir,"charge_full")) {
+            (Some(now),Some(full)) if full > 0.0 => Some((now / full * 100.0).round() as u32),
+            _ => None,
+        };
+
+        Battery {
+            name,
+            status: read(dir,"status").map(|status| Status::parse(&status)).unwrap_or(Status::Unknown),
+            capacity: read(dir,"capacity").and_then(|capacity| capacity.parse().ok()).or_else(charged),
+            now,
+            full,
+            // some drivers report the discharge rate as negative
+            rate: rate.map(f64::abs).filter(|rate| *rate > 0.0),
+        }
+    }
+
+    pub fn percent(&self) -> Option<u32> {
+        self.capacity.or_else(|| match (self.now,self.full) {
+            (Some(now),Some(full)) if full > 0.0 => Some((now / full * 100.0).round() as u32),
+            _ => None,
+        })
+    }
+}
+
+/* every battery and charger under `root`, sorted by name
+ * batteries of peripherals (mice, headsets) have scope Device and are left out
+ */
+pub fn scan(root: &Path) -> io::Result<PowerSupplies> {
+    let mut entries: Vec<_> = fs::read_dir(root)?
+        .filter_map(Result::ok)
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut supplies = PowerSupplies::default();
+    for entry in entries {
+        let dir = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        match read(&dir,"type").as_deref() {
+            Some("Battery") if read(&dir,"scope").as_deref() != Some("Device") => {
+                supplies.batteries.push(Battery::read(&dir,name));
+            },
+            Some("Mains") => supplies.mains.push(Mains {
+                name,
+                online: read(&dir,"online").as_deref() == Some("1"),
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(supplies)
+}
+
+pub fn power_supplies(source: &SystemSource) -> io::Result<PowerSupplies> {
+    let root = source.path(POWER_SUPPLY);
+    if !root.is_dir() {
+        return Ok(PowerSupplies::default())
+    }
+    scan(&root)
+}
+
+impl PowerSupplies {
+    pub fn on_mains(&self) -> bool {
+        self.mains.iter().any(|mains| mains.online)
+    }
+
+    // stored energy over what fits across every battery, drivers' own percentages averaged otherwise
+    pub fn capacity(&self) -> Option<u32> {
+        let totals: Option<Vec<(f64,f64)>> = self.batteries.iter()
+            .map(|battery| Some((battery.now?,battery.full?)))
+            .collect();
+
+        if let Some(totals) = totals.filter(|totals| !totals.is_empty()) {
+            let (now,full) = totals.iter().fold((0.0,0.0),|(now,full),(n,f)| (now + n,full + f));
+            if full > 0.0 {
+                return Some(((now / full * 100.0).round() as u32).min(100))
+            }
+        }
+
+        let percents: Vec<u32> = self.batteries.iter().filter_map(Battery::percent).collect();
+        if percents.is_empty() {
+            return None
+        }
+        Some(percents.iter().sum::<u32>() / percents.len() as u32)
+    }
+
+    /* one status for all batteries
+     * any charging or discharging wins, the other is idle while one is used
+     */
+    pub fn status(&self) -> Status {
+        let any = |status| self.batteries.iter().any(|battery| battery.status == status);
+
+        if any(Status::Charging) {
+            Status::Charging
+        } else if any(Status::Discharging) {
+            Status::Discharging
+        } else if !self.batteries.is_empty() && self.batteries.iter().all(|battery| battery.status == Status::Full) {
+            Status::Full
+        } else if any(Status::NotCharging) || any(Status::Full) {
+            Status::NotCharging
+        } else {
+            Status::Unknown
+        }
+    }
+
+    // until empty while discharging, until full while charging
+    pub fn time_left(&self) -> Option<Duration> {
+        let status = self.status();
+        let active: Vec<&Battery> = self.batteries.iter()
+            .filter(|battery| battery.status == status)
+            .collect();
+
+        let rate: f64 = active.iter().filter_map(|battery| battery.rate).sum();
+        if rate <= 0.0 {
+            return None
+        }
+
+        let amount: f64 = match status {
+            Status::Discharging => self.batteries.iter().filter_map(|battery| battery.now).sum(),
+            Status::Charging => self.batteries.iter()
+                .filter_map(|battery| Some((battery.full? - battery.now?).max(0.0)))
+                .sum(),
+            _ => return None,
+        };
+
+        Some(Duration::from_secs_f64(amount / rate * 3600.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env,
+        path::PathBuf,
+        process,
+    };
+
+    // a power_supply directory with one subdirectory per device
+    struct FakeSysfs {
+        root: PathBuf,
+    }
+
+    impl FakeSysfs {
+        fn new(name: &str) -> Self {
+            let root = env::temp_dir().join(format!("epic-bar-{}-{name}",process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            FakeSysfs { root }
+        }
+
+        fn device(&self, name: &str, attributes: &[(&str,&str)]) -> &Self {
+            let dir = self.root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (key,value) in attributes {
+                fs::write(dir.join(key),format!("{value}\n")).unwrap();
+            }
+            self
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn finds_batteries_and_mains() {
+        let sysfs = FakeSysfs::new("power-kinds");
+        // listed before the battery, as on machines where the old code gave up
+        sysfs.device("AC",&[("type","Mains"),("online","1")])
+            .device("BAT0",&[("type","Battery"),("status","Full"),("capacity","100")])
+            .device("hidpp_battery_0",&[("type","Battery"),("scope","Device"),("capacity","40")])
+            .device("ucsi-source-psy-USBC000:001",&[("type","USB"),("online","0")]);
+
+        let supplies = scan(&sysfs.root).unwrap();
+        let batteries: Vec<&str> = supplies.batteries.iter().map(|battery| battery.name.as_str()).collect();
+        assert_eq!(batteries, ["BAT0"]);
+        assert_eq!(supplies.mains, [Mains { name: "AC".to_string(), online: true }]);
+        assert!(supplies.on_mains());
+        assert_eq!(supplies.status(), Status::Full);
+        assert_eq!(supplies.capacity(), Some(100));
+        assert_eq!(supplies.time_left(), None);
+    }
+
+    #[test]
+    fn charge_and_energy_batteries_add_up() {
+        let sysfs = FakeSysfs::new("power-units");
+        // 10 Wh of 40 Wh at 10 W
+        sysfs.device("BAT0",&[
+                ("type","Battery"),
+                ("status","Discharging"),
+                ("capacity","25"),
+                ("energy_now","10000000"),
+                ("energy_full","40000000"),
+                ("power_now","10000000"),
+            ])
+            // 2 Ah of 5 Ah at 10 V, so 20 Wh of 50 Wh, idle while BAT0 is used
+            .device("BAT1",&[
+                ("type","Battery"),
+                ("status","Not charging"),
+                ("capacity","40"),
+                ("charge_now","2000000"),
+                ("charge_full","5000000"),
+                ("current_now","0"),
+                ("voltage_min_design","10000000"),
+            ])
+            .device("ADP1",&[("type","Mains"),("online","0")]);
+
+        let supplies = scan(&sysfs.root).unwrap();
+        assert_eq!(supplies.batteries[1].now, Some(20_000_000.0));
+        assert_eq!(supplies.batteries[1].rate, None);
+
+        assert!(!supplies.on_mains());
+        assert_eq!(supplies.status(), Status::Discharging);
+        // 30 Wh of 90 Wh
+        assert_eq!(supplies.capacity(), Some(33));
+        // both batteries drained at 10 W
+        assert_eq!(supplies.time_left(), Some(Duration::from_secs(3 * 3600)));
+    }
+
+    #[test]
+    fn charging_time_and_missing_counters() {
+        let sysfs = FakeSysfs::new("power-charging");
+        // negative current as some drivers report
+        sysfs.device("BAT1",&[
+                ("type","Battery"),
+                ("status","Charging"),
+                ("charge_now","1000000"),
+                ("charge_full","4000000"),
+                ("current_now","-2000000"),
+                ("voltage_now","12000000"),
+            ])
+            // only a percentage
+            .device("BAT2",&[("type","Battery"),("status","Unknown"),("capacity","60")]);
+
+        let supplies = scan(&sysfs.root).unwrap();
+        assert_eq!(supplies.batteries[0].percent(), Some(25));
+        assert_eq!(supplies.status(), Status::Charging);
+        // not every battery has counters, the percentages are averaged
+        assert_eq!(supplies.capacity(), Some(42));
+        assert_eq!(supplies.time_left(), Some(Duration::from_secs(3600 + 1800)));
+    }
+
+    #[test]
+    fn charge_without_voltage() {
+        let sysfs = FakeSysfs::new("power-mixed");
+        // 10 Wh of 40 Wh
+        sysfs.device("BAT0",&[
+                ("type","Battery"),
+                ("status","Discharging"),
+                ("capacity","25"),
+                ("energy_now","10000000"),
+                ("energy_full","40000000"),
+                ("power_now","10000000"),
+            ])
+            // 3 Ah of 4 Ah and no voltage to tell the energy
+            .device("BAT1",&[
+                ("type","Battery"),
+                ("status","Discharging"),
+                ("charge_now","3000000"),
+                ("charge_full","4000000"),
+                ("current_now","1000000"),
+            ]);
+
+        let supplies = scan(&sysfs.root).unwrap();
+        assert_eq!(supplies.batteries[1].now, None);
+        assert_eq!(supplies.batteries[1].rate, None);
+        assert_eq!(supplies.batteries[1].percent(), Some(75));
+        // µAh added to µWh would give 30%
+        assert_eq!(supplies.capacity(), Some(50));
+    }
+}
@@ -0,0 +1,1273 @@
+use std::{
+    collections::VecDeque,
+    env,
+    error,
+    fmt,
+    fs,
+    io::{self,Read,Write},
+    os::unix::{
+        io::AsRawFd,
+        net::UnixStream,
+    },
+    path::{Path,PathBuf},
+    sync::{mpsc,OnceLock},
+    thread,
+    time::Duration,
+    cell::{Cell,RefCell},
+    rc::Rc,
+};
+
+use gtk::glib;
+
+/* for this program:
+ *  server: pulseaudio or pipewire-pulse, both serve the same native protocol
+ *  sink: an output (speakers, headphones), source: an input (microphone)
+ *
+ * every packet is a 20 byte descriptor followed by a tagstruct, a list of
+ * values each prefixed with a one byte tag telling its type
+ */
+
+// native-common.h
+const COMMAND_ERROR: u32 = 0;
+const COMMAND_REPLY: u32 = 2;
+const COMMAND_AUTH: u32 = 8;
+const COMMAND_SET_CLIENT_NAME: u32 = 9;
+const COMMAND_GET_SERVER_INFO: u32 = 20;
+const COMMAND_GET_SINK_INFO: u32 = 21;
+const COMMAND_GET_SOURCE_INFO: u32 = 23;
+const COMMAND_GET_SINK_INPUT_INFO_LIST: u32 = 30;
+const COMMAND_GET_SOURCE_OUTPUT_INFO_LIST: u32 = 32;
+const COMMAND_SUBSCRIBE: u32 = 35;
+const COMMAND_SET_SINK_VOLUME: u32 = 36;
+const COMMAND_SET_SINK_INPUT_VOLUME: u32 = 37;
+const COMMAND_SET_SOURCE_VOLUME: u32 = 38;
+const COMMAND_SET_SINK_MUTE: u32 = 39;
+const COMMAND_SET_SOURCE_MUTE: u32 = 40;
+const COMMAND_SUBSCRIBE_EVENT: u32 = 66;
+const COMMAND_SET_SINK_INPUT_MUTE: u32 = 69;
+const COMMAND_SET_SOURCE_OUTPUT_VOLUME: u32 = 98;
+const COMMAND_SET_SOURCE_OUTPUT_MUTE: u32 = 99;
+
+// tagstruct.h
+const TAG_STRING: u8 = b't';
+const TAG_STRING_NULL: u8 = b'N';
+const TAG_U32: u8 = b'L';
+const TAG_U8: u8 = b'B';
+const TAG_USEC: u8 = b'U';
+const TAG_SAMPLE_SPEC: u8 = b'a';
+const TAG_ARBITRARY: u8 = b'x';
+const TAG_BOOLEAN_TRUE: u8 = b'1';
+const TAG_BOOLEAN_FALSE: u8 = b'0';
+const TAG_CHANNEL_MAP: u8 = b'm';
+const TAG_CVOLUME: u8 = b'v';
+const TAG_PROPLIST: u8 = b'P';
+const TAG_FORMAT_INFO: u8 = b'f';
+
+const PROTOCOL_VERSION: u32 = 32;
+// record streams have a volume since 22, every field read here exists by then
+const MIN_PROTOCOL_VERSION: u32 = 22;
+const DESCRIPTOR_LEN: usize = 20;
+const COMMAND_CHANNEL: u32 = u32::MAX;
+const NO_INDEX: u32 = u32::MAX;
+const COOKIE_LEN: usize = 256;
+// a tagstruct bigger than this is not something we asked for
+const MAX_PACKET: usize = 1024 * 1024;
+
+// subscription mask and event facilities, def.h
+const SUBSCRIPTION_MASK_SINK: u32 = 0x1;
+const SUBSCRIPTION_MASK_SOURCE: u32 = 0x2;
+const SUBSCRIPTION_MASK_SINK_INPUT: u32 = 0x4;
+const SUBSCRIPTION_MASK_SOURCE_OUTPUT: u32 = 0x8;
+const SUBSCRIPTION_MASK_SERVER: u32 = 0x80;
+
+// 100%, anything above is software amplification
+pub const VOLUME_NORM: u32 = 0x10000;
+
+const RECONNECT_MIN: Duration = Duration::from_millis(250);
+const RECONNECT_MAX: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum PulseError {
+    // no socket found to connect to
+    NotRunning,
+    Io(io::Error),
+    // reply did not have the shape expected
+    Protocol(&'static str),
+    // error code the server answered with, see pa_error_code
+    Server(u32),
+}
+
+impl fmt::Display for PulseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PulseError::NotRunning => write!(f,"no PulseAudio or PipeWire socket found"),
+            PulseError::Io(err) => write!(f,"audio server socket: {err}"),
+            PulseError::Protocol(what) => write!(f,"unexpected reply from audio server: {what}"),
+            PulseError::Server(code) => write!(f,"audio server refused request (error {code})"),
+        }
+    }
+}
+
+impl error::Error for PulseError {}
+
+impl From<io::Error> for PulseError {
+    fn from(err: io::Error) -> Self {
+        PulseError::Io(err)
+    }
+}
+
+#[derive(Default)]
+pub struct TagWriter {
+    buf: Vec<u8>,
+}
+
+impl TagWriter {
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.push(TAG_U32);
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn string(&mut self, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) => {
+                self.buf.push(TAG_STRING);
+                self.buf.extend_from_slice(value.as_bytes());
+                self.buf.push(0);
+            },
+            None => self.buf.push(TAG_STRING_NULL),
+        }
+        self
+    }
+
+    pub fn boolean(&mut self, value: bool) -> &mut Self {
+        self.buf.push(if value { TAG_BOOLEAN_TRUE } else { TAG_BOOLEAN_FALSE });
+        self
+    }
+
+    pub fn arbitrary(&mut self, data: &[u8]) -> &mut Self {
+        self.buf.push(TAG_ARBITRARY);
+        self.buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.buf.extend_from_slice(data);
+        self
+    }
+
+    pub fn cvolume(&mut self, volume: &[u32]) -> &mut Self {
+        self.buf.push(TAG_CVOLUME);
+        self.buf.push(volume.len() as u8);
+        for channel in volume {
+            self.buf.extend_from_slice(&channel.to_be_bytes());
+        }
+        self
+    }
+
+    // string properties carry their nul terminator
+    pub fn proplist(&mut self, properties: &[(&str,&str)]) -> &mut Self {
+        self.buf.push(TAG_PROPLIST);
+        for (key,value) in properties {
+            let mut data = value.as_bytes().to_vec();
+            data.push(0);
+
+            self.string(Some(key));
+            self.u32(data.len() as u32);
+            self.arbitrary(&data);
+        }
+        self.buf.push(TAG_STRING_NULL);
+        self
+    }
+
+    // only used to answer in tests, the server side of things
+    #[cfg(test)]
+    pub fn raw(&mut self, data: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(data);
+        self
+    }
+}
+
+pub struct TagReader {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl TagReader {
+    pub fn new(buf: Vec<u8>) -> Self {
+        TagReader { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], PulseError> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or(PulseError::Protocol("tagstruct too short"))?;
+        let data = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(data)
+    }
+
+    fn tag(&mut self, expected: u8) -> Result<(), PulseError> {
+        match self.take(1)?[0] {
+            tag if tag == expected => Ok(()),
+            _ => Err(PulseError::Protocol("wrong tag")),
+        }
+    }
+
+    fn be_u32(&mut self) -> Result<u32, PulseError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn u8(&mut self) -> Result<u8, PulseError> {
+        self.tag(TAG_U8)?;
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, PulseError> {
+        self.tag(TAG_U32)?;
+        self.be_u32()
+    }
+
+    pub fn usec(&mut self) -> Result<u64, PulseError> {
+        self.tag(TAG_USEC)?;
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+
+    fn arbitrary(&mut self) -> Result<Vec<u8>, PulseError> {
+        self.tag(TAG_ARBITRARY)?;
+        let len = self.be_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn string(&mut self) -> Result<Option<String>, PulseError> {
+        match self.take(1)?[0] {
+            TAG_STRING_NULL => Ok(None),
+            TAG_STRING => {
+                let rest = &self.buf[self.pos..];
+                let len = rest.iter().position(|byte| *byte == 0)
+                    .ok_or(PulseError::Protocol("unterminated string"))?;
+                let value = String::from_utf8_lossy(&rest[..len]).into_owned();
+                self.pos += len + 1;
+                Ok(Some(value))
+            },
+            _ => Err(PulseError::Protocol("wrong tag")),
+        }
+    }
+
+    pub fn boolean(&mut self) -> Result<bool, PulseError> {
+        match self.take(1)?[0] {
+            TAG_BOOLEAN_TRUE => Ok(true),
+            TAG_BOOLEAN_FALSE => Ok(false),
+            _ => Err(PulseError::Protocol("wrong tag")),
+        }
+    }
+
+    // (format, channels, rate)
+    pub fn sample_spec(&mut self) -> Result<(u8,u8,u32), PulseError> {
+        self.tag(TAG_SAMPLE_SPEC)?;
+        let spec = self.take(2)?;
+        let (format,channels) = (spec[0],spec[1]);
+        Ok((format,channels,self.be_u32()?))
+    }
+
+    pub fn channel_map(&mut self) -> Result<Vec<u8>, PulseError> {
+        self.tag(TAG_CHANNEL_MAP)?;
+        let channels = self.take(1)?[0] as usize;
+        Ok(self.take(channels)?.to_vec())
+    }
+
+    pub fn cvolume(&mut self) -> Result<Vec<u32>, PulseError> {
+        self.tag(TAG_CVOLUME)?;
+        let channels = self.take(1)?[0];
+        (0..channels).map(|_| self.be_u32()).collect()
+    }
+
+    // values are bytes, the ones we read are strings with a nul terminator
+    pub fn proplist(&mut self) -> Result<Vec<(String,String)>, PulseError> {
+        self.tag(TAG_PROPLIST)?;
+        let mut properties = Vec::new();
+
+        while let Some(key) = self.string()? {
+            let len = self.u32()? as usize;
+            let value = self.arbitrary()?;
+            if value.len() != len {
+                return Err(PulseError::Protocol("property length"))
+            }
+            let end = value.iter().position(|byte| *byte == 0).unwrap_or(value.len());
+            properties.push((key,String::from_utf8_lossy(&value[..end]).into_owned()));
+        }
+
+        Ok(properties)
+    }
+
+    // (encoding, properties)
+    pub fn format_info(&mut self) -> Result<(u8,Vec<(String,String)>), PulseError> {
+        self.tag(TAG_FORMAT_INFO)?;
+        Ok((self.u8()?,self.proplist()?))
+    }
+}
+
+fn write_packet(stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let mut packet = Vec::with_capacity(DESCRIPTOR_LEN + payload.len());
+    // length, channel, offset high, offset low, flags
+    for field in [payload.len() as u32, COMMAND_CHANNEL, 0, 0, 0] {
+        packet.extend_from_slice(&field.to_be_bytes());
+    }
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet)
+}
+
+// (channel, payload), only the command channel is used without streams
+fn read_packet(stream: &mut impl Read) -> Result<(u32,Vec<u8>), PulseError> {
+    let mut descriptor = [0u8;DESCRIPTOR_LEN];
+    stream.read_exact(&mut descriptor)?;
+
+    let len = u32::from_be_bytes(descriptor[0..4].try_into().expect("4 bytes")) as usize;
+    let channel = u32::from_be_bytes(descriptor[4..8].try_into().expect("4 bytes"));
+    if len > MAX_PACKET {
+        return Err(PulseError::Protocol("packet too large"))
+    }
+
+    let mut payload = vec![0u8;len];
+    stream.read_exact(&mut payload)?;
+    Ok((channel,payload))
+}
+
+/* Device: a sink or source
+ * volume: one value per channel, VOLUME_NORM being 100%
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub volume: Vec<u32>,
+    pub muted: bool,
+}
+
+impl Device {
+    // sinks and sources start the same, the rest differs between protocol versions
+    fn read(reply: &mut TagReader) -> Result<Self, PulseError> {
+        let index = reply.u32()?;
+        let name = reply.string()?.unwrap_or_default();
+        let description = reply.string()?.unwrap_or_default();
+        reply.sample_spec()?;
+        reply.channel_map()?;
+        // owner module
+        reply.u32()?;
+        let volume = reply.cvolume()?;
+        let muted = reply.boolean()?;
+
+        Ok(Device { index, name, description, volume, muted })
+    }
+
+    pub fn percent(&self) -> u32 {
+        percent(&self.volume)
+    }
+
+    pub fn volume_at(&self, percent: u32) -> Vec<u32> {
+        volume_at(&self.volume,percent)
+    }
+}
+
+// loudest channel, like pavucontrol shows
+fn percent(volume: &[u32]) -> u32 {
+    let max = volume.iter().copied().max().unwrap_or(0) as u64;
+    ((max * 100 + VOLUME_NORM as u64 / 2) / VOLUME_NORM as u64) as u32
+}
+
+// every channel moved so the loudest is at percent, keeping the balance
+fn volume_at(volume: &[u32], percent: u32) -> Vec<u32> {
+    let target = percent as u64 * VOLUME_NORM as u64 / 100;
+    let max = volume.iter().copied().max().unwrap_or(0) as u64;
+
+    volume.iter()
+        .map(|channel| match max {
+            0 => target as u32,
+            _ => (*channel as u64 * target / max) as u32,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    // sink input
+    Playback,
+    // source output
+    Record,
+}
+
+/* Stream: one application playing or recording
+ * name: the application's name, the stream's own name if it has none
+ * corked: paused, a corked record stream is not capturing
+ * has_volume: false for passthrough streams, volume is then meaningless
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub kind: StreamKind,
+    pub index: u32,
+    pub name: String,
+    pub volume: Vec<u32>,
+    pub muted: bool,
+    pub corked: bool,
+    pub has_volume: bool,
+}
+
+fn property<'a>(properties: &'a [(String,String)], key: &str) -> Option<&'a str> {
+    properties.iter()
+        .find(|(name,_)| name == key)
+        .map(|(_,value)| value.as_str())
+}
+
+impl Stream {
+    // one entry of a sink input or source output list, the two differ in field order
+    fn read(reply: &mut TagReader, kind: StreamKind) -> Result<Self, PulseError> {
+        let index = reply.u32()?;
+        let media_name = reply.string()?;
+        // owner module, client, sink or source
+        for _ in 0..3 {
+            reply.u32()?;
+        }
+        reply.sample_spec()?;
+        reply.channel_map()?;
+
+        let mut volume = Vec::new();
+        let mut muted = false;
+        if kind == StreamKind::Playback {
+            volume = reply.cvolume()?;
+        }
+        // latency, device latency, resample method, driver
+        reply.usec()?;
+        reply.usec()?;
+        reply.string()?;
+        reply.string()?;
+        if kind == StreamKind::Playback {
+            muted = reply.boolean()?;
+        }
+        let properties = reply.proplist()?;
+        let corked = reply.boolean()?;
+        if kind == StreamKind::Record {
+            volume = reply.cvolume()?;
+            muted = reply.boolean()?;
+        }
+        let has_volume = reply.boolean()?;
+        // volume writable
+        reply.boolean()?;
+        reply.format_info()?;
+
+        let name = property(&properties,"application.name")
+            .map(str::to_string)
+            .or(media_name)
+            .unwrap_or_default();
+
+        Ok(Stream { kind, index, name, volume, muted, corked, has_volume })
+    }
+
+    pub fn percent(&self) -> u32 {
+        percent(&self.volume)
+    }
+
+    pub fn volume_at(&self, percent: u32) -> Vec<u32> {
+        volume_at(&self.volume,percent)
+    }
+}
+
+/* what the bar shows
+ * sink/source: the defaults, None if the server has none
+ * streams: every playback stream followed by every record stream
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioState {
+    pub sink: Option<Device>,
+    pub source: Option<Device>,
+    pub streams: Vec<Stream>,
+}
+
+impl AudioState {
+    // record streams that are capturing right now, what the mic indicator shows
+    pub fn capturing(&self) -> impl Iterator<Item = &Stream> {
+        self.streams.iter()
+            .filter(|stream| stream.kind == StreamKind::Record && !stream.corked)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+}
+
+/* one connection to the server
+ * events: subscription events that arrived while waiting for a reply
+ */
+pub struct Connection {
+    stream: UnixStream,
+    tag: u32,
+    events: VecDeque<(u32,u32)>,
+}
+
+fn socket_path() -> Option<PathBuf> {
+    // unix:/path or a bare path, tcp servers are not supported
+    if let Some(server) = env::var_os("PULSE_SERVER") {
+        let server = server.to_string_lossy().into_owned();
+        let path = server.strip_prefix("unix:").unwrap_or(&server);
+        if path.starts_with('/') {
+            return Some(PathBuf::from(path))
+        }
+    }
+
+    let runtime = env::var_os("XDG_RUNTIME_DIR")?;
+    Some(PathBuf::from(runtime).join("pulse").join("native"))
+}
+
+// pipewire-pulse does not check it, pulseaudio may
+fn cookie() -> Vec<u8> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let candidates = [
+        env::var_os("PULSE_COOKIE").map(PathBuf::from),
+        env::var_os("XDG_CONFIG_HOME").map(|dir| PathBuf::from(dir).join("pulse/cookie")),
+        home.as_ref().map(|home| home.join(".config/pulse/cookie")),
+        home.as_ref().map(|home| home.join(".pulse-cookie")),
+    ];
+
+    candidates.into_iter()
+        .flatten()
+        .find_map(|path| fs::read(path).ok().filter(|cookie| cookie.len() == COOKIE_LEN))
+        .unwrap_or_else(|| vec![0;COOKIE_LEN])
+}
+
+impl Connection {
+    pub fn connect() -> Result<Self, PulseError> {
+        let path = socket_path().ok_or(PulseError::NotRunning)?;
+        Connection::connect_to(&path)
+    }
+
+    pub fn connect_to(path: &Path) -> Result<Self, PulseError> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut connection = Connection {
+            stream,
+            tag: 0,
+            events: VecDeque::new(),
+        };
+
+        let cookie = cookie();
+        let mut reply = connection.request(COMMAND_AUTH,|request| {
+            request.u32(PROTOCOL_VERSION).arbitrary(&cookie);
+        })?;
+        // the upper bits are flags for shared memory
+        if reply.u32()? & 0xffff < MIN_PROTOCOL_VERSION {
+            return Err(PulseError::Protocol("server too old"))
+        }
+
+        connection.request(COMMAND_SET_CLIENT_NAME,|request| {
+            request.proplist(&[("application.name","epic-bar")]);
+        })?;
+
+        Ok(connection)
+    }
+
+    /* send a command and wait for its reply
+     * subscription events read in the meantime are kept for next_event
+     */
+    pub fn request<F: FnOnce(&mut TagWriter)>(&mut self, command: u32, f: F) -> Result<TagReader, PulseError> {
+        self.tag = self.tag.wrapping_add(1);
+
+        let mut request = TagWriter::default();
+        request.u32(command).u32(self.tag);
+        f(&mut request);
+        write_packet(&mut self.stream,&request.buf)?;
+
+        loop {
+            let (channel,payload) = read_packet(&mut self.stream)?;
+            if channel != COMMAND_CHANNEL {
+                continue
+            }
+
+            let mut reply = TagReader::new(payload);
+            let (command,tag) = (reply.u32()?,reply.u32()?);
+
+            match command {
+                COMMAND_SUBSCRIBE_EVENT => self.events.push_back((reply.u32()?,reply.u32()?)),
+                COMMAND_REPLY if tag == self.tag => return Ok(reply),
+                COMMAND_ERROR if tag == self.tag => return Err(PulseError::Server(reply.u32()?)),
+                _ => {}
+            }
+        }
+    }
+
+    // blocks until the server reports a change, (event type, index)
+    pub fn next_event(&mut self) -> Result<(u32,u32), PulseError> {
+        self.stream.set_read_timeout(None)?;
+
+        let event = loop {
+            if let Some(event) = self.events.pop_front() {
+                break event
+            }
+
+            let (channel,payload) = read_packet(&mut self.stream)?;
+            if channel != COMMAND_CHANNEL {
+                continue
+            }
+            let mut packet = TagReader::new(payload);
+            if packet.u32()? == COMMAND_SUBSCRIBE_EVENT {
+                packet.u32()?;
+                self.events.push_back((packet.u32()?,packet.u32()?));
+            }
+        };
+
+        self.stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        Ok(event)
+    }
+
+    // a packet is waiting on the socket, without blocking
+    fn readable(&self) -> Result<bool, PulseError> {
+        let mut fd = libc::pollfd {
+            fd: self.stream.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut fd,1,0) } {
+            -1 => Err(io::Error::last_os_error().into()),
+            ready => Ok(ready > 0),
+        }
+    }
+
+    /* drop the events queued or already sent by the server, without waiting for more
+     * one read of the state afterwards covers all of them
+     */
+    pub fn skip_events(&mut self) -> Result<(), PulseError> {
+        self.events.clear();
+        while self.readable()? {
+            let (channel,payload) = read_packet(&mut self.stream)?;
+            if channel != COMMAND_CHANNEL {
+                continue
+            }
+            // nothing else is in flight between requests
+            if TagReader::new(payload).u32()? != COMMAND_SUBSCRIBE_EVENT {
+                return Err(PulseError::Protocol("reply without a request"))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&mut self) -> Result<(), PulseError> {
+        let mask = SUBSCRIPTION_MASK_SINK
+            | SUBSCRIPTION_MASK_SOURCE
+            | SUBSCRIPTION_MASK_SINK_INPUT
+            | SUBSCRIPTION_MASK_SOURCE_OUTPUT
+            | SUBSCRIPTION_MASK_SERVER;
+        self.request(COMMAND_SUBSCRIBE,|request| {
+            request.u32(mask);
+        })?;
+        Ok(())
+    }
+
+    // (default sink, default source) names
+    pub fn defaults(&mut self) -> Result<(Option<String>,Option<String>), PulseError> {
+        let mut reply = self.request(COMMAND_GET_SERVER_INFO,|_| {})?;
+
+        // package name, version, user, host
+        for _ in 0..4 {
+            reply.string()?;
+        }
+        reply.sample_spec()?;
+        Ok((reply.string()?,reply.string()?))
+    }
+
+    pub fn device(&mut self, kind: DeviceKind, name: &str) -> Result<Device, PulseError> {
+        let command = match kind {
+            DeviceKind::Sink => COMMAND_GET_SINK_INFO,
+            DeviceKind::Source => COMMAND_GET_SOURCE_INFO,
+        };
+        let mut reply = self.request(command,|request| {
+            request.u32(NO_INDEX).string(Some(name));
+        })?;
+        Device::read(&mut reply)
+    }
+
+    pub fn streams(&mut self, kind: StreamKind) -> Result<Vec<Stream>, PulseError> {
+        let command = match kind {
+            StreamKind::Playback => COMMAND_GET_SINK_INPUT_INFO_LIST,
+            StreamKind::Record => COMMAND_GET_SOURCE_OUTPUT_INFO_LIST,
+        };
+        let mut reply = self.request(command,|_| {})?;
+
+        let mut streams = Vec::new();
+        while !reply.is_empty() {
+            streams.push(Stream::read(&mut reply,kind)?);
+        }
+        Ok(streams)
+    }
+
+    pub fn state(&mut self) -> Result<AudioState, PulseError> {
+        let (sink,source) = self.defaults()?;
+
+        let mut streams = self.streams(StreamKind::Playback)?;
+        streams.extend(self.streams(StreamKind::Record)?);
+
+        Ok(AudioState {
+            sink: sink.map(|name| self.device(DeviceKind::Sink,&name)).transpose()?,
+            source: source.map(|name| self.device(DeviceKind::Source,&name)).transpose()?,
+            streams,
+        })
+    }
+
+    pub fn set_volume(&mut self, kind: DeviceKind, index: u32, volume: &[u32]) -> Result<(), PulseError> {
+        let command = match kind {
+            DeviceKind::Sink => COMMAND_SET_SINK_VOLUME,
+            DeviceKind::Source => COMMAND_SET_SOURCE_VOLUME,
+        };
+        self.request(command,|request| {
+            request.u32(index).string(None).cvolume(volume);
+        })?;
+        Ok(())
+    }
+
+    pub fn set_mute(&mut self, kind: DeviceKind, index: u32, muted: bool) -> Result<(), PulseError> {
+        let command = match kind {
+            DeviceKind::Sink => COMMAND_SET_SINK_MUTE,
+            DeviceKind::Source => COMMAND_SET_SOURCE_MUTE,
+        };
+        self.request(command,|request| {
+            request.u32(index).string(None).boolean(muted);
+        })?;
+        Ok(())
+    }
+
+    // streams are only ever addressed by index, there is no name to send
+    pub fn set_stream_volume(&mut self, kind: StreamKind, index: u32, volume: &[u32]) -> Result<(), PulseError> {
+        let command = match kind {
+            StreamKind::Playback => COMMAND_SET_SINK_INPUT_VOLUME,
+            StreamKind::Record => COMMAND_SET_SOURCE_OUTPUT_VOLUME,
+        };
+        self.request(command,|request| {
+            request.u32(index).cvolume(volume);
+        })?;
+        Ok(())
+    }
+
+    pub fn set_stream_mute(&mut self, kind: StreamKind, index: u32, muted: bool) -> Result<(), PulseError> {
+        let command = match kind {
+            StreamKind::Playback => COMMAND_SET_SINK_INPUT_MUTE,
+            StreamKind::Record => COMMAND_SET_SOURCE_OUTPUT_MUTE,
+        };
+        self.request(command,|request| {
+            request.u32(index).boolean(muted);
+        })?;
+        Ok(())
+    }
+}
+
+pub type SubscriptionId = usize;
+
+type Subscriber = Rc<dyn Fn(&Result<AudioState,String>)>;
+
+// subscribers hold widgets so they only ever live on the main thread
+thread_local! {
+    static SUBSCRIBERS: RefCell<Vec<(SubscriptionId,Subscriber)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<SubscriptionId> = const { Cell::new(0) };
+    static LAST_STATE: RefCell<Option<Result<AudioState,String>>> = const { RefCell::new(None) };
+    static LISTENING: Cell<bool> = const { Cell::new(false) };
+}
+
+/* call `f` on the main thread with the audio state whenever it changes,
+ * right away if it is already known, Err while the server can not be reached
+ * the listener is started by the first subscriber
+ */
+pub fn subscribe<F: Fn(&Result<AudioState,String>) + 'static>(f: F) -> SubscriptionId {
+    if !LISTENING.replace(true) {
+        listen();
+    }
+
+    LAST_STATE.with_borrow(|state| {
+        if let Some(state) = state {
+            f(state);
+        }
+    });
+
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    SUBSCRIBERS.with(|subs| subs.borrow_mut().push((id,Rc::new(f))));
+    id
+}
+
+pub fn unsubscribe(id: SubscriptionId) {
+    SUBSCRIBERS.with(|subs| subs.borrow_mut().retain(|(sub_id,_)| *sub_id != id));
+}
+
+fn dispatch(state: Result<AudioState,String>) {
+    // copy out so subscribers are free to (un)subscribe while handling
+    let subs: Vec<Subscriber> = SUBSCRIBERS.with(|subs| {
+        subs.borrow().iter().map(|(_,f)| f.clone()).collect()
+    });
+
+    for f in subs {
+        f(&state);
+    }
+    LAST_STATE.set(Some(state));
+}
+
+// the error that ended the connection, and whether it got as far as subscribing
+fn watch(ctx: &glib::MainContext) -> (PulseError,bool) {
+    let mut connection = match Connection::connect() {
+        Ok(connection) => connection,
+        Err(err) => return (err,false)
+    };
+
+    if let Err(err) = connection.subscribe() {
+        return (err,false)
+    }
+
+    loop {
+        match connection.state() {
+            Ok(state) => ctx.invoke(move || dispatch(Ok(state))),
+            Err(err) => return (err,true)
+        }
+
+        // the state is read again as a whole, events only say what changed
+        if let Err(err) = connection.next_event() {
+            return (err,true)
+        }
+        // a slider being dragged sends an event per step, they all come down to one read
+        if let Err(err) = connection.skip_events() {
+            return (err,true)
+        }
+    }
+}
+
+/* keep one subscribed connection open and hand the default sink and source
+ * to the main loop every time the server reports a change
+ * if the server goes away keep retrying with backoff until it is back
+ */
+fn listen() {
+    thread::spawn(|| {
+        let ctx = glib::MainContext::default();
+        let mut backoff = RECONNECT_MIN;
+        let mut reported = false;
+
+        loop {
+            let (err,connected) = watch(&ctx);
+            if connected {
+                backoff = RECONNECT_MIN;
+                reported = false;
+            }
+
+            // only report once per disconnect, not on every retry
+            if !reported {
+                eprintln!("epic-bar: {err}, reconnecting");
+                reported = true;
+                let reason = err.to_string();
+                ctx.invoke(move || dispatch(Err(reason)));
+            }
+
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    });
+}
+
+type Change = Box<dyn FnOnce(&mut Connection) -> Result<(), PulseError> + Send>;
+
+static CHANGES: OnceLock<mpsc::Sender<Change>> = OnceLock::new();
+
+/* run changes in order on one connection off the main thread,
+ * dragging a slider sends many and the last one has to win
+ * the connection is opened on first use and again after a failure
+ * the listener picks up the result through the subscription
+ */
+pub fn change<F>(f: F)
+where
+    F: FnOnce(&mut Connection) -> Result<(), PulseError> + Send + 'static {
+
+    let changes = CHANGES.get_or_init(|| {
+        let (sender,receiver) = mpsc::channel::<Change>();
+
+        thread::spawn(move || {
+            let mut connection = None;
+
+            for change in receiver {
+                let result = match connection.as_mut() {
+                    Some(connection) => change(connection),
+                    None => Connection::connect().and_then(|new| change(connection.insert(new))),
+                };
+                if let Err(err) = result {
+                    eprintln!("epic-bar: could not change audio: {err}");
+                    connection = None;
+                }
+            }
+        });
+
+        sender
+    });
+
+    // only fails if the worker is gone, which it never is
+    let _ = changes.send(Box::new(f));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        os::unix::net::UnixListener,
+        process,
+        sync::{Arc,Mutex},
+    };
+
+    const PA_ERR_NOENTITY: u32 = 5;
+    const SUBSCRIPTION_EVENT_CHANGE: u32 = 0x10;
+    const FACILITY_SINK: u32 = 0;
+    const FACILITY_SOURCE: u32 = 1;
+    const FACILITY_SINK_INPUT: u32 = 2;
+    const FACILITY_SOURCE_OUTPUT: u32 = 3;
+
+    /* a server speaking the subset of the protocol the bar uses,
+     * replies carry every field a version 32 server sends so trailing fields are skipped
+     */
+    struct FakeServer {
+        sink: Device,
+        source: Device,
+        streams: Vec<Stream>,
+        subscribers: Vec<UnixStream>,
+    }
+
+    fn device(index: u32, name: &str, volume: Vec<u32>) -> Device {
+        Device {
+            index,
+            name: name.to_string(),
+            description: format!("{name} description"),
+            volume,
+            muted: false,
+        }
+    }
+
+    fn write_device(reply: &mut TagWriter, device: &Device) {
+        reply.u32(device.index)
+            .string(Some(&device.name))
+            .string(Some(&device.description))
+            // s16le, 2 channels, 44100 Hz
+            .raw(&[TAG_SAMPLE_SPEC,3,2]).raw(&44100u32.to_be_bytes())
+            .raw(&[TAG_CHANNEL_MAP,2,1,2])
+            .u32(6)
+            .cvolume(&device.volume)
+            .boolean(device.muted)
+            .u32(NO_INDEX)
+            .string(None)
+            .raw(b"U").raw(&0u64.to_be_bytes())
+            .string(Some("module-alsa-card.c"))
+            .u32(0)
+            .proplist(&[("device.class","sound")])
+            .raw(b"U").raw(&0u64.to_be_bytes())
+            .raw(b"V").raw(&VOLUME_NORM.to_be_bytes())
+            .u32(0);
+    }
+
+    fn stream(kind: StreamKind, index: u32, name: &str, corked: bool) -> Stream {
+        Stream {
+            kind,
+            index,
+            name: name.to_string(),
+            volume: vec![VOLUME_NORM,VOLUME_NORM],
+            muted: false,
+            corked,
+            has_volume: true,
+        }
+    }
+
+    // sink inputs and source outputs carry the same fields in a different order
+    fn write_stream(reply: &mut TagWriter, stream: &Stream) {
+        let playback = stream.kind == StreamKind::Playback;
+
+        reply.u32(stream.index)
+            .string(Some("media"))
+            .u32(NO_INDEX)
+            .u32(12)
+            .u32(3)
+            .raw(&[TAG_SAMPLE_SPEC,3,2]).raw(&48000u32.to_be_bytes())
+            .raw(&[TAG_CHANNEL_MAP,2,1,2]);
+        if playback {
+            reply.cvolume(&stream.volume);
+        }
+        reply.raw(&[TAG_USEC]).raw(&1500u64.to_be_bytes())
+            .raw(&[TAG_USEC]).raw(&0u64.to_be_bytes())
+            .string(Some("speex-float-1"))
+            .string(Some("protocol-native.c"));
+        if playback {
+            reply.boolean(stream.muted);
+        }
+        reply.proplist(&[("application.name",&stream.name),("application.process.id","42")])
+            .boolean(stream.corked);
+        if !playback {
+            reply.cvolume(&stream.volume).boolean(stream.muted);
+        }
+        reply.boolean(stream.has_volume)
+            .boolean(true)
+            // pcm format without properties
+            .raw(&[TAG_FORMAT_INFO,TAG_U8,1])
+            .proplist(&[]);
+    }
+
+    fn handle(server: &Mutex<FakeServer>, stream: &mut UnixStream) -> Result<(), PulseError> {
+        let (_,payload) = read_packet(stream)?;
+        let mut request = TagReader::new(payload);
+        let (command,tag) = (request.u32()?,request.u32()?);
+
+        let mut reply = TagWriter::default();
+        reply.u32(COMMAND_REPLY).u32(tag);
+        let mut event: Option<(u32,u32)> = None;
+
+        let mut server = server.lock().unwrap();
+        match command {
+            COMMAND_AUTH => { reply.u32(PROTOCOL_VERSION); },
+            COMMAND_SET_CLIENT_NAME => { reply.u32(7); },
+            COMMAND_GET_SERVER_INFO => {
+                reply.string(Some("pulseaudio"))
+                    .string(Some("16.1"))
+                    .string(Some("user"))
+                    .string(Some("host"))
+                    .raw(&[TAG_SAMPLE_SPEC,3,2]).raw(&44100u32.to_be_bytes())
+                    .string(Some(&server.sink.name))
+                    .string(Some(&server.source.name))
+                    .u32(0)
+                    .raw(&[TAG_CHANNEL_MAP,2,1,2]);
+            },
+            COMMAND_GET_SINK_INFO | COMMAND_GET_SOURCE_INFO => {
+                request.u32()?;
+                let name = request.string()?;
+                let device = if command == COMMAND_GET_SINK_INFO { &server.sink } else { &server.source };
+
+                if name.as_deref() == Some(device.name.as_str()) {
+                    write_device(&mut reply,device);
+                } else {
+                    reply = TagWriter::default();
+                    reply.u32(COMMAND_ERROR).u32(tag).u32(PA_ERR_NOENTITY);
+                }
+            },
+            COMMAND_SET_SINK_VOLUME | COMMAND_SET_SINK_MUTE => {
+                request.u32()?;
+                request.string()?;
+                if command == COMMAND_SET_SINK_VOLUME {
+                    server.sink.volume = request.cvolume()?;
+                } else {
+                    server.sink.muted = request.boolean()?;
+                }
+                event = Some((FACILITY_SINK,server.sink.index));
+            },
+            COMMAND_SET_SOURCE_MUTE => {
+                request.u32()?;
+                request.string()?;
+                server.source.muted = request.boolean()?;
+                event = Some((FACILITY_SOURCE,server.source.index));
+            },
+            COMMAND_GET_SINK_INPUT_INFO_LIST | COMMAND_GET_SOURCE_OUTPUT_INFO_LIST => {
+                let kind = if command == COMMAND_GET_SINK_INPUT_INFO_LIST { StreamKind::Playback } else { StreamKind::Record };
+                for stream in server.streams.iter().filter(|stream| stream.kind == kind) {
+                    write_stream(&mut reply,stream);
+                }
+            },
+            COMMAND_SET_SINK_INPUT_VOLUME
+            | COMMAND_SET_SOURCE_OUTPUT_VOLUME
+            | COMMAND_SET_SINK_INPUT_MUTE
+            | COMMAND_SET_SOURCE_OUTPUT_MUTE => {
+                let kind = match command {
+                    COMMAND_SET_SINK_INPUT_VOLUME | COMMAND_SET_SINK_INPUT_MUTE => StreamKind::Playback,
+                    _ => StreamKind::Record,
+                };
+                let index = request.u32()?;
+
+                match server.streams.iter_mut().find(|stream| stream.kind == kind && stream.index == index) {
+                    Some(stream) => {
+                        if command == COMMAND_SET_SINK_INPUT_VOLUME || command == COMMAND_SET_SOURCE_OUTPUT_VOLUME {
+                            stream.volume = request.cvolume()?;
+                        } else {
+                            stream.muted = request.boolean()?;
+                        }
+                        let facility = match kind {
+                            StreamKind::Playback => FACILITY_SINK_INPUT,
+                            StreamKind::Record => FACILITY_SOURCE_OUTPUT,
+                        };
+                        event = Some((facility,index));
+                    },
+                    None => {
+                        reply = TagWriter::default();
+                        reply.u32(COMMAND_ERROR).u32(tag).u32(PA_ERR_NOENTITY);
+                    }
+                }
+            },
+            COMMAND_SUBSCRIBE => server.subscribers.push(stream.try_clone()?),
+            _ => {
+                reply = TagWriter::default();
+                reply.u32(COMMAND_ERROR).u32(tag).u32(PA_ERR_NOENTITY);
+            }
+        }
+        write_packet(stream,&reply.buf)?;
+
+        if let Some((facility,index)) = event {
+            for subscriber in server.subscribers.iter_mut() {
+                let mut packet = TagWriter::default();
+                packet.u32(COMMAND_SUBSCRIBE_EVENT)
+                    .u32(NO_INDEX)
+                    .u32(SUBSCRIPTION_EVENT_CHANGE | facility)
+                    .u32(index);
+                write_packet(subscriber,&packet.buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    // the socket of a fake server, removed once the test is done with it
+    struct ServerSocket {
+        path: PathBuf,
+    }
+
+    impl Drop for ServerSocket {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn fake_server(name: &str) -> ServerSocket {
+        let path = env::temp_dir().join(format!("epic-bar-{}-{name}",process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = Arc::new(Mutex::new(FakeServer {
+            sink: device(3,"alsa_output.pci-0000_00_1f.3.analog-stereo",vec![VOLUME_NORM / 2,VOLUME_NORM / 4]),
+            source: device(4,"alsa_input.pci-0000_00_1f.3.analog-stereo",vec![VOLUME_NORM]),
+            streams: vec![
+                stream(StreamKind::Playback,20,"Firefox",false),
+                stream(StreamKind::Playback,21,"mpv",true),
+                stream(StreamKind::Record,30,"Firefox",false),
+                stream(StreamKind::Record,31,"OBS Studio",true),
+            ],
+            subscribers: Vec::new(),
+        }));
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (server,mut stream) = (server.clone(),stream.unwrap());
+                thread::spawn(move || while handle(&server,&mut stream).is_ok() {});
+            }
+        });
+
+        ServerSocket { path }
+    }
+
+    #[test]
+    fn default_devices() {
+        let server = fake_server("devices");
+        let mut connection = Connection::connect_to(&server.path).unwrap();
+
+        let state = connection.state().unwrap();
+        let sink = state.sink.unwrap();
+        assert_eq!(sink.index, 3);
+        assert_eq!(sink.description, "alsa_output.pci-0000_00_1f.3.analog-stereo description");
+        assert_eq!(sink.percent(), 50);
+        assert!(!sink.muted);
+        assert_eq!(state.source.unwrap().percent(), 100);
+
+        assert!(matches!(
+            connection.device(DeviceKind::Sink,"missing"),
+            Err(PulseError::Server(PA_ERR_NOENTITY))));
+    }
+
+    #[test]
+    fn changes_are_reported_to_subscribers() {
+        let server = fake_server("subscribe");
+        let mut listener = Connection::connect_to(&server.path).unwrap();
+        listener.subscribe().unwrap();
+
+        let mut control = Connection::connect_to(&server.path).unwrap();
+        let sink = control.state().unwrap().sink.unwrap();
+        control.set_volume(DeviceKind::Sink,sink.index,&sink.volume_at(60)).unwrap();
+        control.set_mute(DeviceKind::Sink,sink.index,true).unwrap();
+
+        assert_eq!(listener.next_event().unwrap(), (SUBSCRIPTION_EVENT_CHANGE | FACILITY_SINK,3));
+        assert_eq!(listener.next_event().unwrap(), (SUBSCRIPTION_EVENT_CHANGE | FACILITY_SINK,3));
+
+        let sink = listener.state().unwrap().sink.unwrap();
+        assert_eq!(sink.percent(), 60);
+        // balance is kept
+        assert_eq!(sink.volume[1] * 2, sink.volume[0] - sink.volume[0] % 2);
+        assert!(sink.muted);
+    }
+
+    #[test]
+    fn bursts_of_events_are_skipped() {
+        let server = fake_server("skip");
+        let mut listener = Connection::connect_to(&server.path).unwrap();
+        listener.subscribe().unwrap();
+
+        let mut control = Connection::connect_to(&server.path).unwrap();
+        for percent in [10,20,30,40] {
+            control.set_stream_volume(StreamKind::Playback,20,&[VOLUME_NORM * percent / 100;2]).unwrap();
+        }
+
+        // requests are handled in order, once this is answered every event has been sent
+        control.state().unwrap();
+
+        listener.next_event().unwrap();
+        listener.skip_events().unwrap();
+        assert!(listener.events.is_empty());
+        assert!(!listener.readable().unwrap());
+        assert_eq!(listener.state().unwrap().streams[0].percent(), 40);
+    }
+
+    #[test]
+    fn streams() {
+        let server = fake_server("streams");
+        let mut connection = Connection::connect_to(&server.path).unwrap();
+
+        let state = connection.state().unwrap();
+        let names: Vec<_> = state.streams.iter()
+            .map(|stream| (stream.kind,stream.index,stream.name.as_str()))
+            .collect();
+        assert_eq!(names, [
+            (StreamKind::Playback,20,"Firefox"),
+            (StreamKind::Playback,21,"mpv"),
+            (StreamKind::Record,30,"Firefox"),
+            (StreamKind::Record,31,"OBS Studio"),
+        ]);
+        assert!(state.streams[1].corked);
+        assert_eq!(state.streams[2].percent(), 100);
+
+        // the paused recording does not count as capturing
+        let capturing: Vec<_> = state.capturing().map(|stream| stream.index).collect();
+        assert_eq!(capturing, [30]);
+    }
+
+    #[test]
+    fn stream_changes() {
+        let server = fake_server("stream-changes");
+        let mut listener = Connection::connect_to(&server.path).unwrap();
+        listener.subscribe().unwrap();
+
+        let mut control = Connection::connect_to(&server.path).unwrap();
+        control.set_stream_volume(StreamKind::Playback,20,&[VOLUME_NORM / 4,VOLUME_NORM / 4]).unwrap();
+        control.set_stream_mute(StreamKind::Record,30,true).unwrap();
+        assert!(matches!(
+            control.set_stream_mute(StreamKind::Record,20,true),
+            Err(PulseError::Server(PA_ERR_NOENTITY))));
+
+        assert_eq!(listener.next_event().unwrap(), (SUBSCRIPTION_EVENT_CHANGE | FACILITY_SINK_INPUT,20));
+        assert_eq!(listener.next_event().unwrap(), (SUBSCRIPTION_EVENT_CHANGE | FACILITY_SOURCE_OUTPUT,30));
+
+        let state = listener.state().unwrap();
+        assert_eq!(state.streams[0].percent(), 25);
+        assert!(state.streams[2].muted);
+    }
+
+    #[test]
+    fn volume_steps() {
+        let sink = device(0,"sink",vec![VOLUME_NORM / 2,VOLUME_NORM / 2]);
+
+        assert_eq!(sink.volume_at(100), vec![VOLUME_NORM,VOLUME_NORM]);
+        assert_eq!(device(0,"sink",vec![0,0]).volume_at(5), vec![VOLUME_NORM / 20;2]);
+        assert_eq!(device(0,"sink",vec![0x9999]).percent(), 60);
+    }
+}
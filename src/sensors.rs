@@ -0,0 +1,270 @@
+use std::{
+    fs,
+    path::{Path,PathBuf},
+};
+
+use crate::{
+    power::read,
+    system::SystemSource,
+};
+
+pub const HWMON: &str = "/sys/class/hwmon";
+pub const THERMAL: &str = "/sys/class/thermal";
+
+// without a max the warning starts this far below crit, in °C
+const WARNING_MARGIN: f64 = 10.0;
+
+// the sensor shown when the config does not pick one, cpu package sensors first
+const PREFERRED: [(&str,Option<&str>);6] = [
+    ("k10temp", Some("Tctl")),
+    ("zenpower", Some("Tdie")),
+    ("coretemp", Some("Package id 0")),
+    ("cpu_thermal", None),
+    ("x86_pkg_temp", None),
+    ("acpitz", None),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/* Temperature: one temperature channel, in °C
+ *
+ * hwmon chips name themselves (`k10temp`, `coretemp`, `nvme`) and label their
+ * channels where the driver knows what they measure, `temp1` otherwise
+ * thermal zones use their type as the chip and the zone as the label
+ *  max: where the hardware starts to worry, `_max` or a hot/passive trip point
+ *  crit: where it shuts down, `_crit` or the critical trip point
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Temperature {
+    pub chip: String,
+    pub label: String,
+    pub celsius: f64,
+    pub max: Option<f64>,
+    pub crit: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fan {
+    pub chip: String,
+    pub label: String,
+    pub rpm: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Sensors {
+    pub temperatures: Vec<Temperature>,
+    pub fans: Vec<Fan>,
+}
+
+// sysfs temperatures are in millidegrees, some drivers leave unset limits at 0
+fn millidegrees(dir: &Path, key: &str) -> Option<f64> {
+    read(dir,key)?.parse::<f64>().ok().map(|millis| millis / 1000.0)
+}
+
+fn threshold(dir: &Path, key: &str) -> Option<f64> {
+    millidegrees(dir,key).filter(|celsius| *celsius > 0.0)
+}
+
+// directory entries under `root` starting with `prefix`, sorted by name
+fn entries(root: &Path, prefix: &str) -> Vec<(String,PathBuf)> {
+    let mut entries: Vec<(String,PathBuf)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(),entry.path()))
+        .filter(|(name,_)| name.starts_with(prefix))
+        .collect();
+    entries.sort();
+    entries
+}
+
+// numbers of the `{kind}N_input` files, temp1_input and so on
+fn channels(dir: &Path, kind: &str) -> Vec<u32> {
+    let mut channels: Vec<u32> = entries(dir,kind).iter()
+        .filter_map(|(name,_)| name.strip_prefix(kind)?.strip_suffix("_input")?.parse().ok())
+        .collect();
+    channels.sort();
+    channels
+}
+
+impl Temperature {
+    pub fn level(&self) -> Level {
+        let warning = self.max.or(self.crit.map(|crit| crit - WARNING_MARGIN));
+
+        match (self.crit,warning) {
+            (Some(crit),_) if self.celsius >= crit => Level::Critical,
+            (_,Some(warning)) if self.celsius >= warning => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{} {}",self.chip,self.label)
+    }
+}
+
+fn scan_hwmon(root: &Path, sensors: &mut Sensors) {
+    for (_,path) in entries(root,"hwmon") {
+        // kernels before 3.x kept the attributes on the parent device
+        let dir = if path.join("name").exists() { path } else { path.join("device") };
+        let Some(chip) = read(&dir,"name") else {
+            continue
+        };
+
+        for n in channels(&dir,"temp") {
+            // sleeping devices (disks, dgpus) fail the read instead of reporting
+            let Some(celsius) = millidegrees(&dir,&format!("temp{n}_input")) else {
+                continue
+            };
+            sensors.temperatures.push(Temperature {
+                chip: chip.clone(),
+                label: read(&dir,&format!("temp{n}_label")).unwrap_or_else(|| format!("temp{n}")),
+                celsius,
+                max: threshold(&dir,&format!("temp{n}_max")),
+                crit: threshold(&dir,&format!("temp{n}_crit")),
+            });
+        }
+
+        for n in channels(&dir,"fan") {
+            let Some(rpm) = read(&dir,&format!("fan{n}_input")).and_then(|rpm| rpm.parse().ok()) else {
+                continue
+            };
+            sensors.fans.push(Fan {
+                chip: chip.clone(),
+                label: read(&dir,&format!("fan{n}_label")).unwrap_or_else(|| format!("fan{n}")),
+                rpm,
+            });
+        }
+    }
+}
+
+fn scan_thermal(root: &Path, sensors: &mut Sensors) {
+    for (zone,dir) in entries(root,"thermal_zone") {
+        let (Some(chip),Some(celsius)) = (read(&dir,"type"),millidegrees(&dir,"temp")) else {
+            continue
+        };
+
+        let mut max: Option<f64> = None;
+        let mut crit = None;
+        for n in 0.. {
+            let Some(kind) = read(&dir,&format!("trip_point_{n}_type")) else {
+                break
+            };
+            let Some(temp) = threshold(&dir,&format!("trip_point_{n}_temp")) else {
+                continue
+            };
+            match kind.as_str() {
+                "critical" => crit = Some(temp),
+                "hot" | "passive" => max = Some(max.map_or(temp,|max| max.min(temp))),
+                _ => {}
+            }
+        }
+
+        sensors.temperatures.push(Temperature { chip, label: zone, celsius, max, crit });
+    }
+}
+
+// every hwmon chip and thermal zone, either missing is not an error
+pub fn sensors(source: &SystemSource) -> Sensors {
+    let mut sensors = Sensors::default();
+    scan_hwmon(&source.path(HWMON),&mut sensors);
+    scan_thermal(&source.path(THERMAL),&mut sensors);
+    sensors
+}
+
+impl Sensors {
+    /* `chip label` as in `k10temp Tctl` or `coretemp Package id 0`,
+     * only `chip` picks its first channel
+     */
+    pub fn find(&self, name: &str) -> Option<&Temperature> {
+        let (chip,label) = match name.trim().split_once(char::is_whitespace) {
+            Some((chip,label)) => (chip,Some(label.trim())),
+            None => (name.trim(),None),
+        };
+
+        self.temperatures.iter()
+            .find(|temp| temp.chip == chip && label.is_none_or(|label| temp.label == label))
+    }
+
+    // the cpu package where a known sensor has it, the first temperature otherwise
+    pub fn preferred(&self) -> Option<&Temperature> {
+        PREFERRED.iter()
+            .find_map(|(chip,label)| match label {
+                Some(label) => self.find(&format!("{chip} {label}")),
+                None => self.find(chip),
+            })
+            .or(self.temperatures.first())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // intel laptop: coretemp, a thinkpad fan and acpi thermal zones
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    // ryzen desktop: k10temp, a super io chip with fans, a gpu and a sleeping disk
+    const DESKTOP: &str = "tests/fixtures/system/desktop-amd";
+
+    #[test]
+    fn laptop_sensors() {
+        let sensors = sensors(&SystemSource::new(LAPTOP));
+
+        let package = sensors.preferred().unwrap();
+        assert_eq!(package.name(), "coretemp Package id 0");
+        assert_eq!((package.celsius,package.max,package.crit), (62.0,Some(100.0),Some(100.0)));
+        assert_eq!(package.level(), Level::Normal);
+
+        // channels in number order, temp10 after temp2
+        let cores: Vec<&str> = sensors.temperatures.iter()
+            .filter(|temp| temp.chip == "coretemp")
+            .map(|temp| temp.label.as_str())
+            .collect();
+        assert_eq!(cores, ["Package id 0", "Core 0", "Core 4"]);
+
+        // acpitz is both a hwmon chip and a thermal zone, hwmon comes first
+        assert_eq!(sensors.find("acpitz").unwrap().label, "temp1");
+        // trip points, the lowest passive one is where it starts to throttle
+        let zone = sensors.find("acpitz thermal_zone0").unwrap();
+        assert_eq!((zone.max,zone.crit), (Some(90.0),Some(128.0)));
+        assert_eq!(sensors.find("x86_pkg_temp").unwrap().level(), Level::Warning);
+
+        assert_eq!(sensors.fans, [Fan { chip: "thinkpad".to_string(), label: "fan1".to_string(), rpm: 2712 }]);
+    }
+
+    #[test]
+    fn desktop_sensors() {
+        let sensors = sensors(&SystemSource::new(DESKTOP));
+
+        assert_eq!(sensors.preferred().unwrap().name(), "k10temp Tctl");
+        assert_eq!(sensors.find("k10temp Tccd1").unwrap().celsius, 71.25);
+        assert!(sensors.find("k10temp Tccd9").is_none());
+
+        // only crit: warning from 10 °C below it
+        let gpu = sensors.find("amdgpu edge").unwrap();
+        assert_eq!((gpu.max,gpu.crit), (None,Some(100.0)));
+        assert_eq!(gpu.level(), Level::Warning);
+        assert_eq!(sensors.find("amdgpu junction").unwrap().level(), Level::Critical);
+
+        // the sleeping drive has no reading
+        assert!(sensors.find("drivetemp").is_none());
+
+        let fans: Vec<(&str,u32)> = sensors.fans.iter()
+            .filter(|fan| fan.chip == "nct6798")
+            .map(|fan| (fan.label.as_str(),fan.rpm))
+            .collect();
+        assert_eq!(fans, [("fan1",1187), ("fan2",842), ("fan7",0)]);
+    }
+
+    #[test]
+    fn no_sensors() {
+        let sensors = sensors(&SystemSource::new("tests/fixtures/system/missing"));
+        assert!(sensors.temperatures.is_empty());
+        assert!(sensors.preferred().is_none());
+    }
+}
@@ -1,38 +1,97 @@
 use std::{
-    fs::File,
-    io::Read,
-    thread,
+    collections::VecDeque,
+    error,
     fmt,
+    io,
+    path::PathBuf,
 };
 
-use time::{OffsetDateTime,format_description};
+use time::{
+    format_description::{
+        self,
+        OwnedFormatItem,
+    },
+    OffsetDateTime,
+};
+
+use crate::{
+    netlink::NetlinkError,
+    power::{
+        self,
+        PowerSupplies,
+        Status,
+    },
+    system::SystemSource,
+    timezone::Zone,
+};
 
 
-const BATTERY_PERCENTAGE: &str = "/sys/class/power_supply/BAT1/capacity";
-const BATTERY_STATUS: &str = "/sys/class/power_supply/BAT1/status";
-const BATTERY_CHARGE: &str = "/sys/class/power_supply/BAT1/charge_now";
-const BATTERY_CHARGE_FULL: &str = "/sys/class/power_supply/BAT1/charge_full";
-const BATTERY_CURRENT: &str = "/sys/class/power_supply/BAT1/current_now";
 
-const MEMORY_INFO: &str = "/proc/meminfo";
+// `time` format descriptions, https://time-rs.github.io/book/api/format-description.html
+pub const DATE_FORMAT: &str = "[year]/[month]/[day]";
+pub const TIME_FORMAT: &str = "[hour]:[minute]:[second]";
+pub const TIME_FORMAT_12H: &str = "[hour repr:12 padding:none]:[minute]:[second] [period]";
 
 const CPU_STAT: &str = "/proc/stat";
-// ex. path: /sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq
-const CPU_COUNT: usize = 16;
-const CPU_FREQ_PREF: &str = "/sys/devices/system/cpu/cpu"; //followed by cpu number
-const CPU_FREQ_POST: &str = "/cpufreq/scaling_cur_freq";
+// samples kept for the history graph, one a second
+const CPU_HISTORY: usize = 60;
+// a core this busy for a while is most likely one thread that will not finish
+const PINNED_LOAD: f32 = 95.0;
+
+/* StatusError: why a reading could not be taken
+ * widgets show a fallback value with this as the tooltip and keep updating
+ */
+#[derive(Debug)]
+pub enum StatusError {
+    Io(PathBuf, io::Error),
+    // the file was read but is not laid out as expected
+    Parse(PathBuf, &'static str),
+    // nothing by this name, a sensor that went away for example
+    Missing(String),
+    Clock(String),
+    // links and addresses are asked of the kernel rather than read from a file
+    Netlink(NetlinkError),
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusError::Io(path,err) => write!(f,"could not read {}: {err}",path.display()),
+            StatusError::Parse(path,what) => write!(f,"could not parse {}: {what}",path.display()),
+            StatusError::Missing(what) => write!(f,"could not find {what}"),
+            StatusError::Clock(err) => write!(f,"could not format the time: {err}"),
+            StatusError::Netlink(err) => write!(f,"{err}"),
+        }
+    }
+}
+
+impl error::Error for StatusError {}
+
+impl From<NetlinkError> for StatusError {
+    fn from(err: NetlinkError) -> Self {
+        StatusError::Netlink(err)
+    }
+}
+
+pub(crate) fn read(source: &SystemSource, path: &str) -> Result<String, StatusError> {
+    source.read(path).map_err(|err| StatusError::Io(source.path(path),err))
+}
+
+pub(crate) fn parse_error(source: &SystemSource, path: &str, what: &'static str) -> StatusError {
+    StatusError::Parse(source.path(path),what)
+}
 
 pub struct DateTime {
     pub date: String,
     pub time: String
 }
 
-#[derive(PartialEq)]
-enum BatteryStatus {
-    Discharging,
-    Charging,
-    NotCharging,
-    Error
+/* ClockFormat: the date and time lines of the clock
+ * parsed once rather than on every tick, either may use any part of the date and time
+ */
+pub struct ClockFormat {
+    pub date: OwnedFormatItem,
+    pub time: OwnedFormatItem,
 }
 
 pub struct Battery {
@@ -41,7 +100,7 @@ pub struct Battery {
     pub tooltip_text: String, // formatted with remaining
     remaining: String, 
 
-    status: BatteryStatus
+    status: Status
 }
 
 impl Default for Battery {
@@ -51,173 +110,120 @@ impl Default for Battery {
             icon: String::new(),
             remaining: String::new(),
             tooltip_text: String::new(),
-            status: BatteryStatus::Error
+            status: Status::Unknown
         }
     }
 }
 
-pub struct Memory {
-    pub string: String
+/* CpuTimes: one `cpu` line of /proc/stat, in USER_HZ ticks since boot
+ *
+ * cpu user nice system idle iowait irq softirq steal guest guest_nice
+ * idle and iowait are idle, the rest busy: steal is time the hypervisor
+ * took from us, guest and guest_nice are already counted in user and nice
+ * older kernels leave off the trailing fields
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    idle: u64,
 }
 
-#[derive(Clone, Copy)]
-pub struct Cpu {
-    total_load1: u32,
-    non_idle_load1: u32,
-    total_load2: u32,
-    non_idle_load2: u32,
+impl CpuTimes {
+    // (cpu number, None for the aggregate line) and its times
+    fn parse(line: &str) -> Option<(Option<u32>,CpuTimes)> {
+        let mut fields = line.split_whitespace();
+        let core = match fields.next()?.strip_prefix("cpu")? {
+            "" => None,
+            number => Some(number.parse().ok()?),
+        };
+
+        let values: Vec<u64> = fields.take(8)
+            .map(|field| field.parse())
+            .collect::<Result<_,_>>()
+            .ok()?;
+        if values.len() < 4 {
+            return None
+        }
+        let field = |n: usize| values.get(n).copied().unwrap_or(0);
 
-    pub avg_load:f32
-}
+        Some((core,CpuTimes {
+            busy: field(0) + field(1) + field(2) + field(5) + field(6) + field(7),
+            idle: field(3) + field(4),
+        }))
+    }
 
-pub mod Network {
+    // percent busy since `earlier`, None if no time passed
+    fn load_since(&self, earlier: &CpuTimes) -> Option<f32> {
+        let busy = self.busy.saturating_sub(earlier.busy);
+        let total = busy + self.idle.saturating_sub(earlier.idle);
+        if total == 0 {
+            return None
+        }
+        Some(busy as f32 / total as f32 * 100.0)
+    }
 }
 
-impl Cpu {
-
-    pub fn new() -> Self {
-        let mut file = File::open(CPU_STAT).expect("Error opening /proc/stat");
-        // just need first line of aggregate CPU usage measurements
-        let mut buff = [0;64]; 
-
-        file.read_exact(&mut buff).unwrap();
-
-        let binding =  String::from_utf8_lossy(&buff);
-        let string = binding.lines().nth(0).unwrap().to_string();
-
-        let cpu_totals = string.split_whitespace();
-
-        let mut count = 0;
-        let mut total_t = 0;
-        let mut idle_t = u32::default();
-        // cpu user nice system idle iowait irq softirq ... ... ... 
-        //      ^    ^     ^     *     ^     ^     ^                
-        //  0   1    2     3     4     5     6     7                
-        // add idle seperately
-        for field in cpu_totals {
-            match count {
-                0 => {
-                    count += 1;
-                    continue;
-                },
-                4|5 => {
-                    count += 1;
-                    idle_t += field.parse::<u32>().unwrap_or(0);
-                    continue;
-                },
-                8 => break,
-                _ => {
-                    count += 1;
-                }
-            }
+// the aggregate line and one per online core
+#[derive(Clone, Default)]
+struct CpuSample {
+    total: CpuTimes,
+    cores: Vec<(u32,CpuTimes)>,
+}
 
-            total_t += field.parse::<u32>().unwrap_or(0);
-        }
-         // sum non-idle times
-        // total time with idle time
-        // delta between total time AND Non-idle times
-        // ((total_time - non_idle_time) / total_time) * 100 = avg load
-        let total_load = total_t + idle_t;
-        let non_idle_load = total_t;
-
-        Cpu {
-            total_load1: total_load,
-            non_idle_load1: non_idle_load,
-            total_load2: 0,
-            non_idle_load2: 0,
-            avg_load: 0.0
+// the cpu lines come first, the rest of the file is not needed
+fn parse_stat(stat: &str) -> Option<CpuSample> {
+    let mut sample = CpuSample::default();
+    let mut found = false;
+
+    for (core,times) in stat.lines().map_while(CpuTimes::parse) {
+        match core {
+            None => {
+                sample.total = times;
+                found = true;
+            },
+            Some(core) => sample.cores.push((core,times)),
         }
     }
 
-    pub fn get_avg_freq() -> String {
-        // get all paths for CPUs
-        let cpu_freq_paths: Vec<String> = (0..CPU_COUNT)
-            .map(|n| format!("{CPU_FREQ_PREF}{n}{CPU_FREQ_POST}"))
-            .collect();
+    found.then_some(sample)
+}
 
-        let mut handles = Vec::new();
+/* load over the time between two samples of /proc/stat
+ * avg_load: every core together
+ * core_loads: (cpu number, load), cores that went offline in between are left out
+ */
+#[derive(Clone, Default)]
+pub struct Cpu {
+    last: CpuSample,
 
-        // each handle should be the frequency in Hz
-        for path in cpu_freq_paths {
-            let handle = thread::spawn(move || {
-                let mut file = File::open(path).unwrap();
-                let mut buff = String::new();
+    pub avg_load: f32,
+    pub core_loads: Vec<(u32,f32)>,
+}
 
-                let _ = file.read_to_string(&mut buff);
+impl Cpu {
 
-                buff.trim_end().parse::<f32>().unwrap()
-            });
-            handles.push(handle);
-        }
-        
-        let mut total = 0.0f32;
-        for handle in handles {
-            total += handle.join().expect("Thread panicked");
-        }
-    
-        let freq_sum = total/(CPU_COUNT as f32 * 1_000_000.0);
-            if freq_sum < 1.0 {
-                format!("{:.0} MHz",freq_sum*1000.0).to_string()
-            } else {
-                format!("{:.1} GHz",freq_sum).to_string()
-            }
+    // the first load read is the average since boot
+    pub fn new() -> Self {
+        Cpu::default()
     }
 
-    pub fn get_cpu_load(&mut self) -> f32{
-        let mut file = File::open(CPU_STAT).expect("Error opening /proc/stat");
-        // just need first line of aggregate CPU usage measurements
-        let mut buff = [0;64]; 
-
-        file.read_exact(&mut buff).unwrap();
-
-        let binding =  String::from_utf8_lossy(&buff);
-        let string = binding.lines().nth(0).unwrap().to_string();
-
-        let cpu_totals = string.split_whitespace();
-
-        let mut count = 0;
-        let mut total_t = 0;
-        let mut idle_t = u32::default();
-        // cpu user nice system idle iowait irq softirq ... ... ... 
-        //      ^    ^     ^     *     *     ^     ^                
-        //  0   1    2     3     4     5     6     7                
-        // add idle seperately
-        for field in cpu_totals {
-            match count {
-                0 => {
-                    count += 1;
-                    continue;
-                },
-                4|5 => {
-                    count += 1;
-                    idle_t += field.parse::<u32>().unwrap_or(0);
-                    continue;
-                },
-                8 => break,
-                _ => {
-                    count += 1;
-                }
-            }
-
-            total_t += field.parse::<u32>().unwrap_or(0);
+    pub fn get_cpu_load(&mut self, source: &SystemSource) -> Result<f32, StatusError> {
+        let stat = read(source,CPU_STAT)?;
+        let sample = parse_stat(&stat).ok_or_else(|| parse_error(source,CPU_STAT,"no cpu line"))?;
+
+        // read twice within the same tick, keep the last loads
+        if let Some(load) = sample.total.load_since(&self.last.total) {
+            self.avg_load = load;
+            self.core_loads = sample.cores.iter()
+                .filter_map(|(core,times)| {
+                    let (_,earlier) = self.last.cores.iter().find(|(last,_)| last == core)?;
+                    Some((*core,times.load_since(earlier)?))
+                })
+                .collect();
         }
-    
-        // sum non-idle times
-        // total time with idle time
-        // delta between total time AND Non-idle times
-        // ((total_time - non_idle_time) / total_time) * 100 = avg load
-
-        self.total_load2 = self.total_load1;
-        self.non_idle_load2 = self.non_idle_load1;
-
-        self.total_load1 = total_t + idle_t;
-        self.non_idle_load1 = total_t;
 
-        let delta_total = self.total_load1 - self.total_load2;
-        let delta_non_idle = self.non_idle_load1 - self.non_idle_load2;
-
-        self.avg_load = (delta_non_idle as f32/delta_total as f32)*100.0;
-        self.avg_load
+        self.last = sample;
+        Ok(self.avg_load)
     }
 
     pub fn get_cpu_image(&self) -> String {
@@ -233,103 +239,107 @@ impl Cpu {
 
 }
 
-fn get_battery(b: &mut Battery) {
-    let mut file = File::open(BATTERY_PERCENTAGE).unwrap();
-    let mut buff = String::with_capacity(3);
+/* CpuHistory: per-core loads of the last minute, oldest first
+ * only samples with per-core loads are kept, the first read has none
+ */
+#[derive(Default)]
+pub struct CpuHistory {
+    samples: VecDeque<(f32,Vec<(u32,f32)>)>,
+}
+
+impl CpuHistory {
+    pub fn push(&mut self, cpu: &Cpu) {
+        if cpu.core_loads.is_empty() {
+            return
+        }
+        if self.samples.len() == CPU_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((cpu.avg_load,cpu.core_loads.clone()));
+    }
 
-    let _ = file.read_to_string(&mut buff);
+    pub fn capacity(&self) -> usize {
+        CPU_HISTORY
+    }
 
-    b.capacity = buff.trim_end().parse::<u32>().unwrap();
-}
+    // (cpu number, load) of the newest sample
+    pub fn latest(&self) -> &[(u32,f32)] {
+        self.samples.back().map(|(_,cores)| cores.as_slice()).unwrap_or(&[])
+    }
 
-fn get_status(b: &mut Battery) {
-    let mut file = File::open(BATTERY_STATUS).unwrap();
-    let mut buff  = String::with_capacity(16);
+    pub fn averages(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().map(|(avg,_)| *avg)
+    }
 
-    let _ = file.read_to_string(&mut buff);
+    // the busiest core of each sample, one busy thread hardly moves the average on many cores
+    pub fn busiest(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().map(|(_,cores)| cores.iter().map(|(_,load)| *load).fold(0.0,f32::max))
+    }
 
-    b.status = match buff.trim_end() {
-        "Charging" => BatteryStatus::Charging,
-        "Discharging" => BatteryStatus::Discharging,
-        "Not charging"=> BatteryStatus::NotCharging,
-        _ => BatteryStatus::Error
-    };
-        
+    // cores that stayed busy through each of the last `seconds` samples
+    pub fn pinned(&self, seconds: usize) -> Vec<u32> {
+        if seconds == 0 || self.samples.len() < seconds {
+            return Vec::new()
+        }
+
+        let mut recent = self.samples.iter().rev().take(seconds);
+        let Some((_,newest)) = recent.next() else {
+            return Vec::new()
+        };
+        let mut pinned: Vec<u32> = newest.iter()
+            .filter(|(_,load)| *load >= PINNED_LOAD)
+            .map(|(core,_)| *core)
+            .collect();
+
+        for (_,cores) in recent {
+            pinned.retain(|core| cores.iter().any(|(c,load)| c == core && *load >= PINNED_LOAD));
+        }
+        pinned
+    }
 }
 
-fn get_battery_tooltip_text(b: &mut Battery) {
+fn get_battery_tooltip_text(b: &mut Battery, supplies: &PowerSupplies) {
     let status_text = match b.status {
-        BatteryStatus::Charging => "Charging",
-        BatteryStatus::Discharging => "Discharging",
-        BatteryStatus::NotCharging=> "Plugged in, Not Charging",
-        _ => "Error getting state"
+        Status::Charging => "Charging",
+        Status::Discharging => "Discharging",
+        Status::NotCharging => "Plugged in, Not Charging",
+        Status::Full => "Fully charged",
+        Status::Unknown if supplies.on_mains() => "Plugged in",
+        Status::Unknown => "Error getting state"
     };
 
-    b.tooltip_text = format!("{status_text}\n{}",b.remaining);
+    let mut lines = vec![status_text.to_string()];
+    if !b.remaining.is_empty() {
+        lines.push(b.remaining.clone());
+    }
+    // each on its own when there is more than one
+    if supplies.batteries.len() > 1 {
+        for battery in &supplies.batteries {
+            let percent = battery.percent()
+                .map(|percent| format!("{percent}%"))
+                .unwrap_or_else(|| "N/A".to_string());
+            lines.push(format!("{}: {percent}",battery.name));
+        }
+    }
+
+    b.tooltip_text = lines.join("\n");
 }
 
-// TODO: not considering charging until full time
-fn get_remaining(b: &mut Battery) {
-    // read current charge
-    match &b.status {
-        state @ (BatteryStatus::Charging | BatteryStatus::Discharging)=> {
-            let mut file = File::open(BATTERY_CHARGE).unwrap();
-            let mut buff = String::with_capacity(32);
-            let _ = file.read_to_string(&mut buff);
-            let charge_now = buff.trim_end().parse::<f64>().unwrap();
-
-            buff.clear();
-
-            // get current_now
-            let mut file2 = File::open(BATTERY_CURRENT).unwrap();
-            let _ = file2.read_to_string(&mut buff);
-            let current = buff.trim_end().parse::<f64>().unwrap_or(0.0f64);
-
-            match state {
-                BatteryStatus::Charging =>{
-                    buff.clear();
-                    // get full charge of battery to find remaining charge
-                    let _ = File::open(BATTERY_CHARGE_FULL)
-                        .unwrap()
-                        .read_to_string(&mut buff);
-                    let charge_remaining = buff.trim_end()
-                        .parse::<f64>()
-                        .unwrap() - charge_now;
-                    let t = (charge_remaining*3600f64)/current;
-                    let t_h = t/3600f64;
-                    let t_m = (t%3600f64)/60f64;
-
-                    if t_h >= 1.0 {
-                        b.remaining = format!("{} hours {} minutes to full"
-                            ,t_h as u32,t_m as u32).to_string();
-                    } else {
-                        b.remaining = format!("{} minutes to full"
-                            ,t_m as u32).to_string();
-                    }
-
-                },
-                BatteryStatus::Discharging => {
-                    // time remaining in seconds
-                    let t: f64 =  (charge_now * 3600f64) / current;
-                    let t_h: f64 = t / 3600f64;
-                    let t_m: f64 = (t % 3600f64)/60f64;
-
-                    if t_h >= 1.0 {
-                        b.remaining = format!("{} hours {} minutes remaining"
-                            ,t_h as u32,t_m as u32).to_string();
-                    } else {
-                        b.remaining = format!("{} minutes remaining"
-                            ,t_m as u32).to_string();
-                    }
-                }
-                _ => {()}
-            };
+fn get_remaining(b: &mut Battery, supplies: &PowerSupplies) {
+    let Some(t) = supplies.time_left() else {
+        b.remaining = String::new();
+        return
+    };
 
-        },
-        BatteryStatus::NotCharging => b.remaining = "Fully charged".to_string(),
-        _ => b.remaining = "Error getting state".to_string(),
+    let t = t.as_secs();
+    let (t_h,t_m) = (t / 3600,(t % 3600) / 60);
+    let until = if b.status == Status::Charging { "to full" } else { "remaining" };
 
-    };
+    if t_h >= 1 {
+        b.remaining = format!("{t_h} hours {t_m} minutes {until}");
+    } else {
+        b.remaining = format!("{t_m} minutes {until}");
+    }
 }
 
 fn get_icon(b: &mut Battery) {
@@ -338,7 +348,7 @@ fn get_icon(b: &mut Battery) {
     let status = &b.status;
 
     b.icon = match status {
-        BatteryStatus::Charging =>{
+        Status::Charging =>{
 
             match capacity {
                 0..=10=> "assets/status/battery-000-charging.svg".to_string(),
@@ -356,7 +366,7 @@ fn get_icon(b: &mut Battery) {
             }
         },
 
-        BatteryStatus::Discharging => {
+        Status::Discharging => {
             match capacity {
                 0..=10=>  "assets/status/battery-000.svg".to_string(),
                 11..=19=> "assets/status/battery-010.svg".to_string(),
@@ -373,50 +383,66 @@ fn get_icon(b: &mut Battery) {
             }
         },
 
-        BatteryStatus::NotCharging => {
+        Status::NotCharging | Status::Full => {
             match capacity {
                 97..=100=> "assets/status/battery-full-charging.svg".to_string(),
-                _ => "assets/status/battery-missing.svg".to_string()
+                _ => "assets/status/battery-good-charging.svg".to_string()
             }
         }
         _ => "assets/status/battery-missing.svg".to_string()
     }
 }
 
-pub fn get_battery_info() -> Battery {
+// every battery taken together, see power.rs
+pub fn get_battery_info(source: &SystemSource) -> Result<Battery, StatusError> {
     let mut battery = Battery::default();
-    // in this order
-    get_battery(&mut battery);
-    get_status(&mut battery);
-    get_icon(&mut battery);
-    get_remaining(&mut battery);
 
+    let supplies = power::power_supplies(source)
+        .map_err(|err| StatusError::Io(source.path(power::POWER_SUPPLY),err))?;
 
-
-    get_battery_tooltip_text(&mut battery);
-    battery
+    battery.capacity = supplies.capacity()
+        .ok_or_else(|| parse_error(source,power::POWER_SUPPLY,"no battery capacity"))?;
+    battery.status = supplies.status();
+    // in this order
+    get_icon(&mut battery);
+    get_remaining(&mut battery,&supplies);
+    get_battery_tooltip_text(&mut battery,&supplies);
+    Ok(battery)
 }
 
-fn get_date(dt: &OffsetDateTime) -> String {
-    let date = dt.date();
-    let format = format_description::parse("[year]/[month]/[day]").unwrap();
-    date.format(&format).unwrap()
-}
+impl ClockFormat {
+    // `time` falls back to TIME_FORMAT or TIME_FORMAT_12H
+    pub fn new(date: Option<&str>, time: Option<&str>, twelve_hour: bool) -> Self {
+        let default_time = if twelve_hour { TIME_FORMAT_12H } else { TIME_FORMAT };
+        ClockFormat {
+            date: ClockFormat::parse(date,DATE_FORMAT),
+            time: ClockFormat::parse(time,default_time),
+        }
+    }
 
-fn get_time(dt: &OffsetDateTime) -> String {
-    let time = dt.time();
-    let format = format_description::parse("[hour]:[minute]:[second]").unwrap();
-    time.format(&format).unwrap()
-}
+    // a mistake in the config is logged and the default shown instead
+    fn parse(format: Option<&str>, default: &str) -> OwnedFormatItem {
+        let format = format.unwrap_or(default);
+        format_description::parse_owned::<2>(format).unwrap_or_else(|err| {
+            eprintln!("epic-bar: invalid clock format `{format}`: {err}");
+            format_description::parse_owned::<2>(default).expect("default clock format")
+        })
+    }
 
-pub fn get_datetime() -> DateTime {
-    let dt = OffsetDateTime::now_local().unwrap();
-    DateTime{
-        date: get_date(&dt),
-        time: get_time(&dt)
+    pub fn format(&self, dt: OffsetDateTime) -> Result<DateTime, StatusError> {
+        let format = |item| dt.format(item).map_err(|err| StatusError::Clock(err.to_string()));
+        Ok(DateTime { date: format(&self.date)?, time: format(&self.time)? })
     }
 }
 
+/* the time in `zone`
+ * worked out from UTC with the zone's own rules, OffsetDateTime::now_local refuses
+ * to look up the offset once the process has more than one thread
+ */
+pub fn get_datetime(zone: &Zone, format: &ClockFormat) -> Result<DateTime, StatusError> {
+    format.format(zone.to_local(OffsetDateTime::now_utc()))
+}
+
 // so can be easily format out
 impl fmt::Display for DateTime {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -424,32 +450,126 @@ impl fmt::Display for DateTime {
     }
 }
 
-pub fn get_mem_info() -> Memory {
-    let mut file = File::open(MEMORY_INFO).unwrap();
-    let mut buff = [0;96]; // no need to read entire file
-    file.read_exact(&mut buff).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::datetime;
+
+    use crate::topology::CpuTopology;
+
+    // a 16 thread laptop on battery, and its /proc/stat a second later
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    const LAPTOP_LATER: &str = "tests/fixtures/system/laptop-1s";
+    // a 64 core build server up for about 100 days, a second apart
+    // the second snapshot also stands in for a kernel without PSI
+    const SERVER: &str = "tests/fixtures/system/server-64";
+    const SERVER_LATER: &str = "tests/fixtures/system/server-64-1s";
 
-    let binding =  String::from_utf8_lossy(&buff);
-    let mut string = binding.lines();
+    #[test]
+    fn clock_formats() {
+        let source = SystemSource::new(LAPTOP);
+        let berlin = Zone::load(&source,"Europe/Berlin").unwrap();
+        let dt = berlin.to_local(datetime!(2026-07-04 13:05:09 UTC));
 
-    let memtotal_kb: f64 = string.nth(0).unwrap()
-        .to_string()
-        .split_whitespace()
-        .nth(1).unwrap()
-        .parse().unwrap();
+        let clock = ClockFormat::new(None,None,false).format(dt).unwrap();
+        assert_eq!(format!("{clock}"), "2026/07/04\n15:05:09");
+
+        let clock = ClockFormat::new(None,None,true).format(dt).unwrap();
+        assert_eq!(clock.time, "3:05:09 PM");
+
+        let clock = ClockFormat::new(Some("[weekday repr:short] [day] [month repr:short]"),Some("[hour]:[minute] UTC[offset_hour sign:mandatory]"),false)
+            .format(dt).unwrap();
+        assert_eq!((clock.date.as_str(),clock.time.as_str()), ("Sat 04 Jul","15:05 UTC+02"));
+
+        // an unknown component falls back to the default
+        let clock = ClockFormat::new(Some("[yaer]"),None,false).format(dt).unwrap();
+        assert_eq!(clock.date, "2026/07/04");
+    }
+
+    fn assert_load(load: f32, expected: f32) {
+        assert!((load - expected).abs() < 0.001, "load {load}, expected {expected}");
+    }
 
-    let memused_kb: f64 = string.nth(1).unwrap()
-        .to_string()
-        .split_whitespace()
-        .nth(1).unwrap()
-        .parse().unwrap();
+    #[test]
+    fn cpu_load_between_snapshots() {
+        let mut cpu = Cpu::new();
+        cpu.get_cpu_load(&SystemSource::new(LAPTOP)).unwrap();
 
-    let total = memtotal_kb / 1_048_576.0;
-    let used = (memtotal_kb - memused_kb)/1_048_576.0;
+        // 8000 of 32000 jiffies busy, the laptop has no steal and guest time is already in user
+        assert_eq!(cpu.get_cpu_load(&SystemSource::new(LAPTOP_LATER)).unwrap(), 25.0);
+        assert_eq!(cpu.get_cpu_image(), "assets/status/indicator-cpufreq-25.svg");
+    }
+
+    #[test]
+    fn many_core_load() {
+        let mut cpu = Cpu::new();
+        cpu.get_cpu_load(&SystemSource::new(SERVER)).unwrap();
+
+        // a build on 8 cores and 5% on the rest, the counters no longer fit in 32 bits
+        assert_load(cpu.get_cpu_load(&SystemSource::new(SERVER_LATER)).unwrap(), 16.875);
+
+        assert_eq!(cpu.core_loads.len(), 64);
+        // guest time is already in user time
+        assert_eq!(cpu.core_loads[0].0, 0);
+        assert_load(cpu.core_loads[0].1, 100.0);
+        // steal counts as busy
+        assert_eq!(cpu.core_loads[8].0, 8);
+        assert_load(cpu.core_loads[8].1, 5.0);
+        assert_load(cpu.core_loads[63].1, 5.0);
+    }
+
+    #[test]
+    fn cpu_lines() {
+        // kernels before 2.6 only had four fields
+        assert_eq!(CpuTimes::parse("cpu3 10 0 5 100"), Some((Some(3),CpuTimes { busy: 15, idle: 100 })));
+        assert_eq!(CpuTimes::parse("cpu  1 2 3 4 5 6 7 8 9 10"), Some((None,CpuTimes { busy: 27, idle: 9 })));
+        assert_eq!(CpuTimes::parse("intr 1 2 3 4"), None);
+        assert_eq!(CpuTimes::parse("cpu 1 2"), None);
+    }
+
+    #[test]
+    fn history_of_a_stuck_core() {
+        let mut history = CpuHistory::default();
+        let mut cpu = Cpu::new();
+
+        // the first read is since boot and has no per-core loads
+        history.push(&cpu);
+        assert!(history.latest().is_empty());
+
+        for second in 0..70 {
+            // core 3 pinned for the last 12 seconds, core 1 only now and then
+            let three = if second >= 58 { 100.0 } else { 10.0 };
+            let one = if second % 2 == 0 { 100.0 } else { 0.0 };
+            cpu.core_loads = vec![(0,5.0),(1,one),(2,5.0),(3,three)];
+            cpu.avg_load = cpu.core_loads.iter().map(|(_,load)| load).sum::<f32>() / 4.0;
+            history.push(&cpu);
+        }
+
+        assert_eq!(history.averages().count(), 60);
+        assert_eq!(history.latest()[3], (3,100.0));
+        assert_eq!(history.busiest().last(), Some(100.0));
+        assert_eq!(history.pinned(10), vec![3]);
+        assert_eq!(history.pinned(13), Vec::<u32>::new());
+        assert_eq!(history.pinned(1), vec![3]);
+        assert_eq!(history.pinned(61), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn battery_summary() {
+        let battery = get_battery_info(&SystemSource::new(LAPTOP)).unwrap();
+
+        assert_eq!(battery.capacity, 57);
+        assert_eq!(battery.icon, "assets/status/battery-050.svg");
+        // 32 Wh left at 8 W
+        assert_eq!(battery.tooltip_text, "Discharging\n4 hours 0 minutes remaining");
+    }
 
-    let string = format!("{:.1}/{:.1} GiB",used,total).to_string();
+    #[test]
+    fn missing_files_are_errors() {
+        let source = SystemSource::new("tests/fixtures/system/missing");
 
-    Memory {
-        string
+        assert!(matches!(Cpu::new().get_cpu_load(&source), Err(StatusError::Io(..))));
+        assert!(matches!(CpuTopology::read(&source), Err(StatusError::Io(..))));
     }
 }
@@ -0,0 +1,173 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use gtk::{
+    prelude::*,
+    CssProvider,
+    Label,
+    gio,
+    gdk::Display,
+    glib,
+};
+
+use crate::{
+    css,
+    config,
+    theme::Theme,
+};
+
+/* user: ~/.config/epic-bar/style.css, above the built-in css so it can override any rule
+ * _monitor: kept alive for as long as the stylesheet should be watched
+ * errors: problems found in the last load of the user stylesheet
+ * indicators: labels in every bar showing those problems
+ */
+struct Style {
+    user: CssProvider,
+    path: Option<PathBuf>,
+    _monitor: Option<gio::FileMonitor>,
+    errors: Vec<String>,
+    indicators: Vec<glib::WeakRef<Label>>,
+}
+
+thread_local! {
+    static STYLE: RefCell<Option<Style>> = const { RefCell::new(None) };
+}
+
+// install the built-in and user stylesheets on the display, once
+pub fn init(theme: &Theme) {
+    let Some(display) = Display::default() else {
+        return
+    };
+
+    let builtin = CssProvider::new();
+    builtin.load_from_string(&css::render(theme));
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &builtin,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+    let user = CssProvider::new();
+    // only called while loading, so the errors are collected before reload reports them
+    user.connect_parsing_error(|_,section,error| {
+        let location = section.start_location();
+        let problem = format!("line {}, column {}: {}",
+            location.lines() + 1,
+            location.line_chars() + 1,
+            error.message());
+        with_style(|style| style.errors.push(problem));
+    });
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &user,
+        gtk::STYLE_PROVIDER_PRIORITY_USER);
+
+    let path = config::config_dir().map(|dir| dir.join("style.css"));
+    // the file does not have to exist yet, it is picked up once created
+    let monitor = path.as_ref().and_then(|path| {
+        gio::File::for_path(path)
+            .monitor_file(gio::FileMonitorFlags::WATCH_MOVES,None::<&gio::Cancellable>)
+            .inspect_err(|err| eprintln!("epic-bar: can not watch {}: {err}",path.display()))
+            .ok()
+    });
+
+    if let Some(monitor) = &monitor {
+        monitor.connect_changed(|_,_,_,event| {
+            match event {
+                gio::FileMonitorEvent::ChangesDoneHint
+                | gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::Deleted
+                | gio::FileMonitorEvent::MovedIn
+                | gio::FileMonitorEvent::MovedOut
+                | gio::FileMonitorEvent::Renamed => reload(),
+                _ => {}
+            }
+        });
+    }
+
+    STYLE.with_borrow_mut(|style| *style = Some(Style {
+        user,
+        path,
+        _monitor: monitor,
+        errors: Vec::new(),
+        indicators: Vec::new(),
+    }));
+
+    reload();
+}
+
+fn with_style<F: FnOnce(&mut Style)>(f: F) {
+    STYLE.with_borrow_mut(|style| {
+        if let Some(style) = style {
+            f(style);
+        }
+    });
+}
+
+// a missing stylesheet just means only the built-in one is used
+fn read_user_css(path: Option<&PathBuf>) -> Result<String, String> {
+    let Some(path) = path else {
+        return Ok(String::new())
+    };
+
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(format!("could not read: {err}")),
+    }
+}
+
+fn reload() {
+    let Some((user,path)) = STYLE.with_borrow_mut(|style| {
+        style.as_mut().map(|style| {
+            style.errors.clear();
+            (style.user.clone(),style.path.clone())
+        })
+    }) else {
+        return
+    };
+
+    match read_user_css(path.as_ref()) {
+        // parsing errors are pushed while this runs
+        Ok(text) => user.load_from_string(&text),
+        Err(err) => with_style(|style| style.errors.push(err)),
+    }
+
+    with_style(|style| {
+        let name = path.as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        for error in &style.errors {
+            eprintln!("epic-bar: {name}: {error}");
+        }
+
+        style.indicators.retain(|indicator| indicator.upgrade().is_some());
+        for indicator in style.indicators.iter().filter_map(|indicator| indicator.upgrade()) {
+            show_errors(&indicator,&style.errors);
+        }
+    });
+}
+
+fn show_errors(indicator: &Label, errors: &[String]) {
+    indicator.set_visible(!errors.is_empty());
+    indicator.set_tooltip_text(Some(&errors.join("\n")));
+}
+
+// small label shown in a bar while the user stylesheet has errors, hover for the list
+pub fn error_indicator() -> Label {
+    let indicator = Label::builder()
+        .label("󰘦")
+        .css_name("style-error-label")
+        .visible(false)
+        .build();
+
+    with_style(|style| {
+        show_errors(&indicator,&style.errors);
+        style.indicators.push(indicator.downgrade());
+    });
+
+    indicator
+}
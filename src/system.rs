@@ -0,0 +1,49 @@
+use std::{
+    fs,
+    io,
+    path::{Path,PathBuf},
+};
+
+/* SystemSource: where /proc and /sys are read from
+ * `/` on a running system, a recorded snapshot directory in tests
+ */
+#[derive(Debug, Clone)]
+pub struct SystemSource {
+    root: PathBuf,
+}
+
+impl Default for SystemSource {
+    fn default() -> Self {
+        SystemSource::new("/")
+    }
+}
+
+impl SystemSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        SystemSource { root: root.into() }
+    }
+
+    // `path` as it is on a running system, e.g. /proc/stat
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        fs::read_to_string(self.path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_under_root() {
+        assert_eq!(SystemSource::default().path("/proc/stat"), PathBuf::from("/proc/stat"));
+
+        let source = SystemSource::new("tests/fixtures/system/laptop");
+        assert_eq!(source.path("/proc/stat"), PathBuf::from("tests/fixtures/system/laptop/proc/stat"));
+        assert_eq!(source.path("sys/class"), PathBuf::from("tests/fixtures/system/laptop/sys/class"));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+/* a theme is a handful of named values that css::TEMPLATE refers to as $name
+ *
+ * every theme sets every variable in DARK, a config can pick one with
+ * `theme = "light"` and override single values under [palette]
+ */
+pub const DEFAULT_THEME: &str = "dark";
+
+const DARK: &[(&str, &str)] = &[
+    ("font", "'Fira Sans', sans-serif"),
+    ("font-size", "16px"),
+    ("font-size-small", "12px"),
+    ("font-size-icon", "20px"),
+    ("fg", "white"),
+    ("fg-dim", "rgba(255,255,255,0.5)"),
+    ("border", "white"),
+    ("bar-background", "linear-gradient(
+        to bottom,
+        rgba(15,25,35,0.85)0%,
+        rgba(20,30,40,0.85)40%,
+        rgba(10,20,30,0.85)50%,
+        rgba(5,15,25,0.85)70%
+    )"),
+    ("occupied", "rgba(40,44,52,0.7)"),
+    ("occupied-dark", "rgba(30,33,40,0.7)"),
+    ("accent", "rgba(65,105,225,0.8)"),
+    ("accent-dark", "rgba(45,85,205,0.8)"),
+    ("empty-active", "rgba(180,20,20,0.7)"),
+    ("error", "rgba(255,120,120,1)"),
+    ("warning", "rgba(255,200,90,1)"),
+];
+
+const LIGHT: &[(&str, &str)] = &[
+    ("font", "'Fira Sans', sans-serif"),
+    ("font-size", "16px"),
+    ("font-size-small", "12px"),
+    ("font-size-icon", "20px"),
+    ("fg", "rgba(30,30,35,1)"),
+    ("fg-dim", "rgba(30,30,35,0.55)"),
+    ("border", "rgba(30,30,35,0.6)"),
+    ("bar-background", "linear-gradient(
+        to bottom,
+        rgba(245,245,248,0.9),
+        rgba(225,228,235,0.9)
+    )"),
+    ("occupied", "rgba(210,214,222,0.8)"),
+    ("occupied-dark", "rgba(195,200,210,0.8)"),
+    ("accent", "rgba(65,105,225,0.85)"),
+    ("accent-dark", "rgba(45,85,205,0.85)"),
+    ("empty-active", "rgba(200,60,60,0.75)"),
+    ("error", "rgba(190,30,30,1)"),
+    ("warning", "rgba(170,110,0,1)"),
+];
+
+const HIGH_CONTRAST: &[(&str, &str)] = &[
+    ("font", "'Fira Sans', sans-serif"),
+    ("font-size", "18px"),
+    ("font-size-small", "14px"),
+    ("font-size-icon", "22px"),
+    ("fg", "white"),
+    ("fg-dim", "rgba(255,255,255,0.8)"),
+    ("border", "white"),
+    ("bar-background", "linear-gradient(to bottom, black, black)"),
+    ("occupied", "rgba(60,60,60,1)"),
+    ("occupied-dark", "rgba(40,40,40,1)"),
+    ("accent", "rgba(255,215,0,1)"),
+    ("accent-dark", "rgba(230,190,0,1)"),
+    ("empty-active", "rgba(255,0,0,1)"),
+    ("error", "rgba(255,80,80,1)"),
+    ("warning", "rgba(255,215,0,1)"),
+];
+
+pub const THEMES: [(&str, &[(&str, &str)]);3] = [
+    ("dark", DARK),
+    ("light", LIGHT),
+    ("high-contrast", HIGH_CONTRAST),
+];
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    vars: BTreeMap<String,String>,
+}
+
+// names a [palette] table may set
+pub fn is_variable(name: &str) -> bool {
+    DARK.iter().any(|(var,_)| *var == name)
+}
+
+pub fn names() -> Vec<&'static str> {
+    THEMES.iter().map(|(name,_)| *name).collect()
+}
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Self> {
+        let (_,vars) = THEMES.iter().find(|(theme,_)| *theme == name)?;
+
+        Some(Theme {
+            vars: vars.iter()
+                .map(|(var,value)| (var.to_string(),value.to_string()))
+                .collect()
+        })
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(),value.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::named(DEFAULT_THEME).expect("default theme exists")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_set_every_variable() {
+        for (name,vars) in THEMES {
+            let mut theme_vars: Vec<&str> = vars.iter().map(|(var,_)| *var).collect();
+            let mut dark_vars: Vec<&str> = DARK.iter().map(|(var,_)| *var).collect();
+            theme_vars.sort();
+            dark_vars.sort();
+            assert_eq!(theme_vars, dark_vars, "theme {name}");
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::{
+    env,
+    fs,
+    ops::RangeInclusive,
+};
+
+use time::{
+    util::is_leap_year,
+    Date,
+    Duration,
+    Month,
+    OffsetDateTime,
+    PrimitiveDateTime,
+    UtcOffset,
+};
+
+use crate::{
+    status::StatusError,
+    system::SystemSource,
+};
+
+pub const ZONEINFO: &str = "/usr/share/zoneinfo";
+const LOCALTIME: &str = "/etc/localtime";
+
+// julian day of 1970-01-01
+const UNIX_EPOCH_DAY: i64 = 2_440_588;
+
+/* LocalType: what the clocks show in a zone for a while
+ * offset: seconds east of UTC
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct LocalType {
+    offset: i32,
+    dst: bool,
+    abbreviation: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    // Jn: 1 to 365, February 29 is never counted
+    Julian(u16),
+    // n: 0 to 365, counting February 29 in leap years
+    Ordinal(u16),
+    // Mm.w.d: weekday d (0 is Sunday) of week w (5 is the last) of month m
+    Weekday { month: u8, week: u8, weekday: u8 },
+}
+
+// when daylight saving time starts or ends, `time` seconds after local midnight
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Change {
+    day: Day,
+    time: i32,
+}
+
+/* Rule: a POSIX TZ string, as in $TZ or at the end of a TZif file
+ * "CET-1CEST,M3.5.0,M10.5.0/3" covers every year after the file's last transition
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    std: LocalType,
+    dst: Option<(LocalType,Change,Change)>,
+}
+
+// (unix time, index into the local types) from which that type applies, oldest first
+type Transitions = Vec<(i64,usize)>;
+
+/* Zone: one tzdata zone
+ *
+ * the TZif files under /usr/share/zoneinfo list every change of offset up to
+ * some year and end in a Rule for the years after that
+ */
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub name: String,
+    transitions: Transitions,
+    types: Vec<LocalType>,
+    rule: Option<Rule>,
+}
+
+// a cursor over the big endian TZif data
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None
+        }
+        let (taken,rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_be_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+// isutcnt isstdcnt leapcnt timecnt typecnt charcnt
+struct Counts {
+    utc: usize,
+    std: usize,
+    leap: usize,
+    time: usize,
+    types: usize,
+    chars: usize,
+}
+
+// the 44 byte header, the version is b'\0', b'2', b'3' or b'4'
+fn header(reader: &mut Reader) -> Option<(u8,Counts)> {
+    let header = reader.take(20)?;
+    if &header[..4] != b"TZif" {
+        return None
+    }
+    let mut count = || Some(reader.u32()? as usize);
+    Some((header[4],Counts {
+        utc: count()?,
+        std: count()?,
+        leap: count()?,
+        time: count()?,
+        types: count()?,
+        chars: count()?,
+    }))
+}
+
+// one data block, `wide` for the 64-bit block of version 2 and later
+fn block(reader: &mut Reader, counts: &Counts, wide: bool) -> Option<(Transitions,Vec<LocalType>)> {
+    let times: Vec<i64> = (0..counts.time)
+        .map(|_| if wide { reader.i64() } else { reader.i32().map(i64::from) })
+        .collect::<Option<_>>()?;
+    let indices = reader.take(counts.time)?;
+
+    let mut types = Vec::new();
+    for _ in 0..counts.types {
+        let offset = reader.i32()?;
+        let info = reader.take(2)?;
+        types.push((offset,info[0] != 0,info[1] as usize));
+    }
+    let chars = reader.take(counts.chars)?;
+
+    // leap seconds, standard/wall and UT/local indicators are not needed
+    reader.take(counts.leap * if wide { 12 } else { 8 } + counts.std + counts.utc)?;
+
+    let types = types.into_iter()
+        .map(|(offset,dst,start)| {
+            let name = chars.get(start..)?;
+            let end = name.iter().position(|c| *c == 0)?;
+            Some(LocalType { offset, dst, abbreviation: String::from_utf8_lossy(&name[..end]).into_owned() })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let transitions = times.into_iter()
+        .zip(indices.iter().map(|index| *index as usize))
+        .collect::<Vec<_>>();
+    if transitions.iter().any(|(_,index)| *index >= types.len()) {
+        return None
+    }
+    Some((transitions,types))
+}
+
+// a POSIX TZ string, one piece at a time
+struct RuleParser<'a> {
+    rest: &'a str,
+}
+
+impl RuleParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        let end = self.rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len());
+        let (digits,rest) = self.rest.split_at(end);
+        self.rest = rest;
+        digits.parse().ok()
+    }
+
+    // a number POSIX only allows within `range`
+    fn number_in(&mut self, range: RangeInclusive<i32>) -> Option<i32> {
+        self.number().filter(|number| range.contains(number))
+    }
+
+    // "CET", or "<+0530>" for ones that are not letters
+    fn name(&mut self) -> Option<String> {
+        if self.eat('<') {
+            let (name,rest) = self.rest.split_once('>')?;
+            self.rest = rest;
+            return Some(name.to_string())
+        }
+        let end = self.rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(self.rest.len());
+        let (name,rest) = self.rest.split_at(end);
+        self.rest = rest;
+        (name.len() >= 3).then(|| name.to_string())
+    }
+
+    // [+-]hh[:mm[:ss]] in seconds
+    fn time(&mut self) -> Option<i32> {
+        let sign = if self.eat('-') { -1 } else { self.eat('+'); 1 };
+        let mut seconds = self.number()? * 3600;
+        if self.eat(':') {
+            seconds += self.number()? * 60;
+            if self.eat(':') {
+                seconds += self.number()?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    // Jn counts 1 to 365 leaving out the leap day, n counts 0 to 365
+    fn change(&mut self) -> Option<Change> {
+        let day = if self.eat('J') {
+            Day::Julian(self.number_in(1..=365)? as u16)
+        } else if self.eat('M') {
+            let month = self.number_in(1..=12)? as u8;
+            self.eat('.').then_some(())?;
+            let week = self.number_in(1..=5)? as u8;
+            self.eat('.').then_some(())?;
+            Day::Weekday { month, week, weekday: self.number_in(0..=6)? as u8 }
+        } else {
+            Day::Ordinal(self.number_in(0..=365)? as u16)
+        };
+        let time = if self.eat('/') { self.time()? } else { 2 * 3600 };
+        Some(Change { day, time })
+    }
+}
+
+impl Rule {
+    fn parse(tz: &str) -> Option<Self> {
+        let mut parser = RuleParser { rest: tz.trim() };
+
+        // POSIX offsets count west of UTC, the other way round from everything else
+        let std = LocalType {
+            abbreviation: parser.name()?,
+            offset: -parser.time()?,
+            dst: false,
+        };
+        if parser.rest.is_empty() {
+            return Some(Rule { std, dst: None })
+        }
+
+        let abbreviation = parser.name()?;
+        let offset = match parser.peek() {
+            Some(c) if c == '+' || c == '-' || c.is_ascii_digit() => -parser.time()?,
+            _ => std.offset + 3600,
+        };
+        let dst = LocalType { offset, dst: true, abbreviation };
+
+        let (start,end) = if parser.eat(',') {
+            let start = parser.change()?;
+            parser.eat(',').then_some(())?;
+            (start,parser.change()?)
+        } else {
+            // no dates given, as POSIX leaves it to the system: the US rules since 2007
+            (Change { day: Day::Weekday { month: 3, week: 2, weekday: 0 }, time: 7200 },
+             Change { day: Day::Weekday { month: 11, week: 1, weekday: 0 }, time: 7200 })
+        };
+
+        parser.rest.is_empty().then_some(Rule { std, dst: Some((dst,start,end)) })
+    }
+
+    fn local_type(&self, time: i64) -> &LocalType {
+        let Some((dst,start,end)) = &self.dst else {
+            return &self.std
+        };
+        let Ok(year) = OffsetDateTime::from_unix_timestamp(time).map(|utc| utc.year()) else {
+            return &self.std
+        };
+
+        // each change happens at a local time of the offset in effect before it
+        let (Some(start),Some(end)) = (start.at(year),end.at(year)) else {
+            return &self.std
+        };
+        let start = start - self.std.offset as i64;
+        let end = end - dst.offset as i64;
+
+        // the southern hemisphere's summer spans the new year
+        let in_dst = if start < end {
+            start <= time && time < end
+        } else {
+            !(end <= time && time < start)
+        };
+        if in_dst { dst } else { &self.std }
+    }
+}
+
+impl Change {
+    // seconds since the epoch in `year`, as if local time were UTC
+    fn at(&self, year: i32) -> Option<i64> {
+        let date = match self.day {
+            Day::Julian(day) => {
+                Date::from_ordinal_date(year,if is_leap_year(year) && day >= 60 { day + 1 } else { day }).ok()?
+            },
+            Day::Ordinal(day) => Date::from_ordinal_date(year,day + 1).ok()?,
+            Day::Weekday { month, week, weekday } => {
+                let month = Month::try_from(month).ok()?;
+                let first = Date::from_calendar_date(year,month,1).ok()?.weekday().number_days_from_sunday();
+                let day = 1 + (weekday + 7 - first) % 7 + week.saturating_sub(1) * 7;
+                // week 5 is the last such weekday, whether the month has four or five
+                let mut date = Date::from_calendar_date(year,month,day);
+                if date.is_err() {
+                    date = Date::from_calendar_date(year,month,day - 7);
+                }
+                date.ok()?
+            },
+        };
+        Some((date.to_julian_day() as i64 - UNIX_EPOCH_DAY) * 86400 + self.time as i64)
+    }
+}
+
+impl Zone {
+    pub fn utc() -> Self {
+        Zone {
+            name: "UTC".to_string(),
+            transitions: Vec::new(),
+            types: Vec::new(),
+            rule: Rule::parse("UTC0"),
+        }
+    }
+
+    fn from_tzif(name: &str, data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let (version,counts) = header(&mut reader)?;
+        let (mut transitions,mut types) = block(&mut reader,&counts,false)?;
+
+        // version 2 repeats it all with 64-bit times and adds the rule
+        let mut rule = None;
+        if version >= b'2' {
+            let (_,counts) = header(&mut reader)?;
+            (transitions,types) = block(&mut reader,&counts,true)?;
+            let footer = String::from_utf8_lossy(reader.data);
+            rule = footer.trim().lines().next().and_then(Rule::parse);
+        }
+
+        if types.is_empty() {
+            return None
+        }
+        Some(Zone { name: name.to_string(), transitions, types, rule })
+    }
+
+    // `name` as in Europe/Berlin, from the zoneinfo directory
+    pub fn load(source: &SystemSource, name: &str) -> Result<Self, StatusError> {
+        let path = format!("{ZONEINFO}/{name}");
+        if name.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(StatusError::Missing(format!("time zone `{name}`")))
+        }
+        Zone::read(source,&path,name)
+    }
+
+    fn read(source: &SystemSource, path: &str, name: &str) -> Result<Self, StatusError> {
+        let data = fs::read(source.path(path)).map_err(|err| StatusError::Io(source.path(path),err))?;
+        Zone::from_tzif(name,&data).ok_or_else(|| StatusError::Parse(source.path(path),"not a TZif file"))
+    }
+
+    /* $TZ if set, as glibc reads it: empty for UTC, a zone name or file with an
+     * optional leading colon, or a POSIX rule; /etc/localtime otherwise
+     */
+    pub fn local(source: &SystemSource) -> Result<Self, StatusError> {
+        Zone::from_tz(source,env::var("TZ").ok().as_deref())
+    }
+
+    fn from_tz(source: &SystemSource, tz: Option<&str>) -> Result<Self, StatusError> {
+        match tz.map(|tz| tz.strip_prefix(':').unwrap_or(tz)) {
+            None => {
+                // a link into zoneinfo tells the name, a copied file does not
+                let name = fs::read_link(source.path(LOCALTIME)).ok()
+                    .and_then(|target| {
+                        let target = target.to_string_lossy().into_owned();
+                        target.split_once("zoneinfo/").map(|(_,name)| name.to_string())
+                    })
+                    .unwrap_or_else(|| "local".to_string());
+                Zone::read(source,LOCALTIME,&name)
+            },
+            Some("") => Ok(Zone::utc()),
+            Some(tz) if tz.starts_with('/') => Zone::read(source,tz,tz),
+            Some(tz) => Zone::load(source,tz).or_else(|err| {
+                let rule = Rule::parse(tz).ok_or(err)?;
+                Ok(Zone { name: tz.to_string(), transitions: Vec::new(), types: Vec::new(), rule: Some(rule) })
+            }),
+        }
+    }
+
+    fn local_type(&self, time: i64) -> &LocalType {
+        let after_last = self.transitions.last().is_none_or(|(last,_)| time >= *last);
+        if let (true,Some(rule)) = (after_last,&self.rule) {
+            return rule.local_type(time)
+        }
+
+        // before the first transition the first type applies
+        let index = self.transitions.partition_point(|(start,_)| *start <= time);
+        let index = index.checked_sub(1).map(|index| self.transitions[index].1).unwrap_or(0);
+        &self.types[index]
+    }
+
+    pub fn offset(&self, time: OffsetDateTime) -> UtcOffset {
+        UtcOffset::from_whole_seconds(self.local_type(time.unix_timestamp()).offset).unwrap_or(UtcOffset::UTC)
+    }
+
+    // CEST, EST, or a number like +0530 where the zone has no letters
+    pub fn abbreviation(&self, time: OffsetDateTime) -> &str {
+        &self.local_type(time.unix_timestamp()).abbreviation
+    }
+
+    pub fn to_local(&self, time: OffsetDateTime) -> OffsetDateTime {
+        time.to_offset(self.offset(time))
+    }
+
+    /* the instant a wall clock reading in this zone stands for
+     * a time that happens twice when the clocks go back is the earlier one, a time
+     * skipped when they go forward is read with the offset from before, as RFC 5545 has it
+     */
+    pub fn from_local(&self, local: PrimitiveDateTime) -> OffsetDateTime {
+        let before = self.offset(local.assume_utc() - Duration::DAY);
+        let after = self.offset(local.assume_utc() + Duration::DAY);
+        [before,after].into_iter()
+            .map(|offset| local.assume_offset(offset))
+            .find(|time| self.offset(*time) == time.offset())
+            .unwrap_or_else(|| local.assume_offset(before))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::macros::datetime;
+
+    // /etc/localtime links to Europe/Berlin, tzdata 2025b
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+
+    fn zone(name: &str) -> Zone {
+        Zone::load(&SystemSource::new(LAPTOP),name).unwrap()
+    }
+
+    #[test]
+    fn transitions_from_the_file() {
+        let berlin = zone("Europe/Berlin");
+        let summer = datetime!(2026-07-01 12:00 UTC);
+        assert_eq!(berlin.offset(summer), UtcOffset::from_hms(2,0,0).unwrap());
+        assert_eq!(berlin.abbreviation(summer), "CEST");
+        assert_eq!(berlin.abbreviation(datetime!(2026-12-01 12:00 UTC)), "CET");
+
+        // clocks went forward at 01:00 UTC on the last Sunday of March
+        assert_eq!(berlin.abbreviation(datetime!(2026-03-29 00:59:59 UTC)), "CET");
+        assert_eq!(berlin.abbreviation(datetime!(2026-03-29 01:00 UTC)), "CEST");
+
+        // before the first transition, local mean time
+        assert_eq!(berlin.abbreviation(datetime!(1850-01-01 0:00 UTC)), "LMT");
+
+        let kolkata = zone("Asia/Kolkata");
+        assert_eq!(kolkata.offset(summer), UtcOffset::from_hms(5,30,0).unwrap());
+    }
+
+    #[test]
+    fn rules_after_the_last_transition() {
+        // the files list changes up to 2037
+        let berlin = zone("Europe/Berlin");
+        assert_eq!(berlin.abbreviation(datetime!(2040-10-28 00:59 UTC)), "CEST");
+        assert_eq!(berlin.abbreviation(datetime!(2040-10-28 01:00 UTC)), "CET");
+
+        // summer time over the new year
+        let sydney = zone("Australia/Sydney");
+        assert_eq!(sydney.abbreviation(datetime!(2040-01-01 0:00 UTC)), "AEDT");
+        assert_eq!(sydney.offset(datetime!(2040-07-01 0:00 UTC)), UtcOffset::from_hms(10,0,0).unwrap());
+
+        let new_york = zone("America/New_York");
+        assert_eq!(new_york.to_local(datetime!(2040-03-11 07:00 UTC)).hour(), 3);
+        assert_eq!(new_york.to_local(datetime!(2040-03-11 06:59 UTC)).hour(), 1);
+    }
+
+    #[test]
+    fn wall_clock_times() {
+        let berlin = zone("Europe/Berlin");
+        assert_eq!(berlin.from_local(datetime!(2026-07-01 14:00)), datetime!(2026-07-01 12:00 UTC));
+
+        // 2:30 happens twice on the last Sunday of October and not at all in March
+        assert_eq!(berlin.from_local(datetime!(2026-10-25 2:30)), datetime!(2026-10-25 0:30 UTC));
+        assert_eq!(berlin.from_local(datetime!(2026-10-25 3:30)), datetime!(2026-10-25 2:30 UTC));
+        assert_eq!(berlin.from_local(datetime!(2026-03-29 2:30)), datetime!(2026-03-29 1:30 UTC));
+    }
+
+    #[test]
+    fn posix_rules() {
+        let rule = Rule::parse("<+0530>-5:30").unwrap();
+        assert_eq!((rule.std.offset,rule.std.abbreviation.as_str()), (19800,"+0530"));
+        assert!(rule.dst.is_none());
+
+        let rule = Rule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        let (dst,start,_) = rule.dst.as_ref().unwrap();
+        assert_eq!(dst.offset, -4 * 3600);
+        // second Sunday of March 2026 at 2:00
+        assert_eq!(start.at(2026), Some(datetime!(2026-03-08 2:00 UTC).unix_timestamp()));
+
+        assert_eq!(Change { day: Day::Julian(60), time: 0 }.at(2028), Some(datetime!(2028-03-01 0:00 UTC).unix_timestamp()));
+        assert_eq!(Change { day: Day::Ordinal(59), time: 0 }.at(2028), Some(datetime!(2028-02-29 0:00 UTC).unix_timestamp()));
+
+        assert_eq!(Rule::parse("EST"), None);
+        assert_eq!(Rule::parse("CET-1CEST,M3.5.0"), None);
+        for tz in ["EST5EDT,M3.40.0,M11.1.0", "EST5EDT,M13.2.0,M11.1.0", "EST5EDT,M3.2.7,M11.1.0", "EST5EDT,J0,J366", "EST5EDT,0,366"] {
+            assert_eq!(Rule::parse(tz), None, "{tz}");
+        }
+    }
+
+    #[test]
+    fn local_zone() {
+        let source = SystemSource::new(LAPTOP);
+        assert_eq!(Zone::from_tz(&source,None).unwrap().name, "Europe/Berlin");
+        assert_eq!(Zone::from_tz(&source,Some(":America/New_York")).unwrap().name, "America/New_York");
+        assert_eq!(Zone::from_tz(&source,Some("")).unwrap().name, "UTC");
+
+        let rule = Zone::from_tz(&source,Some("JST-9")).unwrap();
+        assert_eq!(rule.offset(datetime!(2026-01-01 0:00 UTC)), UtcOffset::from_hms(9,0,0).unwrap());
+
+        assert!(matches!(Zone::from_tz(&source,Some("Mars/Olympus_Mons")), Err(StatusError::Io(..))));
+        assert!(matches!(Zone::load(&source,"../../etc/passwd"), Err(StatusError::Missing(..))));
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+};
+
+use crate::{
+    status::{
+        self,
+        StatusError,
+    },
+    system::SystemSource,
+};
+
+const CPU_ONLINE: &str = "/sys/devices/system/cpu/online";
+const CPUFREQ: &str = "/sys/devices/system/cpu/cpufreq";
+const CPU_INFO: &str = "/proc/cpuinfo";
+
+// a core slower than this share of the fastest is an efficiency core,
+// favoured cores of the same kind only differ by a few percent
+const EFFICIENCY_RATIO: f64 = 0.85;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreKind {
+    Performance,
+    Efficiency,
+}
+
+/* Policy: cores that always run at the same frequency
+ * max_freq: the highest the hardware allows, in kHz
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct Policy {
+    name: String,
+    cpus: Vec<u32>,
+    max_freq: Option<u64>,
+}
+
+/* CpuTopology: the online cores and how they are grouped, read once
+ * online: as at that read, frequencies look at which cores are online on every sample
+ *
+ * cpufreq policies give the frequencies, machines without cpufreq (most vms)
+ * fall back to /proc/cpuinfo
+ * kinds: per core, empty unless the cores differ, from cpu_capacity where the
+ * kernel has it (arm, recent x86) and from each policy's top frequency otherwise
+ */
+#[derive(Debug, Clone)]
+pub struct CpuTopology {
+    pub online: Vec<u32>,
+    policies: Vec<Policy>,
+    pub kinds: BTreeMap<u32,CoreKind>,
+}
+
+/* Frequencies: one reading across the online cores, in kHz
+ * performance/efficiency: averages of each kind on hybrid cpus
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frequencies {
+    pub min: u64,
+    pub avg: u64,
+    pub max: u64,
+    pub performance: Option<u64>,
+    pub efficiency: Option<u64>,
+}
+
+// kernel cpu lists, "0-3,8,10-11"
+pub fn parse_cpu_list(list: &str) -> Option<Vec<u32>> {
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first,last)) => cpus.extend(first.parse::<u32>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+
+    Some(cpus)
+}
+
+fn number(source: &SystemSource, path: &str) -> Option<u64> {
+    source.read(path).ok()?.trim().parse().ok()
+}
+
+// split by the ratio of each value to the largest
+fn classify(values: impl Iterator<Item = (u32,u64)>) -> BTreeMap<u32,CoreKind> {
+    let values: Vec<(u32,u64)> = values.collect();
+    let fastest = values.iter().map(|(_,value)| *value).max().unwrap_or(0);
+
+    let kinds: BTreeMap<u32,CoreKind> = values.iter()
+        .map(|(cpu,value)| {
+            let kind = if (*value as f64) < fastest as f64 * EFFICIENCY_RATIO {
+                CoreKind::Efficiency
+            } else {
+                CoreKind::Performance
+            };
+            (*cpu,kind)
+        })
+        .collect();
+
+    // every core alike is not hybrid
+    if kinds.values().all(|kind| *kind == CoreKind::Performance) {
+        return BTreeMap::new()
+    }
+    kinds
+}
+
+fn online(source: &SystemSource) -> Result<Vec<u32>, StatusError> {
+    parse_cpu_list(&status::read(source,CPU_ONLINE)?)
+        .ok_or_else(|| StatusError::Parse(source.path(CPU_ONLINE),"not a cpu list"))
+}
+
+impl CpuTopology {
+    pub fn read(source: &SystemSource) -> Result<Self, StatusError> {
+        let online = online(source)?;
+
+        let mut policies = Vec::new();
+        // no cpufreq directory is fine, the frequency comes from cpuinfo then
+        if let Ok(entries) = fs::read_dir(source.path(CPUFREQ)) {
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !name.starts_with("policy") {
+                    continue
+                }
+
+                let path = format!("{CPUFREQ}/{name}");
+                let Some(cpus) = source.read(format!("{path}/related_cpus")).ok()
+                    .and_then(|list| parse_cpu_list(&list)) else {
+                    continue
+                };
+                policies.push(Policy {
+                    max_freq: number(source,&format!("{path}/cpuinfo_max_freq")),
+                    name,
+                    cpus,
+                });
+            }
+        }
+        policies.sort_by_key(|policy| policy.cpus.first().copied());
+
+        let capacities: Vec<(u32,u64)> = online.iter()
+            .filter_map(|cpu| Some((*cpu,number(source,&format!("/sys/devices/system/cpu/cpu{cpu}/cpu_capacity"))?)))
+            .collect();
+
+        let kinds = if capacities.len() == online.len() && !capacities.is_empty() {
+            classify(capacities.into_iter())
+        } else {
+            classify(policies.iter()
+                .filter_map(|policy| Some((policy,policy.max_freq?)))
+                .flat_map(|(policy,max_freq)| policy.cpus.iter().map(move |cpu| (*cpu,max_freq))))
+        };
+
+        Ok(CpuTopology { online, policies, kinds })
+    }
+
+    // current frequency of every online core, (cpu, kHz)
+    fn current(&self, source: &SystemSource) -> Result<Vec<(u32,u64)>, StatusError> {
+        if self.policies.is_empty() {
+            return cpuinfo_frequencies(source)
+        }
+
+        /* cores are hotplugged at any time, a cluster with every core offline
+         * still has its policy and reading it fails with EBUSY
+         */
+        let online = online(source)?;
+        let active = self.policies.iter()
+            .filter(|policy| policy.cpus.iter().any(|cpu| online.contains(cpu)));
+
+        let mut current = Vec::new();
+        for policy in active {
+            let path = format!("{CPUFREQ}/{}/scaling_cur_freq",policy.name);
+            let freq = status::read(source,&path)?
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| StatusError::Parse(source.path(&path),"not a frequency"))?;
+
+            current.extend(policy.cpus.iter()
+                .filter(|cpu| online.contains(cpu))
+                .map(|cpu| (*cpu,freq)));
+        }
+        Ok(current)
+    }
+
+    pub fn frequencies(&self, source: &SystemSource) -> Result<Frequencies, StatusError> {
+        let current = self.current(source)?;
+        if current.is_empty() {
+            return Err(StatusError::Parse(source.path(CPUFREQ),"no cpu frequencies"))
+        }
+
+        let average = |freqs: &[u64]| match freqs.len() {
+            0 => None,
+            len => Some(freqs.iter().sum::<u64>() / len as u64),
+        };
+        let of_kind = |kind| -> Vec<u64> {
+            current.iter()
+                .filter(|(cpu,_)| self.kinds.get(cpu) == Some(&kind))
+                .map(|(_,freq)| *freq)
+                .collect()
+        };
+        let all: Vec<u64> = current.iter().map(|(_,freq)| *freq).collect();
+
+        Ok(Frequencies {
+            min: all.iter().copied().min().unwrap_or(0),
+            avg: average(&all).unwrap_or(0),
+            max: all.iter().copied().max().unwrap_or(0),
+            performance: average(&of_kind(CoreKind::Performance)),
+            efficiency: average(&of_kind(CoreKind::Efficiency)),
+        })
+    }
+}
+
+// "cpu MHz : 2994.374" for each processor, in kHz
+fn cpuinfo_frequencies(source: &SystemSource) -> Result<Vec<(u32,u64)>, StatusError> {
+    let cpuinfo = status::read(source,CPU_INFO)?;
+    let mut processor = None;
+    let mut current = Vec::new();
+
+    for line in cpuinfo.lines() {
+        let Some((key,value)) = line.split_once(':') else {
+            continue
+        };
+        match key.trim() {
+            "processor" => processor = value.trim().parse().ok(),
+            "cpu MHz" => {
+                if let (Some(cpu),Ok(mhz)) = (processor,value.trim().parse::<f64>()) {
+                    current.push((cpu,(mhz * 1000.0) as u64));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok(current)
+}
+
+pub fn format_frequency(khz: u64) -> String {
+    let ghz = khz as f64 / 1_000_000.0;
+    if ghz < 1.0 {
+        format!("{:.0} MHz",ghz * 1000.0)
+    } else {
+        format!("{:.1} GHz",ghz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 12th gen intel: 6 hyperthreaded p-cores then 4 e-cores, policies only
+    const LAPTOP: &str = "tests/fixtures/system/laptop";
+    // big.LITTLE phone soc with cpu_capacity, the last two big cores offline
+    const ARM: &str = "tests/fixtures/system/arm-big-little";
+    // the same with cpu4-7 hotplugged off
+    const ARM_BIG_OFFLINE: &str = "tests/fixtures/system/arm-big-offline";
+    // a vm without cpufreq
+    const SERVER: &str = "tests/fixtures/system/server-64";
+
+    #[test]
+    fn cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), Some(vec![0,1,2,3,8,10,11]));
+        assert_eq!(parse_cpu_list("0"), Some(vec![0]));
+        assert_eq!(parse_cpu_list("\n"), Some(vec![]));
+        assert_eq!(parse_cpu_list("0-a"), None);
+    }
+
+    #[test]
+    fn hybrid_from_policies() {
+        let source = SystemSource::new(LAPTOP);
+        let topology = CpuTopology::read(&source).unwrap();
+
+        assert_eq!(topology.online, (0..16).collect::<Vec<_>>());
+        assert_eq!(topology.kinds.get(&0), Some(&CoreKind::Performance));
+        // the favoured cores boost a little higher, they are still p-cores
+        assert_eq!(topology.kinds.get(&11), Some(&CoreKind::Performance));
+        assert_eq!(topology.kinds.get(&12), Some(&CoreKind::Efficiency));
+
+        assert_eq!(topology.frequencies(&source).unwrap(), Frequencies {
+            min: 1_200_000,
+            avg: 1_800_000,
+            max: 2_400_000,
+            performance: Some(2_000_000),
+            efficiency: Some(1_200_000),
+        });
+    }
+
+    #[test]
+    fn hybrid_from_capacity() {
+        let source = SystemSource::new(ARM);
+        let topology = CpuTopology::read(&source).unwrap();
+
+        assert_eq!(topology.online, vec![0,1,2,3,4,5]);
+        assert_eq!(topology.kinds.get(&3), Some(&CoreKind::Efficiency));
+        assert_eq!(topology.kinds.get(&4), Some(&CoreKind::Performance));
+
+        // offline cores do not count towards the average
+        let frequencies = topology.frequencies(&source).unwrap();
+        assert_eq!(frequencies.avg, 2_000_000);
+        assert_eq!(frequencies.performance, Some(2_400_000));
+        assert_eq!(frequencies.efficiency, Some(1_800_000));
+    }
+
+    #[test]
+    fn offline_cluster() {
+        let source = SystemSource::new(ARM_BIG_OFFLINE);
+        let topology = CpuTopology::read(&source).unwrap();
+        assert_eq!(topology.online, vec![0,1,2,3]);
+
+        // only little cores are left, nothing to split
+        assert_eq!(topology.frequencies(&source).unwrap(), Frequencies {
+            min: 1_800_000,
+            avg: 1_800_000,
+            max: 1_800_000,
+            performance: None,
+            efficiency: None,
+        });
+    }
+
+    #[test]
+    fn cluster_goes_offline() {
+        let topology = CpuTopology::read(&SystemSource::new(ARM)).unwrap();
+
+        // the big cores are switched off after the topology was read
+        let frequencies = topology.frequencies(&SystemSource::new(ARM_BIG_OFFLINE)).unwrap();
+        assert_eq!(frequencies.avg, 1_800_000);
+        assert_eq!(frequencies.performance, None);
+        assert_eq!(frequencies.efficiency, Some(1_800_000));
+    }
+
+    #[test]
+    fn cpuinfo_without_cpufreq() {
+        let source = SystemSource::new(SERVER);
+        let topology = CpuTopology::read(&source).unwrap();
+
+        assert_eq!(topology.online.len(), 64);
+        assert!(topology.kinds.is_empty());
+
+        let frequencies = topology.frequencies(&source).unwrap();
+        assert_eq!((frequencies.min,frequencies.max), (1_500_000,3_500_000));
+        assert_eq!(frequencies.performance, None);
+    }
+
+    #[test]
+    fn frequency_text() {
+        assert_eq!(format_frequency(800_000), "800 MHz");
+        assert_eq!(format_frequency(1_850_000), "1.9 GHz");
+    }
+}
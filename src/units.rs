@@ -0,0 +1,40 @@
+/* byte counts for labels and tooltips, in powers of 1024
+ * whole bytes, then one decimal from KiB up
+ */
+
+// bytes per second, e.g. "1.2 MiB/s"
+pub fn format_rate(rate: f64) -> String {
+    format!("{}/s",format_bytes(rate))
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str;5] = ["B","KiB","MiB","GiB","TiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value:.0} {}",UNITS[unit])
+    } else {
+        format!("{value:.1} {}",UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_units() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(100.0 * 1024.0 * 1024.0 * 1024.0), "100.0 GiB");
+        assert_eq!(format_bytes(2048.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0), "2048.0 TiB");
+        assert_eq!(format_rate(524300.0), "512.0 KiB/s");
+        assert_eq!(format_rate(26214.0), "25.6 KiB/s");
+    }
+}
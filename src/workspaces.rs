@@ -1,280 +1,175 @@
-use std::{
-    io::{Write,Read,BufRead,BufReader},
-    env,
-    os::unix::net::UnixStream,
-    iter::Peekable,
-    str::SplitWhitespace,
-    collections::BTreeMap,
-};
+use std::collections::BTreeMap;
 
-const EVENTS: [&str;5] = [
-            "workspace",
-            "activewindow",
-            "openwindow",
-            "closewindow",
-            "movewindow",
-];
+use crate::hyprland::{
+    self,
+    Client,
+    Monitor,
+};
 
-pub const WORKSPACE_COUNT: usize = 9;
+pub const WORKSPACE_COUNT: i32 = 9;
 
 /* for this program:
- *  tag: the index of the workspace
+ *  tag: the id of the workspace
  *  workspace: the actual thing containing the windows
  */
 
-/* Window: metadata assoicated to an open window on hyprland
- *
- * name: the desktop name of the program
- * info: the secondary title or "information" of the window
- * pid: pid associated with the window open
- * tag: the workspace index th window exists on
- * order: 0 meaning active, the order of when it was used
- */
-pub struct Window {
-    pub name: String,
-    pub info: String,
-    pub address: String,
-    pub class: String,
-    pub tag: usize,
-    pub order: usize 
-}
-
-// sorted by order (revent activity)
-pub type AllWindows = Vec<Window>;
-
  /* windows: vector of windows in order of activity
   * order: the order of this workspace in activity
+  * monitor: name of the output the workspace is on
+  * active: workspace currently shown on its monitor
  */
 pub struct Workspace {
-    pub windows: Vec<Window>,
-    pub tag: usize,
-    pub order:  usize,
-    pub active: bool 
+    pub windows: Vec<Client>,
+    pub tag: i32,
+    pub monitor: String,
+    pub order: usize,
+    pub active: bool
 }
 
-pub type Workspaces = BTreeMap<usize,Workspace>;
+pub type Workspaces = BTreeMap<i32,Workspace>;
 
-fn get_hyprland_sock(num: Option<&str>) -> UnixStream {
-    UnixStream::connect(
-        format!("{}/hypr/{}/.socket{}.sock",
-            env::var("XDG_RUNTIME_DIR").unwrap(),
-            env::var("HYPRLAND_INSTANCE_SIGNATURE").unwrap(),
-            num.unwrap_or_default()
-        )).unwrap()
-}
-
-fn peek_until_newline<'a>(iter: &mut Peekable<SplitWhitespace<'a>>,next_line: &str) -> String {
-    let mut result = Vec::new();
-    while let Some(&word) = iter.peek() {
-        if word.contains(next_line) {
-            let parts: Vec<&str> = word.split(next_line).collect();
-            result.push(parts[0]); 
-            break;
-        }
-        result.push(word);
-        iter.next();
+impl Workspace {
+    // true if the workspace is on the output, or the output name is unknown
+    pub fn on_monitor(&self, connector: Option<&str>) -> bool {
+        connector.is_none_or(|connector| self.monitor == connector)
     }
-    result.join(" ")
 }
 
-fn get_windows() -> Vec<Window>{
-    let mut sock = get_hyprland_sock(None);
-
-    let _ = sock.write_all(b"clients");
-
-    let mut buff = String::new();
-    sock.read_to_string(&mut buff).unwrap();
-
-
-    let mut iter = buff.split_whitespace().peekable();
-
-    let mut done = false;
-    let mut all_windows: Vec<Window>  = Vec::new();
-    let mut name = String::with_capacity(32);
-    let mut info = String::with_capacity(64);
-    let mut class = String::with_capacity(32);
-    let mut address = String::with_capacity(8);
-    let mut tag = usize::default();
-    let mut order = usize::default();
-
-    while let Some(key) = iter.next() {
-        match key {
-            "workspace:" => {
-                tag = iter.peek().unwrap().parse().unwrap();
-            },
-            "title:" => {
-                info = peek_until_newline(&mut iter,"initialClass:").trim_end().to_string();
-                info = match info {
-                    init_class if init_class.contains(".pdf") =>
-                        init_class.split("/").last().expect("PDF Document").to_string(),
-                    _ => info
-                }
-            },
-            "initialTitle:" => {
-                name = peek_until_newline(&mut iter,"pid:").trim_end().to_string();
-                // special cases for ugly initialTitles
-                name = match name {
-                    title if title.contains("Chromium") =>  "Chromium".to_string(),
-                    title if title.contains("OBS") =>  "OBS Studio".to_string(),
-                    title if title.contains(".pdf") => 
-                        title.split("/").last().expect("Document").to_string(),
-                    title if title.contains("WhatsApp") => "WhatsApp".to_string(),
-                    
-                    _ => name 
-                        
-                }
-            }
-            "focusHistoryID:" => {
-                order = iter.peek().unwrap().parse().unwrap();
-                done = true;
-            },
-            
-            "Window" => {
-                address = iter.peek().unwrap().to_string();
-            },
-
-            "class:" => {
-                class = peek_until_newline(&mut iter, "title:").trim_end().to_string();
-            },
-
-            _ => {}
-        };
-
-        if done == true{
-            all_windows.push( Window{
-                 name: name.clone(),
-                 info: info.clone(),
-                 address: address.clone(),
-                 class: class.clone(),
-                 tag,
-                 order
-            });
-            done = false;
-        };
-    }
-    all_windows.sort_by(|win1,win2| win1.order.cmp(&win2.order));
-    all_windows
+// tags of the workspaces on the output, lowest first
+pub fn tags_on(workspaces: &Workspaces, connector: Option<&str>) -> Vec<i32> {
+    workspaces.values()
+        .filter(|workspace| workspace.on_monitor(connector))
+        .map(|workspace| workspace.tag)
+        .collect()
 }
 
-fn assign_tags_to_win(all_wins: AllWindows) -> Workspaces {
-    let mut workspaces: Workspaces = BTreeMap::new();
-
-    let mut order:usize = 0;
-    for window in all_wins{
-        workspaces.entry(window.tag)
-            .or_insert( (|| { 
-                let w = Workspace {
-                    windows: Vec::new(),
-                    active: window.order == 0,
-                    tag: window.tag,
-                    order,
-                };
-                order += 1;
-                w
-            })()).windows.push(window);
-    }
-   workspaces 
+/* the workspace after (or before) the one shown on `connector`, going round its tags
+ * as `workspace m+1` would on that output whichever one has the focus
+ */
+pub fn step(workspaces: &Workspaces, connector: &str, forward: bool) -> Option<i32> {
+    let tags = tags_on(workspaces,Some(connector));
+    let current = tags.iter().position(|tag| workspaces[tag].active)?;
+    let next = match forward {
+        true => (current + 1) % tags.len(),
+        false => (current + tags.len() - 1) % tags.len(),
+    };
+    (next != current).then_some(tags[next])
 }
 
-/* read hyprland socket2 to see if there is 
- * activity on workspace or winndow change
-*/ 
-pub fn is_activity()  -> bool {
-    let sock = get_hyprland_sock(Some("2"));
-
-    let mut buffer = String::new();
-    // for some reason reading socket2 must be buffered
-    let mut reader = BufReader::new(sock);
-    loop {
-        match reader.read_line(&mut buffer) {
-            Ok(_) =>{
-                for line in buffer.lines() {
-                    
-                    let event = line 
-                        .split(">>")
-                        .next()
-                        .unwrap();
-
-                    if EVENTS.contains(&event) {
-                        return true
-                    }
-                }
-            },
-            Err(_) => {}
+/* group clients into their workspaces
+ * special (scratchpad) workspaces have negative ids and are left out
+ */
+pub fn assign_clients(
+    all_workspaces: Vec<hyprland::Workspace>,
+    mut clients: Vec<Client>,
+    monitors: &[Monitor]) -> Workspaces {
+
+    let active_tags: Vec<i32> = monitors.iter()
+        .map(|monitor| monitor.active_workspace.id)
+        .collect();
+
+    let mut workspaces: Workspaces = all_workspaces
+        .into_iter()
+        .filter(|workspace| workspace.id > 0)
+        .map(|workspace| (workspace.id, Workspace {
+            windows: Vec::new(),
+            tag: workspace.id,
+            monitor: workspace.monitor,
+            order: usize::MAX,
+            active: active_tags.contains(&workspace.id)
+        }))
+        .collect();
+
+    clients.sort_by_key(|client| client.focus_history_id);
+
+    for client in clients {
+        if let Some(workspace) = workspaces.get_mut(&client.workspace.id) {
+            workspace.windows.push(client);
         }
     }
 
-}
-
-pub fn switch_window(adr: &String) {
-    
-    let mut sock = get_hyprland_sock(None);
-    
-    let _ = sock.write_all(format!(
-            "dispatch focuswindow address:0x{adr}"
-    ).as_bytes());
-
-}
-
-pub fn switch_workspace(tag: usize) {
-
-    let mut sock = get_hyprland_sock(None);
+    // active first, then by most recently focused window
+    let mut recent: Vec<(bool,i32,i32)> = workspaces.values()
+        .map(|workspace| (
+            !workspace.active,
+            workspace.windows.first()
+                .map(|window| window.focus_history_id)
+                .unwrap_or(i32::MAX),
+            workspace.tag
+        ))
+        .collect();
+    recent.sort();
+
+    for (order,(_,_,tag)) in recent.into_iter().enumerate() {
+        if let Some(workspace) = workspaces.get_mut(&tag) {
+            workspace.order = order;
+        }
+    }
 
-    let _ = sock.write_all(format!(
-            "dispatch workspace {tag}"
-    ).as_bytes());
-   
+    workspaces
 }
 
-fn check_empty_active_workspace(workspaces: &mut Workspaces) {
-    let mut sock = get_hyprland_sock(None);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let _ = sock.write_all(b"activeworkspace");
+    fn fixture_workspaces() -> Workspaces {
+        assign_clients(
+            serde_json::from_str(include_str!("../tests/fixtures/hyprland/workspaces.json")).unwrap(),
+            serde_json::from_str(include_str!("../tests/fixtures/hyprland/clients.json")).unwrap(),
+            &serde_json::from_str::<Vec<Monitor>>(include_str!("../tests/fixtures/hyprland/monitors.json")).unwrap())
+    }
 
-    let mut buff = String::new();
-    sock.read_to_string(&mut buff).unwrap();
+    #[test]
+    fn clients_grouped_by_workspace() {
+        let workspaces = fixture_workspaces();
 
+        // special workspace is not shown
+        assert_eq!(workspaces.keys().copied().collect::<Vec<_>>(), vec![1,3,4]);
 
-    let mut iter = buff.split_whitespace().peekable();
+        let addresses: Vec<&str> = workspaces[&1].windows.iter()
+            .map(|window| window.address.as_str())
+            .collect();
+        assert_eq!(addresses, vec!["0x5c1a0e5b3c80","0x5c1a0e4f1a20"]);
+        assert!(workspaces[&3].windows.is_empty());
+        assert_eq!(workspaces[&4].monitor, "DP-1");
+    }
 
-    let mut tag  = usize::default();
+    #[test]
+    fn active_workspace_per_monitor() {
+        let workspaces = fixture_workspaces();
 
-    while let Some(key) = iter.next() {
-        match key {
-            "ID" => {
-                tag = iter.peek().unwrap().parse().unwrap();
-            },
+        assert!(workspaces[&1].active);
+        assert!(workspaces[&4].active);
+        assert!(!workspaces[&3].active);
 
-            _ => {}
-        };
+        assert_eq!(workspaces[&1].order, 0);
+        assert_eq!(workspaces[&4].order, 1);
+        assert_eq!(workspaces[&3].order, 2);
     }
 
-    workspaces.entry(tag)
-        .or_insert( (|| { 
-            let w = Workspace {
-                windows: Vec::new(),
-                active:  true,
-                tag,
-                order: 0,
-            };
-            w
-        })());
-
-    for (_,workspace) in workspaces {
-        if workspace.tag != tag {
-            workspace.order += 1;
-            workspace.active = false;
-        }
+    #[test]
+    fn tags_per_monitor() {
+        let mut workspaces = fixture_workspaces();
+        // a second monitor on workspaces 11 and up
+        workspaces.insert(12,Workspace {
+            windows: Vec::new(),
+            tag: 12,
+            monitor: "DP-1".to_string(),
+            order: 3,
+            active: false,
+        });
+
+        assert_eq!(tags_on(&workspaces,Some("eDP-1")), vec![1,3]);
+        assert_eq!(tags_on(&workspaces,Some("DP-1")), vec![4,12]);
+        assert_eq!(tags_on(&workspaces,None), vec![1,3,4,12]);
+
+        // round the output's own workspaces, from the one it shows
+        assert_eq!(step(&workspaces,"DP-1",true), Some(12));
+        assert_eq!(step(&workspaces,"DP-1",false), Some(12));
+        assert_eq!(step(&workspaces,"eDP-1",false), Some(3));
+        workspaces.remove(&12);
+        assert_eq!(step(&workspaces,"DP-1",true), None);
+        assert_eq!(step(&workspaces,"HDMI-A-1",true), None);
     }
-
-
-}
-
-pub fn get_workspaces() -> Workspaces {
-
-    let all_windows = get_windows();
-    let mut workspaces = assign_tags_to_win(all_windows);
-    check_empty_active_workspace(&mut workspaces);
-
-    workspaces
 }